@@ -0,0 +1,200 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+pub const ACTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Same windows as the renderer's status dots: a tab with no output for
+/// this long is stale, and after the long window it is long-idle.
+const STALE_AFTER: Duration = Duration::from_secs(10);
+const LONG_IDLE_AFTER: Duration = Duration::from_secs(60 * 60);
+/// Weight of the newest sample in the smoothed byte rate.
+const RATE_SMOOTHING: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityState {
+    /// No output since the terminal was assigned.
+    Idle,
+    Active,
+    Stale,
+    LongIdle,
+}
+
+/// Where the previous chunk left off inside an escape sequence.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    Ground,
+    Escape,
+    Csi,
+    /// OSC, DCS, APC, PM or SOS payload, ended by BEL or ST.
+    String,
+    StringEscape,
+}
+
+/// Output statistics for one session, updated by its reader thread and
+/// sampled by the activity monitor.
+pub struct ActivityTracker {
+    state: ActivityState,
+    scan: ScanState,
+    last_output_at: Option<Instant>,
+    last_output_at_ms: Option<u64>,
+    total_bytes: u64,
+    pending_bytes: u64,
+    bytes_per_sec: f64,
+    last_sample_at: Instant,
+}
+
+impl ActivityTracker {
+    pub fn new(now: Instant) -> Self {
+        ActivityTracker {
+            state: ActivityState::Idle,
+            scan: ScanState::Ground,
+            last_output_at: None,
+            last_output_at_ms: None,
+            total_bytes: 0,
+            pending_bytes: 0,
+            bytes_per_sec: 0.0,
+            last_sample_at: now,
+        }
+    }
+
+    /// Count a chunk of output.  Chunks made only of escape sequences and
+    /// control characters (cursor blinks, redraws after focus changes, title
+    /// updates) do not make the terminal active.
+    pub fn record_output(&mut self, bytes: &[u8], now: Instant, now_ms: u64) {
+        self.total_bytes += bytes.len() as u64;
+        self.pending_bytes += bytes.len() as u64;
+        if self.scan_printable(bytes) {
+            self.last_output_at = Some(now);
+            self.last_output_at_ms = Some(now_ms);
+        }
+    }
+
+    /// Whether `bytes` print anything outside escape sequences.  Sequences
+    /// may span chunks, so the scan state carries over.
+    fn scan_printable(&mut self, bytes: &[u8]) -> bool {
+        let mut printable = false;
+        for &byte in bytes {
+            self.scan = match (self.scan, byte) {
+                (ScanState::Ground, 0x1b) => ScanState::Escape,
+                (ScanState::Ground, byte) => {
+                    printable |= byte >= 0x20 && byte != 0x7f;
+                    ScanState::Ground
+                }
+                (ScanState::Escape, b'[') => ScanState::Csi,
+                (ScanState::Escape, b']' | b'P' | b'_' | b'^' | b'X') => ScanState::String,
+                // Intermediate bytes, e.g. the `(` of a charset selection.
+                (ScanState::Escape, 0x20..=0x2f) => ScanState::Escape,
+                (ScanState::Escape, _) => ScanState::Ground,
+                (ScanState::Csi, 0x40..=0x7e) => ScanState::Ground,
+                (ScanState::Csi, _) => ScanState::Csi,
+                (ScanState::String, 0x07) => ScanState::Ground,
+                (ScanState::String, 0x1b) => ScanState::StringEscape,
+                (ScanState::String, _) => ScanState::String,
+                (ScanState::StringEscape, b'\\') => ScanState::Ground,
+                (ScanState::StringEscape, _) => ScanState::String,
+            };
+        }
+        printable
+    }
+
+    /// Fold output since the previous sample into the byte rate and move to
+    /// the state implied by the time since the last output.  Returns the new
+    /// state when it changed.
+    pub fn sample(&mut self, now: Instant) -> Option<ActivityState> {
+        let elapsed = now.duration_since(self.last_sample_at).as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = self.pending_bytes as f64 / elapsed;
+            self.bytes_per_sec = RATE_SMOOTHING * rate + (1.0 - RATE_SMOOTHING) * self.bytes_per_sec;
+            self.pending_bytes = 0;
+            self.last_sample_at = now;
+        }
+
+        let next = match self.last_output_at {
+            None => ActivityState::Idle,
+            Some(at) => {
+                let idle = now.duration_since(at);
+                if idle >= LONG_IDLE_AFTER {
+                    ActivityState::LongIdle
+                } else if idle >= STALE_AFTER {
+                    ActivityState::Stale
+                } else {
+                    ActivityState::Active
+                }
+            }
+        };
+        if next == self.state {
+            return None;
+        }
+        self.state = next;
+        Some(next)
+    }
+
+    pub fn snapshot(&self, terminal_id: &str) -> TerminalActivity {
+        TerminalActivity {
+            terminal_id: terminal_id.to_owned(),
+            state: self.state,
+            last_output_at_ms: self.last_output_at_ms,
+            total_bytes: self.total_bytes,
+            bytes_per_sec: self.bytes_per_sec.round() as u64,
+        }
+    }
+}
+
+/// Payload of `terminal-activity-changed` and of `get_terminal_activity`.
+#[derive(Clone, Serialize)]
+pub struct TerminalActivity {
+    pub terminal_id: String,
+    pub state: ActivityState,
+    pub last_output_at_ms: Option<u64>,
+    pub total_bytes: u64,
+    pub bytes_per_sec: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_through_idle_thresholds_and_back_on_output() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(start);
+        assert_eq!(tracker.sample(start + Duration::from_secs(1)), None);
+
+        tracker.record_output(&[b'x'; 2048], start + Duration::from_secs(1), 1_000);
+        assert_eq!(
+            tracker.sample(start + Duration::from_secs(2)),
+            Some(ActivityState::Active)
+        );
+        assert_eq!(tracker.snapshot("t").bytes_per_sec, 1024);
+
+        assert_eq!(
+            tracker.sample(start + Duration::from_secs(11)),
+            Some(ActivityState::Stale)
+        );
+        assert_eq!(
+            tracker.sample(start + Duration::from_secs(60 * 60 + 1)),
+            Some(ActivityState::LongIdle)
+        );
+
+        let later = start + Duration::from_secs(60 * 60 + 2);
+        tracker.record_output(b"0123456789", later, 2_000);
+        assert_eq!(tracker.sample(later), Some(ActivityState::Active));
+        assert_eq!(tracker.snapshot("t").total_bytes, 2058);
+    }
+
+    #[test]
+    fn control_only_chunks_do_not_count_as_output() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(start);
+
+        // Cursor blink, a focus-change redraw split across reads, a title.
+        tracker.record_output(b"\x1b[?25l\x1b[?25h\r", start, 1_000);
+        tracker.record_output(b"\x1b[?10", start, 1_000);
+        tracker.record_output(b"04h\x1b]0;vim\x1b\\\x07", start, 1_000);
+        assert_eq!(tracker.sample(start + Duration::from_secs(1)), None);
+        assert_eq!(tracker.snapshot("t").last_output_at_ms, None);
+
+        tracker.record_output(b"\x1b[1mdone\x1b[0m", start, 2_000);
+        assert_eq!(tracker.snapshot("t").last_output_at_ms, Some(2_000));
+    }
+}
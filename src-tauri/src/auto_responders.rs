@@ -0,0 +1,213 @@
+use crate::errors::PtyError;
+use crate::pattern_watchers::LineAssembler;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MIN_INTERVAL_MS: u64 = 2000;
+/// Floor for `min_interval_ms`, so a prompt that reprints itself after
+/// every answer cannot turn a rule into a tight loop.
+const MIN_INTERVAL_FLOOR: Duration = Duration::from_millis(250);
+
+/// An expect-style rule: when a line of output matches `pattern`, type
+/// `response` into the terminal.  Rules are opt-in per terminal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoResponder {
+    pub id: String,
+    pub pattern: String,
+    /// Written as-is, so include `\r` to press Enter.
+    pub response: String,
+    pub case_sensitive: bool,
+    /// Stop firing after this many responses; unlimited when unset.
+    pub max_fires: Option<u32>,
+    /// Minimum time between two responses of this rule.
+    pub min_interval_ms: u64,
+}
+
+impl Default for AutoResponder {
+    fn default() -> Self {
+        AutoResponder {
+            id: String::new(),
+            pattern: String::new(),
+            response: String::new(),
+            case_sensitive: false,
+            max_fires: None,
+            min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+        }
+    }
+}
+
+struct ResponderRule {
+    rule: AutoResponder,
+    regex: Regex,
+    fire_count: u32,
+    last_fired_at: Option<Instant>,
+    /// Line that last matched, so a prompt is answered once even while it
+    /// is still the unfinished line across several chunks.
+    last_matched_seq: Option<u64>,
+}
+
+impl ResponderRule {
+    fn can_fire(&self, now: Instant) -> bool {
+        if self.rule.max_fires.is_some_and(|max| self.fire_count >= max) {
+            return false;
+        }
+        let interval = Duration::from_millis(self.rule.min_interval_ms).max(MIN_INTERVAL_FLOOR);
+        self.last_fired_at
+            .is_none_or(|at| now.duration_since(at) >= interval)
+    }
+}
+
+/// A response the reader thread found; it is written (or skipped) by the
+/// manager, which can check the terminal's echo state.
+#[derive(Debug, Clone)]
+pub struct PendingResponse {
+    pub rule_id: String,
+    pub response: String,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoResponderStatus {
+    #[serde(flatten)]
+    pub rule: AutoResponder,
+    pub fire_count: u32,
+}
+
+/// Auto-responders of one terminal along with their fire counts.
+#[derive(Default)]
+pub struct ResponderSet {
+    rules: Vec<ResponderRule>,
+    lines: LineAssembler,
+}
+
+impl ResponderSet {
+    /// Replace the rules.  Fire counts carry over for rules that keep their
+    /// id, so re-sending the same list does not reset a max-fire limit.
+    pub fn set_rules(&mut self, rules: Vec<AutoResponder>) -> Result<(), PtyError> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        for rule in rules {
+            if rule.pattern.is_empty() || rule.response.is_empty() {
+                return Err(PtyError::from(format!(
+                    "Auto-responder {} needs a pattern and a response",
+                    rule.id
+                )));
+            }
+            let regex = RegexBuilder::new(&rule.pattern)
+                .case_insensitive(!rule.case_sensitive)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|err| {
+                    PtyError::from(format!("Invalid pattern for auto-responder {}: {}", rule.id, err))
+                })?;
+            let previous = self.rules.iter().find(|existing| existing.rule.id == rule.id);
+            compiled.push(ResponderRule {
+                fire_count: previous.map_or(0, |existing| existing.fire_count),
+                last_fired_at: previous.and_then(|existing| existing.last_fired_at),
+                last_matched_seq: previous.and_then(|existing| existing.last_matched_seq),
+                rule,
+                regex,
+            });
+        }
+        self.rules = compiled;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn status(&self) -> Vec<AutoResponderStatus> {
+        self.rules
+            .iter()
+            .map(|entry| AutoResponderStatus {
+                rule: entry.rule.clone(),
+                fire_count: entry.fire_count,
+            })
+            .collect()
+    }
+
+    /// Match a chunk of output against the rules.  At most one response per
+    /// rule is returned per chunk; limits are only charged by `claim_fire`.
+    pub fn scan(&mut self, bytes: &[u8], now: Instant) -> Vec<PendingResponse> {
+        let mut pending: Vec<PendingResponse> = Vec::new();
+        for (seq, line) in self.lines.feed(bytes) {
+            for entry in &mut self.rules {
+                if entry.last_matched_seq == Some(seq)
+                    || pending.iter().any(|response| response.rule_id == entry.rule.id)
+                    || !entry.can_fire(now)
+                    || !entry.regex.is_match(&line)
+                {
+                    continue;
+                }
+                entry.last_matched_seq = Some(seq);
+                pending.push(PendingResponse {
+                    rule_id: entry.rule.id.clone(),
+                    response: entry.rule.response.clone(),
+                    line: line.trim_end().to_owned(),
+                });
+            }
+        }
+        pending
+    }
+
+    /// Charge a response that is about to be written.  Returns the new fire
+    /// count, or `None` when the rule is gone or another response already
+    /// used up its interval or limit since the scan.
+    pub fn claim_fire(&mut self, rule_id: &str, now: Instant) -> Option<u32> {
+        let entry = self.rules.iter_mut().find(|entry| entry.rule.id == rule_id)?;
+        if !entry.can_fire(now) {
+            return None;
+        }
+        entry.fire_count += 1;
+        entry.last_fired_at = Some(now);
+        Some(entry.fire_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_each_prompt_once_within_limits() {
+        let mut set = ResponderSet::default();
+        set.set_rules(vec![AutoResponder {
+            id: "continue".to_string(),
+            pattern: r"Continue\? \[y/N\]".to_string(),
+            response: "y\r".to_string(),
+            max_fires: Some(2),
+            min_interval_ms: 1000,
+            ..AutoResponder::default()
+        }])
+        .unwrap();
+        let start = Instant::now();
+
+        let pending = set.scan(b"step 1\r\nContinue? [y/N] ", start);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].response, "y\r");
+        assert_eq!(pending[0].line, "Continue? [y/N]");
+        assert_eq!(set.claim_fire("continue", start), Some(1));
+        // A second response found before the first was written is dropped.
+        assert_eq!(set.claim_fire("continue", start), None);
+
+        // The same prompt echoing the answer is not answered again.
+        assert!(set.scan(b"y\r\n", start).is_empty());
+        // A new prompt inside the interval waits.
+        let soon = start + Duration::from_millis(500);
+        assert!(set.scan(b"Continue? [y/N] \r\n", soon).is_empty());
+
+        let later = start + Duration::from_secs(2);
+        assert_eq!(set.scan(b"Continue? [y/N] ", later).len(), 1);
+        assert_eq!(set.claim_fire("continue", later), Some(2));
+
+        let much_later = start + Duration::from_secs(10);
+        assert!(set.scan(b"\r\nContinue? [y/N] ", much_later).is_empty());
+
+        // Re-sending the rules keeps the count.
+        let rules = set.status().into_iter().map(|status| status.rule).collect();
+        set.set_rules(rules).unwrap();
+        assert_eq!(set.status()[0].fire_count, 2);
+    }
+}
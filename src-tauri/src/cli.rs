@@ -0,0 +1,420 @@
+use crate::control_protocol::{shell_join, PROJECT_ID_VAR, TERMINAL_ID_VAR};
+use serde_json::{json, Value};
+
+const USAGE: &str = "Usage: Dispatcher <command> [options]
+
+Commands:
+  new-tab [--project ID|NAME] [--cwd DIR] [-- COMMAND...]
+                          Open a tab, optionally typing COMMAND at its prompt
+  split [-t ID] [-h|-v]   Split a pane side by side (-h, default) or stacked (-v)
+  send-keys [-t ID] [-l] KEYS...
+                          Type KEYS; names like Enter, Tab or C-c are keys unless -l
+  rename [-t ID] TITLE    Rename a terminal
+  notes [-t ID] TEXT      Append a line to a terminal's notes
+  close [-t ID]           Close a terminal
+  capture [-t ID] [-S LINES]
+                          Print the screen, with LINES of scrollback above it
+  list                    List terminals: id, state, cwd
+  wait-for-exit [-t ID] [--timeout SECONDS]
+                          Wait for the shell to exit and exit with its status
+
+-t defaults to $DISPATCHER_TERMINAL_ID and --project to $DISPATCHER_PROJECT_ID.
+--json prints the raw result.";
+
+const COMMANDS: [&str; 9] = [
+    "new-tab",
+    "split",
+    "send-keys",
+    "rename",
+    "notes",
+    "close",
+    "capture",
+    "list",
+    "wait-for-exit",
+];
+
+/// How a command's result is printed.
+#[derive(Debug, PartialEq)]
+enum Output {
+    Nothing,
+    TerminalId,
+    Listing,
+    Text,
+    ExitCode,
+}
+
+#[derive(Debug, PartialEq)]
+struct CliRequest {
+    method: &'static str,
+    params: Value,
+    output: Output,
+    json: bool,
+}
+
+/// The terminal and project the CLI runs in, if any.
+#[derive(Default)]
+struct CliContext {
+    terminal_id: Option<String>,
+    project_id: Option<String>,
+}
+
+#[derive(Default)]
+struct ParsedArgs {
+    options: Vec<(String, String)>,
+    switches: Vec<String>,
+    positional: Vec<String>,
+    /// Everything after `--`.
+    trailing: Vec<String>,
+}
+
+impl ParsedArgs {
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn switch(&self, names: &[&str]) -> bool {
+        self.switches
+            .iter()
+            .any(|switch| names.contains(&switch.as_str()))
+    }
+}
+
+/// Split arguments into options taking a value, switches and positional
+/// arguments, rejecting anything `command` does not accept.
+fn parse_flags(
+    command: &str,
+    args: &[String],
+    valued: &[&str],
+    switches: &[&str],
+) -> Result<ParsedArgs, String> {
+    let mut parsed = ParsedArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.trailing = args.cloned().collect();
+            break;
+        }
+        if arg == "--json" || switches.contains(&arg.as_str()) {
+            parsed.switches.push(arg.clone());
+        } else if valued.contains(&arg.as_str()) {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{}: {} needs a value", command, arg))?;
+            parsed.options.push((arg.clone(), value.clone()));
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("{}: unknown option {}", command, arg));
+        } else {
+            parsed.positional.push(arg.clone());
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_args(args: &[String], context: &CliContext) -> Result<CliRequest, String> {
+    let (command, rest) = args.split_first().ok_or_else(|| String::from("no command"))?;
+    let command = command.as_str();
+    let (valued, switches): (&[&str], &[&str]) = match command {
+        "new-tab" => (&["--project", "--cwd"], &[]),
+        "split" => (&["-t"], &["-h", "--horizontal", "-v", "--vertical"]),
+        "send-keys" => (&["-t"], &["-l", "--literal"]),
+        "rename" | "notes" | "close" | "list" => (&["-t"], &[]),
+        "capture" => (&["-t", "-S"], &[]),
+        "wait-for-exit" => (&["-t", "--timeout"], &[]),
+        _ => return Err(format!("unknown command {}", command)),
+    };
+    let parsed = parse_flags(command, rest, valued, switches)?;
+    // Only send-keys and new-tab take arguments after `--`.
+    let mut positional = parsed.positional.clone();
+    if command != "new-tab" {
+        positional.extend(parsed.trailing.iter().cloned());
+    }
+
+    let target = || {
+        parsed
+            .option("-t")
+            .map(str::to_owned)
+            .or_else(|| context.terminal_id.clone())
+            .ok_or_else(|| format!("{}: no terminal; pass -t or run inside Dispatcher", command))
+    };
+    let text = || {
+        if positional.is_empty() {
+            Err(format!("{}: missing text", command))
+        } else {
+            Ok(positional.join(" "))
+        }
+    };
+    let no_positional = || match positional.first() {
+        Some(arg) => Err(format!("{}: unexpected argument {}", command, arg)),
+        None => Ok(()),
+    };
+
+    let (method, params, output) = match command {
+        "new-tab" => {
+            no_positional()?;
+            let project = parsed
+                .option("--project")
+                .map(str::to_owned)
+                .or_else(|| context.project_id.clone());
+            let command_line = (!parsed.trailing.is_empty()).then(|| shell_join(&parsed.trailing));
+            let params = json!({
+                "project": project,
+                "cwd": parsed.option("--cwd"),
+                "command": command_line,
+            });
+            ("new-tab", params, Output::TerminalId)
+        }
+        "split" => {
+            no_positional()?;
+            let direction = if parsed.switch(&["-v", "--vertical"]) {
+                "vertical"
+            } else {
+                "horizontal"
+            };
+            let params = json!({ "terminalId": target()?, "direction": direction });
+            ("split", params, Output::TerminalId)
+        }
+        "send-keys" => {
+            if positional.is_empty() {
+                return Err(String::from("send-keys: missing keys"));
+            }
+            let params = json!({
+                "terminalId": target()?,
+                "keys": positional,
+                "literal": parsed.switch(&["-l", "--literal"]),
+            });
+            ("send-keys", params, Output::Nothing)
+        }
+        "rename" => {
+            let params = json!({ "terminalId": target()?, "title": text()? });
+            ("rename", params, Output::Nothing)
+        }
+        "notes" => {
+            let params = json!({ "terminalId": target()?, "text": text()? });
+            ("append-notes", params, Output::Nothing)
+        }
+        "close" => {
+            no_positional()?;
+            ("close", json!({ "terminalId": target()? }), Output::Nothing)
+        }
+        "capture" => {
+            no_positional()?;
+            let history_lines = match parsed.option("-S") {
+                Some(lines) => lines
+                    .parse::<usize>()
+                    .map_err(|_| format!("capture: -S expects a line count, got {}", lines))?,
+                None => 0,
+            };
+            let params = json!({ "terminalId": target()?, "historyLines": history_lines });
+            ("capture", params, Output::Text)
+        }
+        "list" => {
+            no_positional()?;
+            ("list", json!({}), Output::Listing)
+        }
+        _ => {
+            no_positional()?;
+            let timeout_ms = match parsed.option("--timeout") {
+                Some(seconds) => Some(
+                    seconds
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|seconds| *seconds >= 0.0)
+                        .map(|seconds| (seconds * 1000.0) as u64)
+                        .ok_or_else(|| {
+                            format!("wait-for-exit: --timeout expects seconds, got {}", seconds)
+                        })?,
+                ),
+                None => None,
+            };
+            let params = json!({ "terminalId": target()?, "timeoutMs": timeout_ms });
+            ("wait-for-exit", params, Output::ExitCode)
+        }
+    };
+    Ok(CliRequest {
+        method,
+        params,
+        output,
+        json: parsed.switch(&["--json"]),
+    })
+}
+
+/// `Dispatcher <command> ...` talks to the running app instead of starting
+/// it.  Returns the process exit code, or `None` when the arguments are not
+/// a CLI command.
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let first = args.first()?.as_str();
+    if matches!(first, "help" | "--help") {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+    if !COMMANDS.contains(&first) {
+        return None;
+    }
+
+    let context = CliContext {
+        terminal_id: std::env::var(TERMINAL_ID_VAR).ok().filter(|id| !id.is_empty()),
+        project_id: std::env::var(PROJECT_ID_VAR).ok().filter(|id| !id.is_empty()),
+    };
+    let mut request = match parse_args(&args, &context) {
+        Ok(request) => request,
+        Err(err) => {
+            eprintln!("dispatcher: {}\n\n{}", err, USAGE);
+            return Some(2);
+        }
+    };
+    if let Err(err) = resolve_cwd(&mut request.params) {
+        eprintln!("dispatcher: {}", err);
+        return Some(1);
+    }
+    match call(request.method, &request.params) {
+        Ok(result) => Some(print_result(&request, &result)),
+        Err(err) => {
+            eprintln!("dispatcher: {}", err);
+            Some(1)
+        }
+    }
+}
+
+/// The app does not share the caller's working directory, so `--cwd` is
+/// made absolute here, where relative paths and symlinks mean what the
+/// caller meant.
+fn resolve_cwd(params: &mut Value) -> Result<(), String> {
+    let Some(cwd) = params["cwd"].as_str() else {
+        return Ok(());
+    };
+    let resolved = std::fs::canonicalize(cwd).map_err(|err| format!("--cwd {}: {}", cwd, err))?;
+    if !resolved.is_dir() {
+        return Err(format!("--cwd {}: not a directory", cwd));
+    }
+    params["cwd"] = json!(resolved.to_string_lossy());
+    Ok(())
+}
+
+fn print_result(request: &CliRequest, result: &Value) -> i32 {
+    if request.json {
+        println!("{}", result);
+    }
+    match request.output {
+        Output::Nothing => {}
+        Output::TerminalId if request.json => {}
+        Output::TerminalId => {
+            if let Some(terminal_id) = result["terminalId"].as_str() {
+                println!("{}", terminal_id);
+            }
+        }
+        Output::Listing if request.json => {}
+        Output::Listing => {
+            for terminal in result.as_array().into_iter().flatten() {
+                let state = match terminal["exitCode"].as_i64() {
+                    Some(code) => format!("{}({})", terminal["state"].as_str().unwrap_or(""), code),
+                    None => terminal["state"].as_str().unwrap_or("").to_owned(),
+                };
+                println!(
+                    "{}\t{}\t{}",
+                    terminal["terminalId"].as_str().unwrap_or(""),
+                    state,
+                    terminal["cwd"].as_str().unwrap_or("")
+                );
+            }
+        }
+        Output::Text if request.json => {}
+        Output::Text => print!("{}", result["text"].as_str().unwrap_or("")),
+        // Killed by a signal: no exit code of its own.
+        Output::ExitCode => return result["exitCode"].as_i64().map_or(1, |code| code as i32),
+    }
+    0
+}
+
+#[cfg(unix)]
+fn call(method: &str, params: &Value) -> Result<Value, String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let path = crate::control_protocol::client_socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| format!("cannot reach Dispatcher at {}: {}", path.display(), err))?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{}", request).map_err(|err| err.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(|err| err.to_string())?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|_| String::from("Dispatcher closed the connection"))?;
+    match response.get("error") {
+        Some(error) => Err(error["message"].as_str().unwrap_or("request failed").to_owned()),
+        None => Ok(response["result"].clone()),
+    }
+}
+
+#[cfg(not(unix))]
+fn call(_method: &str, _params: &Value) -> Result<Value, String> {
+    Err(String::from("the control socket is only available on Unix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliRequest, String> {
+        let context = CliContext {
+            terminal_id: Some("t1".to_string()),
+            project_id: Some("p1".to_string()),
+        };
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args, &context)
+    }
+
+    #[test]
+    fn maps_commands_to_requests() {
+        let request = parse(&["new-tab", "--cwd", "/work", "--", "npm", "run", "it's"]).unwrap();
+        assert_eq!(request.method, "new-tab");
+        assert_eq!(
+            request.params,
+            json!({ "project": "p1", "cwd": "/work", "command": "npm run 'it'\\''s'" })
+        );
+
+        let request = parse(&["send-keys", "-t", "t2", "-l", "--", "-x", "Enter"]).unwrap();
+        assert_eq!(
+            request.params,
+            json!({ "terminalId": "t2", "keys": ["-x", "Enter"], "literal": true })
+        );
+
+        let request = parse(&["split", "-v", "--json"]).unwrap();
+        assert_eq!(request.params, json!({ "terminalId": "t1", "direction": "vertical" }));
+        assert!(request.json);
+
+        assert_eq!(parse(&["notes", "build", "done"]).unwrap().params["text"], "build done");
+        assert_eq!(parse(&["capture", "-S", "200"]).unwrap().params["historyLines"], 200);
+        assert_eq!(
+            parse(&["wait-for-exit", "--timeout", "1.5"]).unwrap().params["timeoutMs"],
+            1500
+        );
+
+        assert!(parse(&["rename"]).is_err());
+        assert!(parse(&["close", "--force"]).is_err());
+        assert!(parse(&["capture", "-S", "lots"]).is_err());
+        assert!(parse_args(&["close".to_string()], &CliContext::default()).is_err());
+    }
+
+    #[test]
+    fn resolves_cwd_against_the_callers_directory() {
+        let here = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let mut params = json!({ "cwd": "." });
+        resolve_cwd(&mut params).unwrap();
+        assert_eq!(params["cwd"], json!(here.to_string_lossy()));
+
+        let mut params = json!({ "cwd": "./no/such/dir" });
+        assert!(resolve_cwd(&mut params).is_err());
+
+        let mut params = json!({ "cwd": null });
+        resolve_cwd(&mut params).unwrap();
+        assert!(params["cwd"].is_null());
+    }
+}
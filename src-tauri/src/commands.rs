@@ -1,11 +1,26 @@
+use crate::activity::TerminalActivity;
+use crate::auto_responders::{AutoResponder, AutoResponderStatus};
+use crate::env_scrub::EnvScrubRules;
 use crate::errors::PtyError;
-use crate::pty_manager::{PtyManager, TerminalDebugInfo, TerminalOutput};
+use crate::hibernation::HibernationConfig;
+use crate::orphan_tracker::OrphanedTerminal;
+use crate::pattern_watchers::PatternWatcher;
+use crate::project_env::ProjectEnvConfig;
+use crate::pty_manager::{
+    ProjectKey, PtyManager, SpawnEnvironmentPreview, TerminalDebugInfo, TerminalOutput,
+    TerminalSpawnOptions, TerminalSpawnRequest, TerminalSpawnResult,
+};
 use crate::renderer_watchdog::{RendererHeartbeatDetails, RendererWatchdog};
+use crate::session_store::LocalSessionRecord;
+use crate::terminal_export::{ExportFormat, ExportRange, ExportTheme};
+use crate::terminal_screen::TerminalScreen;
+use crate::terminal_search::{SearchOptions, TerminalSearchMatch};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::time::SystemTime;
 #[allow(unused_imports)]
-use tauri::{ipc::Channel, AppHandle, Manager, State};
+use tauri::{ipc::Channel, AppHandle, Manager, State, Webview};
 
 fn preview_terminal_data(data: &str, limit: usize) -> String {
     let mut preview = String::new();
@@ -32,14 +47,30 @@ fn preview_terminal_data(data: &str, limit: usize) -> String {
     preview
 }
 
+/// Run spawn work on a blocking worker.  Spawns can wait on a project
+/// activation, and a sync command would hold the main thread meanwhile.
+async fn on_worker<T, F>(app_handle: AppHandle, work: F) -> Result<T, PtyError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppHandle, &PtyManager) -> Result<T, PtyError> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<PtyManager>();
+        work(&app_handle, &state)
+    })
+    .await
+    .map_err(|err| PtyError::from(err.to_string()))?
+}
+
 #[tauri::command]
-pub fn create_terminal(
+#[allow(clippy::too_many_arguments)]
+pub async fn create_terminal(
     app_handle: AppHandle,
-    state: State<'_, PtyManager>,
     terminal_id: String,
     cwd: Option<String>,
     cols: u16,
     rows: u16,
+    options: Option<TerminalSpawnOptions>,
     on_output: Channel<TerminalOutput>,
 ) -> Result<(), PtyError> {
     let _ = crate::debug_log::append_debug_log(&format!(
@@ -47,8 +78,19 @@ pub fn create_terminal(
         terminal_id, cwd, cols, rows
     ));
 
-    let result =
-        state.create_terminal(&app_handle, terminal_id.clone(), cwd, cols, rows, on_output);
+    let id = terminal_id.clone();
+    let result = on_worker(app_handle, move |app_handle, state| {
+        state.create_terminal(
+            app_handle,
+            id,
+            cwd,
+            cols,
+            rows,
+            options.unwrap_or_default(),
+            on_output,
+        )
+    })
+    .await;
     if let Err(err) = &result {
         let _ = crate::debug_log::append_debug_log(&format!(
             "[backend:create_terminal:error] terminal_id={} error={}",
@@ -58,20 +100,259 @@ pub fn create_terminal(
     result
 }
 
+#[tauri::command]
+pub async fn create_terminals(
+    app_handle: AppHandle,
+    webview: Webview,
+    terminals: Vec<TerminalSpawnRequest>,
+) -> Result<Vec<TerminalSpawnResult>, PtyError> {
+    let requested = terminals.len();
+    let deferred = terminals.iter().filter(|request| request.deferred).count();
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:create_terminals] requested={} deferred={}",
+        requested, deferred
+    ));
+
+    let results = on_worker(app_handle, move |app_handle, state| {
+        Ok(state.create_terminals(app_handle, &webview, terminals))
+    })
+    .await?;
+    for result in results.iter().filter(|result| result.error.is_some()) {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:create_terminals:error] terminal_id={} error={}",
+            result.terminal_id,
+            result.error.as_deref().unwrap_or_default()
+        ));
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+pub fn get_restorable_terminals(
+    state: State<'_, PtyManager>,
+) -> Result<Vec<LocalSessionRecord>, PtyError> {
+    Ok(state.restorable_terminals())
+}
+
+#[tauri::command]
+pub fn get_hibernation_config(state: State<'_, PtyManager>) -> Result<HibernationConfig, PtyError> {
+    Ok(state.hibernation_config())
+}
+
+#[tauri::command]
+pub fn set_hibernation_config(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    config: HibernationConfig,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_hibernation_config] enabled={} idle_after_secs={}",
+        config.enabled, config.idle_after_secs
+    ));
+    state.set_hibernation_config(&app_handle, config)
+}
+
+#[tauri::command]
+pub fn get_pattern_watchers(state: State<'_, PtyManager>) -> Result<Vec<PatternWatcher>, PtyError> {
+    Ok(state.pattern_watchers())
+}
+
+#[tauri::command]
+pub fn set_pattern_watchers(
+    state: State<'_, PtyManager>,
+    watchers: Vec<PatternWatcher>,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_pattern_watchers] count={}",
+        watchers.len()
+    ));
+    state.set_pattern_watchers(watchers)
+}
+
+#[tauri::command]
+pub fn get_auto_responders(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+) -> Result<Vec<AutoResponderStatus>, PtyError> {
+    state.auto_responders(&terminal_id)
+}
+
+#[tauri::command]
+pub fn set_auto_responders(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+    rules: Vec<AutoResponder>,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_auto_responders] terminal_id={} rule_ids={}",
+        terminal_id,
+        rules
+            .iter()
+            .map(|rule| rule.id.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    ));
+    state.set_auto_responders(&terminal_id, rules)
+}
+
+#[tauri::command]
+pub fn get_terminal_encoding(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+) -> Result<Option<String>, PtyError> {
+    state.terminal_encoding(&terminal_id)
+}
+
+#[tauri::command]
+pub fn set_terminal_encoding(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+    encoding: Option<String>,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_terminal_encoding] terminal_id={} encoding={:?}",
+        terminal_id, encoding
+    ));
+    state.set_terminal_encoding(&terminal_id, encoding)
+}
+
+#[tauri::command]
+pub async fn restart_terminal_safe_mode(
+    app_handle: AppHandle,
+    terminal_id: String,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:restart_terminal_safe_mode] terminal_id={}",
+        terminal_id
+    ));
+    on_worker(app_handle, move |app_handle, state| {
+        state.restart_terminal_safe_mode(app_handle, &terminal_id)
+    })
+    .await
+}
+
+/// The renderer's answer to a `terminal-control-request`.
+#[tauri::command]
+pub fn complete_control_request(
+    request_id: u64,
+    error: Option<String>,
+    terminal_id: Option<String>,
+) {
+    crate::control_socket::complete_request(
+        request_id,
+        crate::control_socket::RendererReply { error, terminal_id },
+    );
+}
+
+#[tauri::command]
+pub fn get_env_scrub_rules(state: State<'_, PtyManager>) -> Result<EnvScrubRules, PtyError> {
+    Ok(state.env_scrub_rules())
+}
+
+#[tauri::command]
+pub fn set_env_scrub_rules(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    rules: EnvScrubRules,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_env_scrub_rules] exact_names={} prefixes={} value_rules={} allow={}",
+        rules.exact_names.len(),
+        rules.prefixes.len(),
+        rules.value_rules.len(),
+        rules.allow.len()
+    ));
+    state.set_env_scrub_rules(&app_handle, rules)
+}
+
+#[tauri::command]
+pub fn get_project_env_config(
+    state: State<'_, PtyManager>,
+    project_id: String,
+) -> Result<ProjectEnvConfig, PtyError> {
+    Ok(state.project_env_config(&project_id))
+}
+
+#[tauri::command]
+pub fn set_project_env_config(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    project_id: String,
+    config: ProjectEnvConfig,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:set_project_env_config] project_id={} env_files={} env={}",
+        project_id,
+        config.env_files.join(","),
+        config.env.len()
+    ));
+    state.set_project_env_config(&app_handle, &project_id, config)
+}
+
+#[tauri::command]
+pub async fn preview_spawn_environment(
+    app_handle: AppHandle,
+    project_id: Option<String>,
+    project_root: Option<String>,
+    env: Option<HashMap<String, String>>,
+) -> Result<SpawnEnvironmentPreview, PtyError> {
+    on_worker(app_handle, move |_, state| {
+        state.preview_spawn_environment(project_id, project_root, env.unwrap_or_default())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn activate_terminal(
+    app_handle: AppHandle,
+    terminal_id: String,
+) -> Result<bool, PtyError> {
+    let id = terminal_id.clone();
+    let result = on_worker(app_handle, move |app_handle, state| {
+        state.activate_terminal(app_handle, &id)
+    })
+    .await;
+    match &result {
+        Ok(true) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:activate_terminal] spawned deferred terminal_id={}",
+                terminal_id
+            ));
+        }
+        Ok(false) => {}
+        Err(err) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:activate_terminal:error] terminal_id={} error={}",
+                terminal_id, err.message
+            ));
+        }
+    }
+    result
+}
+
 #[tauri::command]
 pub fn write_terminal(
+    app_handle: AppHandle,
     state: State<'_, PtyManager>,
     terminal_id: String,
     data: String,
 ) -> Result<(), PtyError> {
+    // Never log what is typed at a password prompt or into an incognito tab.
+    let preview = if state.is_incognito(&terminal_id) {
+        String::from("<redacted: incognito>")
+    } else if state.secure_input_active(&terminal_id) {
+        String::from("<redacted: secure input>")
+    } else {
+        preview_terminal_data(&data, 120)
+    };
     let _ = crate::debug_log::append_debug_log(&format!(
         "[backend:write_terminal] terminal_id={} bytes={} preview={}",
         terminal_id,
         data.len(),
-        preview_terminal_data(&data, 120)
+        preview
     ));
 
-    let result = state.write_terminal(&terminal_id, data.as_bytes());
+    let result = state.write_terminal(&app_handle, &terminal_id, data.as_bytes());
     if let Err(err) = &result {
         let _ = crate::debug_log::append_debug_log(&format!(
             "[backend:write_terminal:error] terminal_id={} error={}",
@@ -120,6 +401,94 @@ pub fn close_terminal(state: State<'_, PtyManager>, terminal_id: String) -> Resu
     result
 }
 
+#[tauri::command]
+pub fn get_terminal_activity(state: State<'_, PtyManager>) -> Result<Vec<TerminalActivity>, PtyError> {
+    Ok(state.terminal_activity())
+}
+
+#[tauri::command]
+pub fn get_terminal_screen(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+    history_lines: Option<u32>,
+) -> Result<TerminalScreen, PtyError> {
+    state.get_terminal_screen(&terminal_id, history_lines.unwrap_or(0) as usize)
+}
+
+#[tauri::command]
+pub fn export_terminal_output(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+    format: ExportFormat,
+    range: ExportRange,
+    path: Option<String>,
+    theme: Option<ExportTheme>,
+) -> Result<String, PtyError> {
+    let result = state.export_terminal_output(
+        &app_handle,
+        &terminal_id,
+        format,
+        range,
+        path,
+        theme.unwrap_or_default(),
+    );
+    if let Err(err) = &result {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:export_terminal:error] terminal_id={} error={}",
+            terminal_id, err.message
+        ));
+    }
+    result
+}
+
+// Runs off the main thread: a search over many large scrollbacks can take a
+// noticeable moment.
+#[tauri::command(async)]
+pub fn search_terminals(
+    state: State<'_, PtyManager>,
+    query: String,
+    regex: Option<bool>,
+    case_sensitive: Option<bool>,
+    on_matches: Channel<Vec<TerminalSearchMatch>>,
+) -> Result<Vec<TerminalSearchMatch>, PtyError> {
+    let options = SearchOptions {
+        regex: regex.unwrap_or(false),
+        case_sensitive: case_sensitive.unwrap_or(false),
+    };
+    state.search_terminals(&query, options, on_matches)
+}
+
+#[tauri::command]
+pub fn reconcile_terminals(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    live_ids: Vec<String>,
+) -> Result<Vec<OrphanedTerminal>, PtyError> {
+    Ok(state.reconcile_terminals(&app_handle, live_ids))
+}
+
+#[tauri::command]
+pub fn adopt_terminal(
+    state: State<'_, PtyManager>,
+    terminal_id: String,
+    on_output: Channel<TerminalOutput>,
+) -> Result<(), PtyError> {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:adopt_terminal] terminal_id={}",
+        terminal_id
+    ));
+
+    let result = state.adopt_terminal(&terminal_id, on_output);
+    if let Err(err) = &result {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:adopt_terminal:error] terminal_id={} error={}",
+            terminal_id, err.message
+        ));
+    }
+    result
+}
+
 #[tauri::command]
 pub fn get_terminal_cwd(
     state: State<'_, PtyManager>,
@@ -156,8 +525,16 @@ pub fn warm_pool(
     app_handle: AppHandle,
     state: State<'_, PtyManager>,
     count: usize,
+    project_id: Option<String>,
+    project_root: Option<String>,
 ) -> Result<(), PtyError> {
-    state.warm_pool(&app_handle, count)
+    let project = project_root
+        .filter(|root| !root.is_empty())
+        .map(|root| ProjectKey {
+            id: project_id,
+            root,
+        });
+    state.warm_pool(&app_handle, count, project)
 }
 
 #[tauri::command]
@@ -166,7 +543,8 @@ pub fn refresh_pool(app_handle: AppHandle, state: State<'_, PtyManager>) -> Resu
 }
 
 #[tauri::command]
-pub fn append_debug_log(message: String) -> Result<(), PtyError> {
+pub fn append_debug_log(state: State<'_, PtyManager>, message: String) -> Result<(), PtyError> {
+    let message = crate::incognito::redact_log_lines(&message, &state.incognito_terminals());
     crate::debug_log::append_debug_log(&message)
 }
 
@@ -251,7 +629,32 @@ fn prune_debug_artifacts(dir: &Path) -> Result<(), PtyError> {
 }
 
 #[tauri::command]
-pub fn write_debug_artifact(file_name: String, content: String) -> Result<String, PtyError> {
+pub fn write_debug_artifact(
+    state: State<'_, PtyManager>,
+    file_name: String,
+    content: String,
+    terminal_ids: Vec<String>,
+) -> Result<String, PtyError> {
+    // Screenshots are opaque, so the caller names the terminals an artifact
+    // shows; the text check only backs that up.
+    let incognito = state.incognito_terminals();
+    if let Some(terminal_id) = terminal_ids
+        .iter()
+        .find(|terminal_id| incognito.contains(terminal_id.as_str()))
+        .map(String::as_str)
+        .or_else(|| crate::incognito::mentioned_terminal(&file_name, &incognito))
+        .or_else(|| crate::incognito::mentioned_terminal(&content, &incognito))
+    {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:write_debug_artifact] skipped incognito terminal_id={}",
+            terminal_id
+        ));
+        return Err(PtyError::from(format!(
+            "Debug artifact skipped: terminal {} is incognito",
+            terminal_id
+        )));
+    }
+
     let debug_log_path = crate::debug_log::debug_log_path();
     let dir = debug_log_path
         .parent()
@@ -260,7 +663,7 @@ pub fn write_debug_artifact(file_name: String, content: String) -> Result<String
     fs::create_dir_all(&dir)?;
 
     let path = dir.join(sanitize_debug_artifact_name(&file_name));
-    fs::write(&path, content)?;
+    fs::write(&path, crate::redaction::redact_text(&content).as_bytes())?;
     let _ = prune_debug_artifacts(&dir);
 
     Ok(path.display().to_string())
@@ -327,7 +730,13 @@ pub fn read_app_state_backup(app_handle: AppHandle) -> Result<Option<String>, Pt
 }
 
 #[tauri::command]
-pub fn write_app_state_backup(app_handle: AppHandle, content: String) -> Result<String, PtyError> {
+pub fn write_app_state_backup(
+    app_handle: AppHandle,
+    state: State<'_, PtyManager>,
+    content: String,
+) -> Result<String, PtyError> {
+    let content =
+        crate::incognito::redact_app_state_backup(&content, &state.incognito_terminals())?;
     let path = app_state_backup_path(&app_handle)?;
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
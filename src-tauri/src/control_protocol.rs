@@ -0,0 +1,271 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Exported to every shell so scripts can find their own tab.
+pub const TERMINAL_ID_VAR: &str = "DISPATCHER_TERMINAL_ID";
+pub const PROJECT_ID_VAR: &str = "DISPATCHER_PROJECT_ID";
+pub const PROJECT_CWD_VAR: &str = "DISPATCHER_PROJECT_CWD";
+/// Path of the control socket.
+pub const SOCKET_VAR: &str = "DISPATCHER_SOCKET";
+/// Pooled shells start before they belong to a terminal; the shell
+/// integration loads the variables above from this file once assigned.
+pub const IDENTITY_FILE_VAR: &str = "DISPATCHER_IDENTITY_FILE";
+
+pub const IDENTITY_VARS: [&str; 3] = [TERMINAL_ID_VAR, PROJECT_ID_VAR, PROJECT_CWD_VAR];
+
+/// The socket of the first running instance; later ones add their pid.
+pub const SOCKET_NAME: &str = "control.sock";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request was valid but could not be carried out.
+pub const REQUEST_FAILED: i64 = -32000;
+
+/// One JSON-RPC 2.0 request per line.  Requests without an id are
+/// notifications and get no response.
+#[derive(Debug, Deserialize)]
+pub struct RpcRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl RpcRequest {
+    pub fn params<T: DeserializeOwned>(&self) -> Result<T, RpcError> {
+        let params = match self.params {
+            Value::Null => json!({}),
+            ref params => params.clone(),
+        };
+        serde_json::from_value(params)
+            .map_err(|err| RpcError::new(INVALID_PARAMS, format!("{}: {}", self.method, err)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<crate::errors::PtyError> for RpcError {
+    fn from(err: crate::errors::PtyError) -> Self {
+        RpcError::new(REQUEST_FAILED, err.message)
+    }
+}
+
+/// The directory holding the socket: `$XDG_RUNTIME_DIR/dispatcher`, else
+/// `dispatcher-<uid>` in the temp dir.
+pub fn runtime_dir_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime) => PathBuf::from(runtime).join("dispatcher"),
+        #[cfg(unix)]
+        None => std::env::temp_dir().join(format!("dispatcher-{}", unsafe { libc::getuid() })),
+        #[cfg(not(unix))]
+        None => std::env::temp_dir().join("dispatcher"),
+    }
+}
+
+/// Where a client connects: the terminal's own `$DISPATCHER_SOCKET`, else
+/// the first instance's socket.
+pub fn client_socket_path() -> PathBuf {
+    std::env::var_os(SOCKET_VAR)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| runtime_dir_path().join(SOCKET_NAME))
+}
+
+pub fn parse_request(line: &str) -> Result<RpcRequest, RpcError> {
+    let value: Value =
+        serde_json::from_str(line).map_err(|err| RpcError::new(PARSE_ERROR, err.to_string()))?;
+    let request: RpcRequest = serde_json::from_value(value)
+        .map_err(|err| RpcError::new(INVALID_REQUEST, err.to_string()))?;
+    if request.jsonrpc != "2.0" {
+        return Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    Ok(request)
+}
+
+/// A response line, newline included.
+pub fn response_line(id: &Value, result: Result<Value, RpcError>) -> String {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    };
+    format!("{}\n", response)
+}
+
+/// Input bytes for `send-keys` arguments, tmux style: arguments are
+/// concatenated, and key names such as `Enter`, `Tab`, `Escape`, `Up` or
+/// `C-c` become their sequences unless `literal` is set.
+pub fn key_bytes(keys: &[String], literal: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for key in keys {
+        match (literal, named_key(key)) {
+            (false, Some(sequence)) => bytes.extend_from_slice(&sequence),
+            _ => bytes.extend_from_slice(key.as_bytes()),
+        }
+    }
+    bytes
+}
+
+fn named_key(key: &str) -> Option<Vec<u8>> {
+    let sequence: &[u8] = match key {
+        "Enter" => b"\r",
+        "Tab" => b"\t",
+        "Escape" => b"\x1b",
+        "Space" => b" ",
+        "BSpace" => b"\x7f",
+        "Up" => b"\x1b[A",
+        "Down" => b"\x1b[B",
+        "Right" => b"\x1b[C",
+        "Left" => b"\x1b[D",
+        "Home" => b"\x1b[H",
+        "End" => b"\x1b[F",
+        _ => {
+            let ctrl = key.strip_prefix("C-")?;
+            let &[byte] = ctrl.as_bytes() else {
+                return None;
+            };
+            return match byte.to_ascii_lowercase() {
+                byte @ (b'a'..=b'z' | b'@' | b'[' | b'\\' | b']' | b'^' | b'_') => {
+                    Some(vec![byte & 0x1f])
+                }
+                _ => None,
+            };
+        }
+    };
+    Some(sequence.to_vec())
+}
+
+/// Quote arguments for a POSIX shell command line.
+pub fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || b"-_./:=@%+,".contains(&byte));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The identity variables of a terminal's shell.
+pub fn identity_vars(
+    terminal_id: &str,
+    project_id: Option<&str>,
+    project_cwd: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut vars = vec![(TERMINAL_ID_VAR, terminal_id.to_owned())];
+    let optional = [(PROJECT_ID_VAR, project_id), (PROJECT_CWD_VAR, project_cwd)];
+    for (name, value) in optional {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            vars.push((name, value.to_owned()));
+        }
+    }
+    vars
+}
+
+/// `NAME=value` lines for a pooled shell's identity file.  The shells read
+/// it line by line, so values spanning lines are left out.
+pub fn format_identity_file(vars: &[(&str, String)]) -> String {
+    vars.iter()
+        .filter(|(_, value)| !value.contains(['\n', '\r']))
+        .map(|(name, value)| format!("{}={}\n", name, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests_and_formats_responses() {
+        let request = parse_request(
+            r#"{"jsonrpc":"2.0","id":7,"method":"rename","params":{"terminalId":"t1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id, Some(json!(7)));
+        assert_eq!(request.method, "rename");
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            terminal_id: String,
+        }
+        assert_eq!(request.params::<Params>().unwrap().terminal_id, "t1");
+        assert_eq!(
+            parse_request(r#"{"jsonrpc":"2.0","method":"rename"}"#)
+                .unwrap()
+                .params::<Params>()
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+
+        assert_eq!(parse_request("{not json").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(
+            parse_request(r#"{"jsonrpc":"1.0","id":1,"method":"x"}"#)
+                .unwrap_err()
+                .code,
+            INVALID_REQUEST
+        );
+
+        assert_eq!(
+            response_line(&json!(7), Ok(Value::Null)),
+            "{\"id\":7,\"jsonrpc\":\"2.0\",\"result\":null}\n"
+        );
+        assert_eq!(
+            response_line(&json!("a"), Err(RpcError::new(METHOD_NOT_FOUND, "nope"))),
+            "{\"error\":{\"code\":-32601,\"message\":\"nope\"},\"id\":\"a\",\"jsonrpc\":\"2.0\"}\n"
+        );
+    }
+
+    #[test]
+    fn identity_file_skips_missing_and_multiline_values() {
+        let vars = identity_vars("t1", Some("p1"), Some("/work/my\nrepo"));
+        assert_eq!(
+            format_identity_file(&vars),
+            "DISPATCHER_TERMINAL_ID=t1\nDISPATCHER_PROJECT_ID=p1\n"
+        );
+        assert_eq!(identity_vars("t2", None, Some("")).len(), 1);
+    }
+
+    #[test]
+    fn translates_key_names_and_quotes_commands() {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        assert_eq!(key_bytes(&keys(&["ls -l", "Enter"]), false), b"ls -l\r");
+        assert_eq!(key_bytes(&keys(&["C-c", "C-[", "Up"]), false), b"\x03\x1b\x1b[A");
+        assert_eq!(key_bytes(&keys(&["C-cc", "Enter"]), true), b"C-ccEnter");
+
+        assert_eq!(
+            shell_join(&keys(&["npm", "run", "dev:web", "it's here", ""])),
+            "npm run dev:web 'it'\\''s here' ''"
+        );
+    }
+}
@@ -0,0 +1,477 @@
+use crate::control_protocol::{
+    format_identity_file, key_bytes, parse_request, response_line, runtime_dir_path, RpcError,
+    RpcRequest, METHOD_NOT_FOUND, REQUEST_FAILED, SOCKET_NAME,
+};
+use crate::errors::PtyError;
+use crate::pty_manager::PtyManager;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How long a request handled by the renderer may take.
+const RENDERER_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long `new-tab` waits for the tab's shell to show a prompt before
+/// typing its command.
+const TERMINAL_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+static SOCKET_PATH: OnceLock<PathBuf> = OnceLock::new();
+static IDENTITY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+/// Requests forwarded to the renderer, waiting for `complete_request`.
+static PENDING: LazyLock<Mutex<HashMap<u64, Sender<RendererReply>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Where the control socket listens, once the server is up.
+pub fn socket_path() -> Option<&'static Path> {
+    SOCKET_PATH.get().map(PathBuf::as_path)
+}
+
+/// Sent to the renderer for requests that change what it owns: tabs,
+/// titles, notes and layouts.  Answered with `complete_control_request`.
+#[derive(Clone, Serialize)]
+pub struct ControlRequestPayload {
+    pub request_id: u64,
+    #[serde(flatten)]
+    pub action: ControlAction,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum ControlAction {
+    /// `project` is a project id or name; the active project when unset.
+    NewTab {
+        project: Option<String>,
+        cwd: Option<String>,
+    },
+    Split {
+        terminal_id: String,
+        direction: SplitDirection,
+    },
+    Rename {
+        terminal_id: String,
+        title: String,
+    },
+    AppendNotes {
+        terminal_id: String,
+        text: String,
+    },
+    Close {
+        terminal_id: String,
+    },
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    /// Side by side, like Cmd+D.
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// The renderer's answer: an error, or the terminal it created.
+pub struct RendererReply {
+    pub error: Option<String>,
+    pub terminal_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalParams {
+    terminal_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewTabParams {
+    project: Option<String>,
+    cwd: Option<String>,
+    /// Typed at the new shell's prompt.
+    command: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SplitParams {
+    terminal_id: String,
+    #[serde(default)]
+    direction: SplitDirection,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenameParams {
+    terminal_id: String,
+    title: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppendNotesParams {
+    terminal_id: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendKeysParams {
+    terminal_id: String,
+    keys: Vec<String>,
+    #[serde(default)]
+    literal: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureParams {
+    terminal_id: String,
+    /// Scrollback lines to include above the visible screen.
+    #[serde(default)]
+    history_lines: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WaitForExitParams {
+    terminal_id: String,
+    timeout_ms: Option<u64>,
+}
+
+/// The socket directory, created private to the user.
+#[cfg(unix)]
+fn runtime_dir() -> Result<PathBuf, String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    let dir = runtime_dir_path();
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(format!("{}: {}", dir.display(), err)),
+    }
+    // In a shared temp dir someone else may have created it first.
+    let metadata = std::fs::symlink_metadata(&dir).map_err(|err| err.to_string())?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+        return Err(format!("{} is not a directory owned by us", dir.display()));
+    }
+    if metadata.permissions().mode() & 0o077 != 0 {
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+            .map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    Ok(dir)
+}
+
+/// Bind the control socket and serve it in the background.  Only the user
+/// can reach it: it lives in a 0700 directory and is itself 0600.
+#[cfg(unix)]
+pub fn start(app_handle: &AppHandle) {
+    use std::os::unix::net::UnixListener;
+
+    let bound = runtime_dir().and_then(|dir| bind(&dir).map_err(|err| err.to_string()));
+    let (listener, path): (UnixListener, PathBuf) = match bound {
+        Ok(bound) => bound,
+        Err(err) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:control_socket:error] not listening error={}",
+                err
+            ));
+            return;
+        }
+    };
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:control_socket] listening path={}",
+        path.display()
+    ));
+    let _ = SOCKET_PATH.set(path);
+
+    let handle = app_handle.clone();
+    let result = std::thread::Builder::new()
+        .name("dispatcher-control".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let handle = handle.clone();
+                let _ = std::thread::Builder::new()
+                    .name("dispatcher-control-conn".to_string())
+                    .spawn(move || serve_connection(&handle, stream));
+            }
+        });
+    if let Err(err) = result {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:control_socket:error] failed to start accept thread error={}",
+            err
+        ));
+    }
+}
+
+#[cfg(not(unix))]
+pub fn start(_app_handle: &AppHandle) {}
+
+/// `control.sock`, or `control-<pid>.sock` while another instance owns it.
+/// Sockets left behind by crashed instances are removed.
+#[cfg(unix)]
+fn bind(dir: &Path) -> std::io::Result<(std::os::unix::net::UnixListener, PathBuf)> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let is_stale = |path: &Path| path.exists() && UnixStream::connect(path).is_err();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with("control") && name.ends_with(".sock") && is_stale(&entry.path()) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let primary = dir.join(SOCKET_NAME);
+    let (listener, path) = match UnixListener::bind(&primary) {
+        Ok(listener) => (listener, primary),
+        Err(_) => {
+            let path = dir.join(format!("control-{}.sock", std::process::id()));
+            (UnixListener::bind(&path)?, path)
+        }
+    };
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    Ok((listener, path))
+}
+
+#[cfg(unix)]
+fn serve_connection(app_handle: &AppHandle, stream: std::os::unix::net::UnixStream) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match parse_request(&line) {
+            Ok(request) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:control_socket] method={}",
+                    request.method
+                ));
+                let result = handle_request(app_handle, &request);
+                if let Err(ref err) = result {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:control_socket:error] method={} error={}",
+                        request.method, err.message
+                    ));
+                }
+                match request.id {
+                    Some(ref id) => response_line(id, result),
+                    None => continue,
+                }
+            }
+            Err(err) => response_line(&Value::Null, Err(err)),
+        };
+        if writer.write_all(response.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle_request(app_handle: &AppHandle, request: &RpcRequest) -> Result<Value, RpcError> {
+    let pty = app_handle.state::<PtyManager>();
+    match request.method.as_str() {
+        "new-tab" => {
+            let params: NewTabParams = request.params()?;
+            let terminal_id = ask_renderer(
+                app_handle,
+                ControlAction::NewTab {
+                    project: params.project,
+                    cwd: params.cwd,
+                },
+            )?
+            .ok_or_else(|| RpcError::new(REQUEST_FAILED, "No tab was created"))?;
+            if let Some(command) = params.command.filter(|command| !command.is_empty()) {
+                wait_for_prompt(&pty, &terminal_id)?;
+                pty.write_terminal(app_handle, &terminal_id, format!("{}\r", command).as_bytes())?;
+            }
+            Ok(json!({ "terminalId": terminal_id }))
+        }
+        "split" => {
+            let params: SplitParams = request.params()?;
+            require_terminal(&pty, &params.terminal_id)?;
+            let terminal_id = ask_renderer(
+                app_handle,
+                ControlAction::Split {
+                    terminal_id: params.terminal_id,
+                    direction: params.direction,
+                },
+            )?;
+            Ok(json!({ "terminalId": terminal_id }))
+        }
+        "rename" => {
+            let params: RenameParams = request.params()?;
+            require_terminal(&pty, &params.terminal_id)?;
+            ask_renderer(
+                app_handle,
+                ControlAction::Rename {
+                    terminal_id: params.terminal_id,
+                    title: params.title.trim().to_owned(),
+                },
+            )?;
+            Ok(Value::Null)
+        }
+        "append-notes" => {
+            let params: AppendNotesParams = request.params()?;
+            require_terminal(&pty, &params.terminal_id)?;
+            ask_renderer(
+                app_handle,
+                ControlAction::AppendNotes {
+                    terminal_id: params.terminal_id,
+                    text: params.text,
+                },
+            )?;
+            Ok(Value::Null)
+        }
+        "close" => {
+            let params: TerminalParams = request.params()?;
+            require_terminal(&pty, &params.terminal_id)?;
+            ask_renderer(
+                app_handle,
+                ControlAction::Close {
+                    terminal_id: params.terminal_id,
+                },
+            )?;
+            Ok(Value::Null)
+        }
+        "send-keys" => {
+            let params: SendKeysParams = request.params()?;
+            let bytes = key_bytes(&params.keys, params.literal);
+            pty.write_terminal(app_handle, &params.terminal_id, &bytes)?;
+            Ok(Value::Null)
+        }
+        "list" => Ok(json!(pty.list_terminals())),
+        "capture" => {
+            let params: CaptureParams = request.params()?;
+            let screen = pty.get_terminal_screen(&params.terminal_id, params.history_lines)?;
+            let mut text = String::new();
+            for line in &screen.lines {
+                text.push_str(line.text.trim_end());
+                if !line.wrapped {
+                    text.push('\n');
+                }
+            }
+            let text = text.trim_end_matches('\n');
+            Ok(json!({ "text": format!("{}\n", text) }))
+        }
+        "wait-for-exit" => {
+            let params: WaitForExitParams = request.params()?;
+            let exit_code = pty.wait_for_exit(
+                &params.terminal_id,
+                params.timeout_ms.map(Duration::from_millis),
+            )?;
+            Ok(json!({ "exitCode": exit_code }))
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    }
+}
+
+fn require_terminal(pty: &PtyManager, terminal_id: &str) -> Result<(), RpcError> {
+    if pty.has_terminal(terminal_id) {
+        Ok(())
+    } else {
+        Err(PtyError::from(format!("Terminal {} not found", terminal_id)).into())
+    }
+}
+
+/// A new tab's shell starts once the renderer mounts it; its command is
+/// typed at the first prompt so startup files cannot swallow it.
+fn wait_for_prompt(pty: &PtyManager, terminal_id: &str) -> Result<(), RpcError> {
+    let started = Instant::now();
+    while !pty.has_prompted(terminal_id) {
+        if started.elapsed() >= TERMINAL_START_TIMEOUT {
+            return Err(RpcError::new(
+                REQUEST_FAILED,
+                format!("Terminal {} did not show a prompt", terminal_id),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+/// Forward an action to the renderer and wait for its reply.
+fn ask_renderer(app_handle: &AppHandle, action: ControlAction) -> Result<Option<String>, RpcError> {
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = channel();
+    PENDING.lock().unwrap().insert(request_id, sender);
+
+    let emitted = app_handle.emit(
+        "terminal-control-request",
+        ControlRequestPayload { request_id, action },
+    );
+    let reply = match emitted {
+        Ok(()) => receiver.recv_timeout(RENDERER_REPLY_TIMEOUT).ok(),
+        Err(_) => None,
+    };
+    PENDING.lock().unwrap().remove(&request_id);
+
+    let reply =
+        reply.ok_or_else(|| RpcError::new(REQUEST_FAILED, "The Dispatcher window did not respond"))?;
+    match reply.error {
+        Some(error) => Err(RpcError::new(REQUEST_FAILED, error)),
+        None => Ok(reply.terminal_id),
+    }
+}
+
+/// Deliver the renderer's reply to a forwarded request.  Replies to
+/// requests that already timed out are dropped.
+pub fn complete_request(request_id: u64, reply: RendererReply) {
+    if let Some(sender) = PENDING.lock().unwrap().remove(&request_id) {
+        let _ = sender.send(reply);
+    }
+}
+
+/// A pooled shell's identity file, written once the shell is assigned to a
+/// terminal.  The shell integration deletes it after reading; shells
+/// without the integration never do, so it is also removed when dropped.
+pub struct IdentityFile {
+    path: PathBuf,
+}
+
+impl IdentityFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, vars: &[(&str, String)]) -> std::io::Result<()> {
+        std::fs::write(&self.path, format_identity_file(vars))
+    }
+}
+
+impl Drop for IdentityFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub fn new_identity_file() -> Option<IdentityFile> {
+    #[cfg(unix)]
+    {
+        let dir = runtime_dir().ok()?;
+        let n = IDENTITY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("identity-{}-{}.env", std::process::id(), n));
+        Some(IdentityFile { path })
+    }
+
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
@@ -104,6 +104,9 @@ pub fn append_debug_log(message: &str) -> Result<(), PtyError> {
         .open(&path)
         .map_err(PtyError::from)?;
 
+    // Log lines carry command previews and output snippets; secrets in them
+    // never reach the file.
+    let message = crate::redaction::redact_text(message);
     if message.ends_with('\n') {
         write!(file, "{} {}", timestamp_prefix(), message).map_err(PtyError::from)?;
     } else {
@@ -0,0 +1,353 @@
+use crate::project_env::{file_stamps, FileStamps};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A cached activation is re-evaluated once one of these files changes.
+pub const ACTIVATION_WATCH_FILES: [&str; 2] = [".envrc", "flake.lock"];
+/// `nix develop` may build a whole toolchain the first time.
+pub const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(180);
+/// Variables describing the evaluating process rather than the project.
+const IGNORED_ACTIVATION_VARS: [&str; 4] = ["PWD", "OLDPWD", "SHLVL", "_"];
+
+/// How a project's development environment is produced before a shell
+/// starts, so the first prompt already has it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum EnvActivation {
+    /// `direnv export json` in the project root.
+    Direnv,
+    /// A shell command run in the project root that prints a JSON object
+    /// (like direnv) or `env -0` output, e.g. `nix develop --command env -0`.
+    Command { command: String },
+}
+
+impl EnvActivation {
+    pub fn describe(&self) -> String {
+        match self {
+            EnvActivation::Direnv => String::from("direnv export json"),
+            EnvActivation::Command { command } => command.clone(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        match self {
+            EnvActivation::Direnv => {
+                let mut command = crate::login_env::command("direnv");
+                command.args(["export", "json"]);
+                command
+            }
+            EnvActivation::Command { command: script } => {
+                let mut command = crate::login_env::command("/bin/sh");
+                command.args(["-c", script]);
+                command
+            }
+        }
+    }
+}
+
+/// Variables to set, or to unset when the value is `None`.
+pub type ActivationVars = Vec<(String, Option<String>)>;
+
+pub fn activation_watch_paths(dir: &Path) -> Vec<PathBuf> {
+    ACTIVATION_WATCH_FILES
+        .iter()
+        .map(|file| dir.join(file))
+        .collect()
+}
+
+/// Parse what an activation printed: a JSON object of strings and nulls,
+/// NUL-separated `NAME=value` pairs, or one pair per line.
+pub fn parse_activation_output(output: &str) -> Result<ActivationVars, String> {
+    let trimmed = output.trim();
+    if trimmed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut vars: ActivationVars = if trimmed.starts_with('{') {
+        let object: HashMap<String, Option<String>> = serde_json::from_str(trimmed)
+            .map_err(|err| format!("expected a JSON object of strings: {}", err))?;
+        object.into_iter().collect()
+    } else {
+        let separator = if output.contains('\0') { '\0' } else { '\n' };
+        let mut vars: ActivationVars = Vec::new();
+        for entry in output.split(separator) {
+            match entry.split_once('=') {
+                Some((name, value)) if !name.is_empty() && !name.contains(char::is_whitespace) => {
+                    vars.push((name.to_owned(), Some(value.to_owned())));
+                }
+                // A newline inside a value in line-separated output.
+                _ if separator == '\n' && !vars.is_empty() && !entry.is_empty() => {
+                    if let Some((_, Some(value))) = vars.last_mut() {
+                        value.push('\n');
+                        value.push_str(entry);
+                    }
+                }
+                _ => {}
+            }
+        }
+        vars
+    };
+
+    vars.retain(|(name, _)| !IGNORED_ACTIVATION_VARS.contains(&name.as_str()));
+    vars.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(vars)
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Run a command with piped output, killing it after `timeout`.  Returns
+/// the exit status with stdout and stderr.
+pub fn run_captured(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<(ExitStatus, Vec<u8>, Vec<u8>), String> {
+    // Its own process group, so a timeout also reaches whatever the command
+    // started; a surviving grandchild would hold the pipes open.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+
+    // Read both pipes on their own threads so a chatty command cannot fill
+    // one and stall.
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= timeout => {
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(err) => return Err(err.to_string()),
+        }
+    };
+
+    Ok((
+        status,
+        stdout.join().unwrap_or_default(),
+        stderr.join().unwrap_or_default(),
+    ))
+}
+
+fn evaluate(dir: &Path, activation: &EnvActivation) -> Result<ActivationVars, String> {
+    let describe = activation.describe();
+    let (status, stdout, stderr) =
+        run_captured(activation.command().current_dir(dir), ACTIVATION_TIMEOUT)
+            .map_err(|err| format!("{}: {}", describe, err))?;
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("no error output");
+        return Err(format!("{} failed ({}): {}", describe, status, reason.trim()));
+    }
+    parse_activation_output(&String::from_utf8_lossy(&stdout))
+        .map_err(|err| format!("{}: {}", describe, err))
+}
+
+pub enum ActivationLookup {
+    Ready(ActivationVars),
+    Failed(String),
+    /// Still evaluating after the caller's wait; the result will be cached.
+    Pending,
+}
+
+enum CacheEntry {
+    Evaluating {
+        activation: EnvActivation,
+        stamps: FileStamps,
+    },
+    Ready {
+        activation: EnvActivation,
+        stamps: FileStamps,
+        result: Result<ActivationVars, String>,
+    },
+}
+
+/// Activation results per directory.  An entry is valid while the
+/// activation and the watched files are unchanged; concurrent lookups for
+/// the same state share one background evaluation.
+#[derive(Default)]
+pub struct ActivationCache {
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+    finished: Condvar,
+}
+
+impl ActivationCache {
+    /// The activation result for `dir`, evaluating it in the background
+    /// when the cache is stale and waiting up to `wait` for it.
+    pub fn lookup(
+        self: &Arc<Self>,
+        dir: &Path,
+        activation: &EnvActivation,
+        wait: Duration,
+    ) -> ActivationLookup {
+        let stamps = file_stamps(&activation_watch_paths(dir));
+        let deadline = Instant::now() + wait;
+        let mut entries = self.entries.lock().unwrap();
+        loop {
+            match entries.get(dir) {
+                Some(CacheEntry::Ready {
+                    activation: cached,
+                    stamps: cached_stamps,
+                    result,
+                }) if cached == activation && *cached_stamps == stamps => {
+                    return match result {
+                        Ok(vars) => ActivationLookup::Ready(vars.clone()),
+                        Err(err) => ActivationLookup::Failed(err.clone()),
+                    };
+                }
+                Some(CacheEntry::Evaluating {
+                    activation: cached,
+                    stamps: cached_stamps,
+                }) if cached == activation && *cached_stamps == stamps => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return ActivationLookup::Pending;
+                    }
+                    entries = self.finished.wait_timeout(entries, deadline - now).unwrap().0;
+                }
+                _ => {
+                    let entry = match self.start_evaluation(dir, activation, stamps.clone()) {
+                        Ok(()) => CacheEntry::Evaluating {
+                            activation: activation.clone(),
+                            stamps: stamps.clone(),
+                        },
+                        Err(err) => CacheEntry::Ready {
+                            activation: activation.clone(),
+                            stamps: stamps.clone(),
+                            result: Err(err),
+                        },
+                    };
+                    entries.insert(dir.to_path_buf(), entry);
+                }
+            }
+        }
+    }
+
+    /// Evaluate on a background thread that stores the result.  Called with
+    /// the entries lock held; the thread only takes it once done.
+    fn start_evaluation(
+        self: &Arc<Self>,
+        dir: &Path,
+        activation: &EnvActivation,
+        stamps: FileStamps,
+    ) -> Result<(), String> {
+        let cache = Arc::clone(self);
+        let dir = dir.to_path_buf();
+        let activation = activation.clone();
+        std::thread::Builder::new()
+            .name("dispatcher-env-activation".to_string())
+            .spawn(move || {
+                let started = Instant::now();
+                let result = evaluate(&dir, &activation);
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:env_activation] dir={} activation={} ok={} vars={} elapsed_ms={}",
+                    dir.display(),
+                    activation.describe(),
+                    result.is_ok(),
+                    result.as_ref().map_or(0, Vec::len),
+                    started.elapsed().as_millis()
+                ));
+                cache.finish(dir, activation, stamps, result);
+            })
+            .map(|_| ())
+            .map_err(|err| format!("failed to start activation thread: {}", err))
+    }
+
+    fn finish(
+        &self,
+        dir: PathBuf,
+        activation: EnvActivation,
+        stamps: FileStamps,
+        result: Result<ActivationVars, String>,
+    ) {
+        self.entries.lock().unwrap().insert(
+            dir,
+            CacheEntry::Ready {
+                activation,
+                stamps,
+                result,
+            },
+        );
+        self.finished.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_direnv_json_and_env_dumps() {
+        let direnv = r#"{"PATH": "/nix/store/abc/bin:/usr/bin", "VIRTUAL_ENV": null, "DIRENV_DIR": "-/src/app"}"#;
+        assert_eq!(
+            parse_activation_output(direnv).unwrap(),
+            vec![
+                ("DIRENV_DIR".to_string(), Some("-/src/app".to_string())),
+                ("PATH".to_string(), Some("/nix/store/abc/bin:/usr/bin".to_string())),
+                ("VIRTUAL_ENV".to_string(), None),
+            ]
+        );
+
+        let env_dump = "SHLVL=2\0CC=clang\0shellHook=echo hi\nexport X=1\0PWD=/src/app\0";
+        assert_eq!(
+            parse_activation_output(env_dump).unwrap(),
+            vec![
+                ("CC".to_string(), Some("clang".to_string())),
+                ("shellHook".to_string(), Some("echo hi\nexport X=1".to_string())),
+            ]
+        );
+
+        assert!(parse_activation_output("").unwrap().is_empty());
+        assert!(parse_activation_output("{not json").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_kills_what_the_command_started() {
+        let pid_file = std::env::temp_dir().join(format!("activation-{}.pid", std::process::id()));
+        let script = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+        let result = run_captured(
+            Command::new("/bin/sh").arg("-c").arg(script),
+            Duration::from_millis(500),
+        );
+        assert!(result.is_err());
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        std::thread::sleep(Duration::from_millis(200));
+        // Gone, or a zombie waiting for init to reap it.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()));
+        assert!(stat.map_or(true, |stat| stat.contains(") Z ")));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::errors::PtyError;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// User rules, stored in the app data dir.
+pub const ENV_SCRUB_FILE_NAME: &str = "dispatcher-env-scrub.json";
+/// Project rules, relative to the project's root directory.
+pub const PROJECT_ENV_SCRUB_PATH: &str = ".dispatcher/env-scrub.json";
+
+/// Which inherited variables a spawned shell must not see.  Dispatcher is
+/// often launched from inside an agent or IDE session whose variables would
+/// otherwise leak into every terminal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EnvScrubRules {
+    pub exact_names: Vec<String>,
+    pub prefixes: Vec<String>,
+    pub value_rules: Vec<EnvValueRule>,
+    /// Names kept even when another rule matches them.
+    pub allow: Vec<String>,
+}
+
+/// Removes a variable when its value matches `pattern`.  With `names` empty
+/// the rule looks at every variable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EnvValueRule {
+    pub names: Vec<String>,
+    pub pattern: String,
+}
+
+impl Default for EnvScrubRules {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        EnvScrubRules {
+            exact_names: strings(&[
+                "AGENT",
+                "BUCK2_CLIENT_METADATA",
+                "CPE_RUST_X2P_SUPPORTS_VPNLESS",
+                "DOTSLASH_X2P_EDGETERM",
+                "ENABLE_AGENTS_CLI_TRACING_THRIFT",
+                "ENABLE_ENHANCED_TELEMETRY_BETA",
+                "JF_VPNLESS",
+                "LINTTOOL_CALLER",
+                "OPENAI_API_KEY",
+                "X2P_AGENT_PROXY_ADDRESS",
+                "X2P_INJECT_CAT",
+                "X2P_SUPPORTS_VPNLESS",
+            ]),
+            prefixes: strings(&[
+                "CLAUDE_",
+                "CODEX_",
+                "CODING_AGENT_",
+                "META_3PAI_",
+                "META_CLAUDE_",
+                "META_CODEX_",
+                "OTEL_",
+            ]),
+            // The agent sandbox proxy; a real corporate proxy is kept.
+            value_rules: vec![EnvValueRule {
+                names: strings(&["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]),
+                pattern: r"(?:localhost|127\.0\.0\.1|\[::1\]):10054".to_string(),
+            }],
+            allow: Vec::new(),
+        }
+    }
+}
+
+impl EnvScrubRules {
+    /// User rules extended with a project's rules.  Projects can add rules
+    /// and allow names, not drop the user's rules.
+    pub fn merged(&self, project: Option<&EnvScrubRules>) -> EnvScrubRules {
+        let mut merged = self.clone();
+        if let Some(project) = project {
+            merged.exact_names.extend(project.exact_names.iter().cloned());
+            merged.prefixes.extend(project.prefixes.iter().cloned());
+            merged.value_rules.extend(project.value_rules.iter().cloned());
+            merged.allow.extend(project.allow.iter().cloned());
+        }
+        merged
+    }
+
+    pub fn compile(&self) -> Result<EnvScrubber, PtyError> {
+        let mut value_rules = Vec::with_capacity(self.value_rules.len());
+        for rule in &self.value_rules {
+            if rule.pattern.is_empty() {
+                return Err(PtyError::from(String::from(
+                    "Environment value rule has an empty pattern",
+                )));
+            }
+            let regex = RegexBuilder::new(&rule.pattern)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|err| {
+                    PtyError::from(format!(
+                        "Invalid environment value pattern {}: {}",
+                        rule.pattern, err
+                    ))
+                })?;
+            value_rules.push((rule.clone(), regex));
+        }
+        Ok(EnvScrubber {
+            rules: self.clone(),
+            value_rules,
+        })
+    }
+}
+
+pub struct EnvScrubber {
+    rules: EnvScrubRules,
+    value_rules: Vec<(EnvValueRule, Regex)>,
+}
+
+impl Default for EnvScrubber {
+    fn default() -> Self {
+        EnvScrubRules::default()
+            .compile()
+            .expect("built-in environment rules compile")
+    }
+}
+
+/// A variable kept out of a spawned shell, and the rule responsible.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedEnvVar {
+    pub name: String,
+    pub value: String,
+    pub reason: String,
+}
+
+impl EnvScrubber {
+    /// Why `name` would be removed, or `None` when it is kept.
+    pub fn removal_reason(&self, name: &str, value: &str) -> Option<String> {
+        if self.rules.allow.iter().any(|allowed| allowed == name) {
+            return None;
+        }
+        if self.rules.exact_names.iter().any(|exact| exact == name) {
+            return Some(String::from("name"));
+        }
+        if let Some(prefix) = self
+            .rules
+            .prefixes
+            .iter()
+            .find(|prefix| !prefix.is_empty() && name.starts_with(prefix.as_str()))
+        {
+            return Some(format!("prefix {}", prefix));
+        }
+        self.value_rules
+            .iter()
+            .find(|(rule, regex)| {
+                (rule.names.is_empty() || rule.names.iter().any(|scoped| scoped == name))
+                    && regex.is_match(value)
+            })
+            .map(|(rule, _)| format!("value {}", rule.pattern))
+    }
+
+    /// The variables of `env` that would be removed, sorted by name.
+    pub fn scan<'a>(&self, env: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<RemovedEnvVar> {
+        let mut removed: Vec<RemovedEnvVar> = env
+            .filter_map(|(name, value)| {
+                self.removal_reason(name, value).map(|reason| RemovedEnvVar {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                    reason,
+                })
+            })
+            .collect();
+        removed.sort_by(|a, b| a.name.cmp(&b.name));
+        removed.dedup_by(|a, b| a.name == b.name);
+        removed
+    }
+}
+
+pub fn project_rules_path(project_root: &str) -> PathBuf {
+    Path::new(project_root).join(PROJECT_ENV_SCRUB_PATH)
+}
+
+/// Read a rules file; a missing file is `None`, a malformed one an error.
+pub fn load_rules(path: &Path) -> Result<Option<EnvScrubRules>, PtyError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(PtyError::from(err)),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|err| PtyError::from(format!("Invalid rules in {}: {}", path.display(), err)))
+}
+
+pub fn save_rules(path: &Path, rules: &EnvScrubRules) -> Result<(), PtyError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content =
+        serde_json::to_string_pretty(rules).map_err(|err| PtyError::from(err.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_rules_extend_user_rules_and_allow_wins() {
+        let project: EnvScrubRules = serde_json::from_str(
+            r#"{
+                "exactNames": ["TEAM_SESSION"],
+                "prefixes": ["ACME_"],
+                "valueRules": [{"pattern": "^/run/agent/"}],
+                "allow": ["CLAUDE_CONFIG_DIR"]
+            }"#,
+        )
+        .unwrap();
+        let scrubber = EnvScrubRules::default().merged(Some(&project)).compile().unwrap();
+
+        let env = [
+            ("PATH", "/usr/bin"),
+            ("TEAM_SESSION", "1"),
+            ("ACME_TRACE", "on"),
+            ("SSH_AUTH_SOCK", "/run/agent/sock"),
+            ("CLAUDE_CODE_TMPDIR", "/tmp/claude"),
+            ("CLAUDE_CONFIG_DIR", "~/.claude"),
+            ("HTTPS_PROXY", "http://127.0.0.1:10054"),
+        ];
+        let removed = scrubber.scan(env.into_iter());
+        let reasons: Vec<(&str, &str)> = removed
+            .iter()
+            .map(|var| (var.name.as_str(), var.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("ACME_TRACE", "prefix ACME_"),
+                ("CLAUDE_CODE_TMPDIR", "prefix CLAUDE_"),
+                ("HTTPS_PROXY", r"value (?:localhost|127\.0\.0\.1|\[::1\]):10054"),
+                ("SSH_AUTH_SOCK", "value ^/run/agent/"),
+                ("TEAM_SESSION", "name"),
+            ]
+        );
+
+        let bad = EnvScrubRules {
+            value_rules: vec![EnvValueRule {
+                names: Vec::new(),
+                pattern: "(".to_string(),
+            }],
+            ..EnvScrubRules::default()
+        };
+        assert!(bad.compile().is_err());
+    }
+}
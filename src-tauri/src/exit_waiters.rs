@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+/// A blocked caller's token and the channel that wakes it.
+type Waiter = (u64, Sender<Option<i32>>);
+
+/// Callers blocked until a terminal's shell exits, e.g. `dispatcher
+/// wait-for-exit`.
+#[derive(Default)]
+pub struct ExitWaiters {
+    next_waiter: AtomicU64,
+    waiting: Mutex<HashMap<String, Vec<Waiter>>>,
+}
+
+impl ExitWaiters {
+    /// Register before checking whether the shell already exited, so an
+    /// exit in between is not missed.  Returns the waiter's token for
+    /// `unregister`.
+    pub fn register(&self, terminal_id: &str) -> (u64, Receiver<Option<i32>>) {
+        let waiter = self.next_waiter.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = channel();
+        self.waiting
+            .lock()
+            .unwrap()
+            .entry(terminal_id.to_owned())
+            .or_default()
+            .push((waiter, sender));
+        (waiter, receiver)
+    }
+
+    /// Forget a waiter that gave up before the terminal exited.
+    pub fn unregister(&self, terminal_id: &str, waiter: u64) {
+        let mut waiting = self.waiting.lock().unwrap();
+        if let Some(senders) = waiting.get_mut(terminal_id) {
+            senders.retain(|(id, _)| *id != waiter);
+            if senders.is_empty() {
+                waiting.remove(terminal_id);
+            }
+        }
+    }
+
+    /// Wake everyone waiting on the terminal with its exit code.
+    pub fn notify(&self, terminal_id: &str, exit_code: Option<i32>) {
+        let senders = self.waiting.lock().unwrap().remove(terminal_id);
+        for (_, sender) in senders.into_iter().flatten() {
+            // The waiter may have timed out.
+            let _ = sender.send(exit_code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wakes_only_waiters_of_the_exited_terminal() {
+        let waiters = ExitWaiters::default();
+        let (_, first) = waiters.register("t1");
+        let (_, second) = waiters.register("t1");
+        let (_, other) = waiters.register("t2");
+        drop(waiters.register("t1"));
+
+        waiters.notify("t1", Some(3));
+        assert_eq!(first.try_recv(), Ok(Some(3)));
+        assert_eq!(second.try_recv(), Ok(Some(3)));
+        assert!(other.try_recv().is_err());
+
+        waiters.notify("t1", Some(0));
+        assert!(first.try_recv().is_err());
+    }
+
+    #[test]
+    fn unregistered_waiters_are_dropped() {
+        let waiters = ExitWaiters::default();
+        let (gave_up, _) = waiters.register("t1");
+        let (_, still_waiting) = waiters.register("t1");
+        waiters.unregister("t1", gave_up);
+        assert_eq!(waiters.waiting.lock().unwrap()["t1"].len(), 1);
+
+        waiters.notify("t1", None);
+        assert_eq!(still_waiting.try_recv(), Ok(None));
+
+        let (only, _) = waiters.register("t2");
+        waiters.unregister("t2", only);
+        assert!(waiters.waiting.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::errors::PtyError;
+use crate::pty_manager::{TerminalOutput, TerminalSpawnOptions};
+use crate::shell_integration::ShellMetadata;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tauri::ipc::Channel;
+
+pub const HIBERNATION_CONFIG_FILE_NAME: &str = "dispatcher-hibernation.json";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HibernationConfig {
+    pub enabled: bool,
+    pub idle_after_secs: u64,
+}
+
+impl Default for HibernationConfig {
+    fn default() -> Self {
+        HibernationConfig {
+            enabled: false,
+            idle_after_secs: 4 * 60 * 60,
+        }
+    }
+}
+
+impl HibernationConfig {
+    pub fn idle_after(&self) -> Duration {
+        // Anything shorter than a minute would hibernate tabs the user is
+        // still reading.
+        Duration::from_secs(self.idle_after_secs.max(60))
+    }
+}
+
+/// Read the saved config; a missing file is `None`, a malformed one an
+/// error.
+pub fn load_config(path: &Path) -> Result<Option<HibernationConfig>, PtyError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(PtyError::from(err)),
+    };
+    serde_json::from_str(&content).map(Some).map_err(|err| {
+        PtyError::from(format!("Invalid hibernation config in {}: {}", path.display(), err))
+    })
+}
+
+pub fn save_config(path: &Path, config: &HibernationConfig) -> Result<(), PtyError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content =
+        serde_json::to_string_pretty(config).map_err(|err| PtyError::from(err.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A local terminal whose shell was shut down to reclaim resources.  The
+/// renderer keeps its tab and output channel; the backend keeps everything
+/// needed to respawn it in place.
+pub struct HibernatedTerminal {
+    pub cwd: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    pub options: TerminalSpawnOptions,
+    pub channel: Channel<TerminalOutput>,
+    pub metadata: ShellMetadata,
+    /// When the metadata last changed; persisted as is so a hibernated
+    /// terminal does not look freshly updated on every save.
+    pub metadata_updated_at_ms: u64,
+    pub scrollback: Vec<u8>,
+    pub hibernated_at: SystemTime,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TerminalHibernationPayload {
+    pub terminal_id: String,
+    pub hibernated: bool,
+}
@@ -0,0 +1,190 @@
+use crate::errors::PtyError;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+const INCOGNITO_TITLE: &str = "Incognito terminal";
+const REDACTED_TEXT: &str = "<redacted: incognito>";
+
+/// The first incognito terminal id that appears in `content`.  Terminal ids
+/// are UUIDs, so a plain substring search does not misfire.
+pub fn mentioned_terminal<'a>(content: &str, incognito: &'a HashSet<String>) -> Option<&'a str> {
+    incognito
+        .iter()
+        .find(|terminal_id| content.contains(terminal_id.as_str()))
+        .map(String::as_str)
+}
+
+/// Replace everything the renderer's state backup knows about incognito
+/// terminals (title, notes, cwd, tree node name) with a placeholder.  The
+/// ids stay so the tab tree and layouts remain consistent.  Sessions the
+/// renderer itself marks incognito count too, which covers tabs whose
+/// shell has not been created yet in this run.
+pub fn redact_app_state_backup(
+    content: &str,
+    incognito: &HashSet<String>,
+) -> Result<String, PtyError> {
+    let mut snapshot: Value = serde_json::from_str(content)
+        .map_err(|err| PtyError::from(format!("Invalid app state backup: {}", err)))?;
+
+    let mut incognito = incognito.clone();
+    if let Some(sessions) = snapshot
+        .pointer("/dispatcher-terminals/state/sessions")
+        .and_then(Value::as_object)
+    {
+        incognito.extend(
+            sessions
+                .iter()
+                .filter(|(_, session)| session.get("isIncognito") == Some(&Value::Bool(true)))
+                .map(|(terminal_id, _)| terminal_id.clone()),
+        );
+    }
+    if incognito.is_empty() {
+        return Ok(content.to_owned());
+    }
+    let incognito = &incognito;
+
+    if let Some(sessions) = snapshot
+        .pointer_mut("/dispatcher-terminals/state/sessions")
+        .and_then(Value::as_object_mut)
+    {
+        for (terminal_id, session) in sessions.iter_mut() {
+            if incognito.contains(terminal_id) {
+                *session = placeholder_session(terminal_id);
+            }
+        }
+    }
+
+    if let Some(nodes) = snapshot
+        .pointer_mut("/dispatcher-projects/state/nodes")
+        .and_then(Value::as_object_mut)
+    {
+        for node in nodes.values_mut() {
+            let is_incognito = node
+                .get("terminalId")
+                .and_then(Value::as_str)
+                .is_some_and(|terminal_id| incognito.contains(terminal_id));
+            if is_incognito {
+                node["name"] = Value::from(INCOGNITO_TITLE);
+            }
+        }
+    }
+
+    // Free text that still names the terminal is renderer state this
+    // function does not know about; drop it rather than write it.
+    redact_free_text_mentions(&mut snapshot, incognito);
+    serde_json::to_string(&snapshot).map_err(|err| PtyError::from(err.to_string()))
+}
+
+fn placeholder_session(terminal_id: &str) -> Value {
+    let mut session = Map::new();
+    session.insert("id".into(), Value::from(terminal_id));
+    session.insert("title".into(), Value::from(INCOGNITO_TITLE));
+    session.insert("notes".into(), Value::from(""));
+    session.insert("hasDetectedActivity".into(), Value::from(false));
+    session.insert("lastUserInputAt".into(), Value::from(0));
+    session.insert("lastOutputAt".into(), Value::from(0));
+    session.insert("isNeedsAttention".into(), Value::from(false));
+    session.insert("isPossiblyDone".into(), Value::from(false));
+    session.insert("isLongInactive".into(), Value::from(false));
+    session.insert("isRecentlyFocused".into(), Value::from(false));
+    session.insert("isIncognito".into(), Value::from(true));
+    session.insert("backendKind".into(), Value::from("local"));
+    Value::Object(session)
+}
+
+/// Incognito ids may only appear as map keys, `id`/`terminalId` fields and
+/// layout references.  Free text such as titles or notes of other tabs that
+/// still names one is replaced as a whole.
+fn redact_free_text_mentions(value: &mut Value, incognito: &HashSet<String>) {
+    match value {
+        Value::String(text)
+            if !incognito.contains(text.as_str())
+                && mentioned_terminal(text, incognito).is_some() =>
+        {
+            *value = Value::from(REDACTED_TEXT);
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_free_text_mentions(item, incognito);
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                redact_free_text_mentions(item, incognito);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace each line of a renderer debug-log batch that names an incognito
+/// terminal with a placeholder, keeping the line count.
+pub fn redact_log_lines(message: &str, incognito: &HashSet<String>) -> String {
+    if incognito.is_empty() {
+        return message.to_owned();
+    }
+    message
+        .split_inclusive('\n')
+        .map(|line| {
+            if mentioned_terminal(line, incognito).is_none() {
+                return line;
+            }
+            if line.ends_with('\n') {
+                "<redacted: incognito>\n"
+            } else {
+                REDACTED_TEXT
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_incognito_sessions_and_node_names() {
+        let content = r#"{
+            "dispatcher-projects": {"state": {"nodes": {
+                "n1": {"id": "n1", "type": "terminal", "name": "prod db", "terminalId": "secret-1"},
+                "n2": {"id": "n2", "type": "terminal", "name": "dev", "terminalId": "plain-1"}
+            }}},
+            "dispatcher-terminals": {"state": {"sessions": {
+                "secret-1": {"id": "secret-1", "title": "prod db", "notes": "pw in 1password", "cwd": "/srv/prod"},
+                "plain-1": {"id": "plain-1", "title": "dev", "notes": "", "cwd": "/src"}
+            }, "activeTerminalId": "secret-1"}},
+            "dispatcher-layouts": {"state": {"layouts": {"secret-1": {"type": "terminal", "terminalId": "secret-1"}}}}
+        }"#;
+        let incognito: HashSet<String> = ["secret-1".to_string()].into_iter().collect();
+
+        let redacted = redact_app_state_backup(content, &incognito).unwrap();
+        assert!(!redacted.contains("prod db"));
+        assert!(!redacted.contains("/srv/prod"));
+        assert!(!redacted.contains("1password"));
+        assert!(redacted.contains("/src"));
+        assert!(redacted.contains(INCOGNITO_TITLE));
+
+        let leaky = content.replace("\"notes\": \"\"", "\"notes\": \"see secret-1\"");
+        let redacted = redact_app_state_backup(&leaky, &incognito).unwrap();
+        assert!(!redacted.contains("see secret-1"));
+        assert!(redacted.contains(REDACTED_TEXT));
+        assert!(redacted.contains("\"activeTerminalId\":\"secret-1\""));
+
+        let flagged = content.replace("\"cwd\": \"/src\"", "\"cwd\": \"/src\", \"isIncognito\": true");
+        let redacted = redact_app_state_backup(&flagged, &HashSet::new()).unwrap();
+        assert!(!redacted.contains("/src"));
+    }
+
+    #[test]
+    fn redacts_log_lines_that_name_incognito_terminals() {
+        let incognito: HashSet<String> = ["secret-1".to_string()].into_iter().collect();
+        let message = "a [x] terminal=plain-1 ok\nb [x] terminal=secret-1 ls ~/prod\nc tail";
+
+        let redacted = redact_log_lines(message, &incognito);
+        assert_eq!(
+            redacted,
+            "a [x] terminal=plain-1 ok\n<redacted: incognito>\nc tail"
+        );
+        assert_eq!(redact_log_lines(message, &HashSet::new()), message);
+    }
+}
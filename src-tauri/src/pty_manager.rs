@@ -1,15 +1,76 @@
+use crate::activity::{ActivityTracker, TerminalActivity, ACTIVITY_CHECK_INTERVAL};
+use crate::auto_responders::{AutoResponder, AutoResponderStatus, PendingResponse, ResponderSet};
+use crate::control_protocol::{identity_vars, IDENTITY_FILE_VAR, IDENTITY_VARS, SOCKET_VAR};
+use crate::control_socket::IdentityFile;
+use crate::env_activation::{
+    ActivationCache, ActivationLookup, ActivationVars, EnvActivation, ACTIVATION_TIMEOUT,
+};
+use crate::env_scrub::{
+    load_rules, project_rules_path, save_rules, EnvScrubRules, EnvScrubber, RemovedEnvVar,
+    ENV_SCRUB_FILE_NAME,
+};
 use crate::errors::PtyError;
+use crate::exit_waiters::ExitWaiters;
+use crate::hibernation::{
+    load_config as load_hibernation_config, save_config as save_hibernation_config,
+    HibernatedTerminal, HibernationConfig, TerminalHibernationPayload, HIBERNATION_CONFIG_FILE_NAME,
+};
+use crate::orphan_tracker::{OrphanTracker, OrphanedTerminal};
+use crate::project_env::{
+    file_stamps, history_file_name, load_configs, load_project_env, parse_config_env,
+    resolve_assignments, save_configs, EnvAssignment, FileStamps, ProjectEnvConfig,
+    PROJECT_ENV_FILE_NAME, PROJECT_HISTORY_DIR,
+};
+use crate::pattern_watchers::{
+    show_desktop_notification, PatternMatch, PatternScanner, PatternWatcher, WatcherSet,
+};
+use crate::safe_mode::{
+    reports_prompts, safe_mode_argv, safe_mode_banner, spawn_failed_message, STARTUP_PROMPT_TIMEOUT,
+};
+use crate::scrollback::{
+    format_restored_history, load_scrollback, prune_scrollback, remove_scrollback,
+    save_scrollback, ScrollbackBuffer,
+};
+use crate::session_store::{
+    now_ms, LocalSessionRecord, SessionStore, SESSION_PERSIST_INTERVAL, SESSION_STORE_FILE_NAME,
+};
+use crate::shell_integration::{
+    ShellEvent, ShellIntegrationParser, ShellMetadata, BASH_INTEGRATION, FISH_INTEGRATION,
+    INTEGRATION_ANNOUNCEMENT, ZSH_INTEGRATION,
+};
+use crate::spawn_queue::{DeferredTerminal, SpawnQueue};
+use crate::terminal_encoding::{lookup_encoding, TerminalTranscoder, UTF_8_NAME};
+use crate::terminal_export::{
+    export_file_name, render_export, ExportFormat, ExportRange, ExportTheme, EXPORT_DIR_NAME,
+};
+use crate::terminal_screen::{ScreenModel, TerminalScreen};
+use crate::terminal_search::{
+    build_matcher, search_source, SearchOptions, SearchSource, TerminalSearchMatch,
+    MAX_SEARCH_RESULTS,
+};
 use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::sync::{Arc, Mutex};
-use tauri::{ipc::Channel, AppHandle, Emitter};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::ipc::{Channel, JavaScriptChannelId};
+use tauri::{AppHandle, Emitter, Manager, Webview};
 
 const MAX_POOL_SIZE: usize = 3;
+const SESSION_MONITOR_INTERVAL: Duration = Duration::from_secs(60);
+/// How often pooled project shells are checked against their env files.
+const PROJECT_ENV_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// How long creating a terminal waits for a project's direnv/nix
+/// activation before starting the shell without it.
+const SPAWN_ACTIVATION_WAIT: Duration = Duration::from_secs(5);
+/// How long activating a terminal waits for the background spawner when it
+/// is already forking that terminal.
+const BACKGROUND_SPAWN_WAIT: Duration = Duration::from_secs(10);
 
 fn preview_terminal_data(data: &str, limit: usize) -> String {
     let mut preview = String::new();
@@ -67,93 +128,83 @@ fn shell_basename(shell: &str) -> &str {
     shell.rsplit('/').next().unwrap_or(shell)
 }
 
-fn remove_agent_environment(cmd: &mut CommandBuilder) {
-    let mut removed: Vec<String> = Vec::new();
-    let prefixes = [
-        "CLAUDE_",
-        "CODEX_",
-        "CODING_AGENT_",
-        "META_3PAI_",
-        "META_CLAUDE_",
-        "META_CODEX_",
-        "OTEL_",
-    ];
-    let exact_names = [
-        "AGENT",
-        "BUCK2_CLIENT_METADATA",
-        "CPE_RUST_X2P_SUPPORTS_VPNLESS",
-        "DOTSLASH_X2P_EDGETERM",
-        "ENABLE_AGENTS_CLI_TRACING_THRIFT",
-        "ENABLE_ENHANCED_TELEMETRY_BETA",
-        "JF_VPNLESS",
-        "LINTTOOL_CALLER",
-        "OPENAI_API_KEY",
-        "X2P_AGENT_PROXY_ADDRESS",
-        "X2P_INJECT_CAT",
-        "X2P_SUPPORTS_VPNLESS",
-    ];
-
-    for name in exact_names {
-        if cmd.get_env(name).is_some() {
-            cmd.env_remove(name);
-            removed.push(name.to_string());
-        }
-    }
-
-    let prefixed_names: Vec<String> = cmd
-        .iter_full_env_as_str()
-        .filter_map(|(name, _)| {
-            if prefixes.iter().any(|prefix| name.starts_with(prefix)) {
-                Some(name.to_string())
-            } else {
-                None
-            }
-        })
-        .collect();
-    for name in prefixed_names {
-        cmd.env_remove(&name);
-        removed.push(name);
+/// Remove inherited variables the scrub rules match.  Returns what was
+/// removed, sorted by name.
+fn remove_agent_environment(cmd: &mut CommandBuilder, scrubber: &EnvScrubber) -> Vec<RemovedEnvVar> {
+    let removed = scrubber.scan(cmd.iter_full_env_as_str());
+    for var in &removed {
+        cmd.env_remove(&var.name);
     }
+    removed
+}
 
-    for name in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
-        let should_remove = cmd
-            .get_env(name)
+/// The project's resolved assignments.  References to inherited variables
+/// see the environment after scrubbing.
+fn project_env_vars(cmd: &CommandBuilder, assignments: &[EnvAssignment]) -> Vec<(String, String)> {
+    resolve_assignments(assignments, |name| {
+        cmd.get_env(name)
             .and_then(|value| value.to_str())
-            .map(|value| {
-                value.contains("localhost:10054")
-                    || value.contains("127.0.0.1:10054")
-                    || value.contains("[::1]:10054")
-            })
-            .unwrap_or(false);
-        if should_remove {
-            cmd.env_remove(name);
-            removed.push(name.to_string());
+            .map(str::to_owned)
+    })
+}
+
+fn apply_activation_vars(cmd: &mut CommandBuilder, vars: &ActivationVars) {
+    for (name, value) in vars {
+        match value {
+            Some(value) => cmd.env(name, value),
+            None => cmd.env_remove(name),
         }
     }
+}
 
+fn apply_shell_env(cmd: &mut CommandBuilder, spawn_env: &SpawnEnv) {
+    let removed = remove_agent_environment(cmd, &spawn_env.scrubber);
     if !removed.is_empty() {
         #[cfg(not(test))]
         {
-            removed.sort();
-            removed.dedup();
+            let mut names: Vec<&str> = removed.iter().map(|var| var.name.as_str()).collect();
+            names.sort();
+            names.dedup();
             let _ = crate::debug_log::append_debug_log(&format!(
                 "[backend:pty_env] removed inherited agent variables names={}",
-                removed.join(",")
+                names.join(",")
             ));
         }
     }
-}
 
-fn apply_shell_env(cmd: &mut CommandBuilder) {
-    remove_agent_environment(cmd);
+    if !spawn_env.activation.is_empty() {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:env_activation] applied activation variables count={}",
+            spawn_env.activation.len()
+        ));
+    }
+    apply_activation_vars(cmd, &spawn_env.activation);
+
+    let project_vars = project_env_vars(cmd, &spawn_env.project_env);
+    if !project_vars.is_empty() {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:project_env] applied project variables count={}",
+            project_vars.len()
+        ));
+    }
+    for (name, value) in project_vars {
+        cmd.env(name, value);
+    }
 
     // Start each PTY as a clean terminal session instead of inheriting
     // emulator-specific parent metadata from Terminal.app/iTerm/tmux.
-    cmd.env("TERM", "xterm-256color");
+    // xterm.js renders 24-bit color, which TERM alone cannot say.
+    cmd.env("TERM", crate::terminfo::TERM);
+    cmd.env("COLORTERM", "truecolor");
+    let inherited_dirs = cmd
+        .get_env("TERMINFO_DIRS")
+        .map(|dirs| dirs.to_string_lossy().into_owned());
+    if let Some(dirs) = crate::terminfo::terminfo_dirs(inherited_dirs.as_deref()) {
+        cmd.env("TERMINFO_DIRS", dirs);
+    }
     cmd.env("TERM_PROGRAM", "Dispatcher");
-    cmd.env_remove("TERM_PROGRAM_VERSION");
+    cmd.env("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION"));
     cmd.env_remove("TERM_SESSION_ID");
-    cmd.env_remove("COLORTERM");
     cmd.env_remove("ITERM_PROFILE");
     cmd.env_remove("ITERM_SESSION_ID");
     cmd.env_remove("LC_TERMINAL");
@@ -165,19 +216,66 @@ fn apply_shell_env(cmd: &mut CommandBuilder) {
     cmd.env_remove("VTE_VERSION");
     cmd.env_remove("WT_SESSION");
 
+    // Read by the zsh and bash shims after the user's startup files, which
+    // commonly set HISTFILE themselves.
+    match spawn_env.history_file {
+        Some(ref path) => cmd.env("DISPATCHER_PROJECT_HISTFILE", path),
+        None => cmd.env_remove("DISPATCHER_PROJECT_HISTFILE"),
+    }
+
+    // Scripts find their tab and the control socket through these.
+    for name in IDENTITY_VARS {
+        cmd.env_remove(name);
+    }
+    cmd.env_remove(IDENTITY_FILE_VAR);
+    match spawn_env.identity {
+        Some(ShellIdentity::Terminal(ref vars)) => {
+            for (name, value) in vars {
+                cmd.env(name, value);
+            }
+        }
+        Some(ShellIdentity::Pending(ref path)) => cmd.env(IDENTITY_FILE_VAR, path),
+        None => {}
+    }
+    match crate::control_socket::socket_path() {
+        Some(path) => cmd.env(SOCKET_VAR, path),
+        None => cmd.env_remove(SOCKET_VAR),
+    }
+
     match shell_basename(&cmd.get_shell()) {
-        // Keep bash history effectively unlimited.
+        // Keep bash history effectively unlimited, and start it with an
+        // --rcfile wrapper that runs the user's login files before the
+        // integration.
         "bash" => {
             cmd.env("HISTSIZE", "999999999");
             cmd.env("HISTFILESIZE", "999999999");
+            if let Err(err) = configure_bash_startup(cmd) {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:startup:error] failed to configure bash startup shim error={}",
+                    err
+                ));
+            }
         }
         // Run zsh through a small proxy ZDOTDIR so Dispatcher can preserve the
         // user's existing startup files while restoring the standard Ctrl+R
-        // history search binding in app-spawned sessions. Other shells keep
-        // their normal startup behavior unless they need a targeted fix.
+        // history search binding in app-spawned sessions.
         "zsh" => {
             if let Err(err) = configure_zsh_startup(cmd) {
-                eprintln!("dispatcher: failed to configure zsh startup shim: {err}");
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:startup:error] failed to configure zsh startup shim error={}",
+                    err
+                ));
+            }
+        }
+        // Fish reads its own startup files as usual; the integration comes in
+        // as a vendor conf.d snippet. Other shells keep their normal startup
+        // behavior unless they need a targeted fix.
+        "fish" => {
+            if let Err(err) = configure_fish_startup(cmd) {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:startup:error] failed to configure fish startup shim error={}",
+                    err
+                ));
             }
         }
         _ => {}
@@ -200,7 +298,7 @@ mod tests {
         cmd.env("HTTP_PROXY", "http://localhost:10054");
         cmd.env("HTTPS_PROXY", "http://corp-proxy.example.com:8080");
 
-        remove_agent_environment(&mut cmd);
+        remove_agent_environment(&mut cmd, &EnvScrubber::default());
 
         assert_eq!(
             cmd.get_env("USER").and_then(|value| value.to_str()),
@@ -216,14 +314,41 @@ mod tests {
             Some("http://corp-proxy.example.com:8080"),
         );
     }
+
+    #[test]
+    fn hibernation_keeps_incognito_scrollback_off_disk() {
+        let dir = std::env::temp_dir().join(format!("dispatcher-hibernate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let incognito = TerminalSpawnOptions {
+            incognito: true,
+            ..TerminalSpawnOptions::default()
+        };
+
+        save_hibernated_scrollback(Some(&dir), "secret", &incognito, b"token=abc\r\n");
+        save_hibernated_scrollback(
+            Some(&dir),
+            "plain",
+            &TerminalSpawnOptions::default(),
+            b"ls\r\n",
+        );
+
+        assert!(!crate::scrollback::scrollback_path(&dir, "secret").exists());
+        assert!(crate::scrollback::scrollback_path(&dir, "plain").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn closing_a_terminal_without_a_shell_wakes_exit_waiters() {
+        let manager = PtyManager::new();
+        let (_, waiter) = manager.exit_waiters.register("deferred");
+
+        manager.close_terminal("deferred").unwrap();
+        assert_eq!(waiter.try_recv(), Ok(None));
+    }
 }
 
 fn configure_zsh_startup(cmd: &mut CommandBuilder) -> Result<(), PtyError> {
-    let home = cmd
-        .get_env("HOME")
-        .and_then(|v| v.to_str())
-        .ok_or_else(|| PtyError::from(String::from("HOME is not set")))?
-        .to_owned();
+    let home = shim_home(cmd)?;
 
     let original_zdotdir = cmd
         .get_env("ZDOTDIR")
@@ -241,38 +366,60 @@ fn configure_zsh_startup(cmd: &mut CommandBuilder) -> Result<(), PtyError> {
     let shim_dir = Path::new(&home).join(".dispatcher").join("zsh");
     fs::create_dir_all(&shim_dir).map_err(PtyError::from)?;
 
-    write_zsh_shim_file(
+    write_shim_file(
         &shim_dir.join(".zshenv"),
-        r#"if [ -n "${DISPATCHER_ORIG_ZDOTDIR:-}" ] && [ -r "${DISPATCHER_ORIG_ZDOTDIR}/.zshenv" ]; then
+        &format!(
+            "if [[ -o interactive ]]; then\n  {}\nfi\n{}",
+            INTEGRATION_ANNOUNCEMENT,
+            r#"if [ -n "${DISPATCHER_ORIG_ZDOTDIR:-}" ] && [ -r "${DISPATCHER_ORIG_ZDOTDIR}/.zshenv" ]; then
   . "${DISPATCHER_ORIG_ZDOTDIR}/.zshenv"
 fi
-"#,
+"#
+        ),
     )?;
-    write_zsh_shim_file(
+    write_shim_file(
         &shim_dir.join(".zprofile"),
         r#"if [ -n "${DISPATCHER_ORIG_ZDOTDIR:-}" ] && [ -r "${DISPATCHER_ORIG_ZDOTDIR}/.zprofile" ]; then
   . "${DISPATCHER_ORIG_ZDOTDIR}/.zprofile"
 fi
 "#,
     )?;
-    write_zsh_shim_file(
+    write_shim_file(
         &shim_dir.join(".zshrc"),
-        r#"if [ -n "${DISPATCHER_ORIG_HISTFILE:-}" ]; then
+        &format!(
+            "{}{}",
+            r#"if [ -n "${DISPATCHER_ORIG_HISTFILE:-}" ]; then
   HISTFILE="${DISPATCHER_ORIG_HISTFILE}"
   export HISTFILE
 fi
 if [ -n "${DISPATCHER_ORIG_ZDOTDIR:-}" ] && [ -r "${DISPATCHER_ORIG_ZDOTDIR}/.zshrc" ]; then
   . "${DISPATCHER_ORIG_ZDOTDIR}/.zshrc"
 fi
-if [ -n "${HISTFILE:-}" ] && [ -r "${HISTFILE}" ]; then
+if [ -n "${DISPATCHER_PROJECT_HISTFILE:-}" ]; then
+  DISPATCHER_GLOBAL_HISTFILE="${HISTFILE:-}"
+  fc -p "${DISPATCHER_PROJECT_HISTFILE}"
+elif [ -n "${HISTFILE:-}" ] && [ -r "${HISTFILE}" ]; then
   fc -R "${HISTFILE}" 2>/dev/null || true
 fi
 bindkey '^R' history-incremental-search-backward 2>/dev/null || true
 bindkey -M emacs '^R' history-incremental-search-backward 2>/dev/null || true
 bindkey -M viins '^R' history-incremental-search-backward 2>/dev/null || true
+if [ -n "${DISPATCHER_GLOBAL_HISTFILE:-}" ]; then
+  __dispatcher_global_history_search() {
+    fc -ap "${DISPATCHER_GLOBAL_HISTFILE}"
+    # Read-only: the pop would otherwise rewrite the global file.
+    unset HISTFILE
+    zle history-incremental-search-backward
+  }
+  zle -N __dispatcher_global_history_search
+  bindkey -M emacs '^[r' __dispatcher_global_history_search 2>/dev/null || true
+  bindkey -M viins '^[r' __dispatcher_global_history_search 2>/dev/null || true
+fi
 "#,
+            ZSH_INTEGRATION
+        ),
     )?;
-    write_zsh_shim_file(
+    write_shim_file(
         &shim_dir.join(".zlogin"),
         r#"if [ -n "${DISPATCHER_ORIG_ZDOTDIR:-}" ] && [ -r "${DISPATCHER_ORIG_ZDOTDIR}/.zlogin" ]; then
   . "${DISPATCHER_ORIG_ZDOTDIR}/.zlogin"
@@ -287,10 +434,311 @@ fi
     Ok(())
 }
 
-fn write_zsh_shim_file(path: &PathBuf, content: &str) -> Result<(), PtyError> {
+fn write_shim_file(path: &PathBuf, content: &str) -> Result<(), PtyError> {
     fs::write(path, content).map_err(PtyError::from)
 }
 
+fn shim_home(cmd: &CommandBuilder) -> Result<String, PtyError> {
+    cmd.get_env("HOME")
+        .and_then(|v| v.to_str())
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| PtyError::from(String::from("HOME is not set")))
+}
+
+/// Bash ignores --rcfile in a login shell, so the wrapper starts an
+/// interactive shell and reads the login files itself, in bash's order.
+fn configure_bash_startup(cmd: &mut CommandBuilder) -> Result<(), PtyError> {
+    if !cmd.is_default_prog() {
+        return Ok(());
+    }
+    let home = shim_home(cmd)?;
+    let shim_dir = Path::new(&home).join(".dispatcher").join("bash");
+    fs::create_dir_all(&shim_dir).map_err(PtyError::from)?;
+
+    let rcfile = shim_dir.join("bashrc");
+    write_shim_file(
+        &rcfile,
+        &format!(
+            "{}\n{}{}",
+            INTEGRATION_ANNOUNCEMENT,
+            r#"if [ -r /etc/profile ]; then
+  . /etc/profile
+fi
+if [ -r "$HOME/.bash_profile" ]; then
+  . "$HOME/.bash_profile"
+elif [ -r "$HOME/.bash_login" ]; then
+  . "$HOME/.bash_login"
+elif [ -r "$HOME/.profile" ]; then
+  . "$HOME/.profile"
+fi
+bind '"\C-r": reverse-search-history' 2>/dev/null || true
+if [ -n "${DISPATCHER_PROJECT_HISTFILE:-}" ]; then
+  DISPATCHER_GLOBAL_HISTFILE="${HISTFILE:-$HOME/.bash_history}"
+  # Bash reads HISTFILE once this file is done.
+  HISTFILE="${DISPATCHER_PROJECT_HISTFILE}"
+  history -c
+  __dispatcher_load_global_history() {
+    history -a
+    history -c
+    history -r "${DISPATCHER_GLOBAL_HISTFILE}"
+    __dispatcher_global_history_loaded=1
+  }
+  # Commands run meanwhile are new entries, so `history -a` saves them to
+  # the project file.
+  __dispatcher_restore_history() {
+    if [ -n "${__dispatcher_global_history_loaded:-}" ]; then
+      __dispatcher_global_history_loaded=
+      history -a
+      history -c
+      history -r
+    fi
+  }
+  PROMPT_COMMAND="__dispatcher_restore_history${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+  bind -x '"\C-x\C-]": __dispatcher_load_global_history' 2>/dev/null || true
+  # end-of-history resets readline's position in the swapped-in list.
+  bind '"\er": "\C-x\C-]\e>\C-r"' 2>/dev/null || true
+fi
+"#,
+            BASH_INTEGRATION
+        ),
+    )?;
+
+    let shell = cmd.get_shell();
+    cmd.get_argv_mut().extend([
+        shell.into(),
+        OsString::from("--rcfile"),
+        rcfile.into_os_string(),
+    ]);
+    Ok(())
+}
+
+/// Fish sources `fish/vendor_conf.d` under every XDG_DATA_DIRS entry before
+/// the user's config, so a proxy entry carries the snippet.  The snippet
+/// restores XDG_DATA_DIRS so programs started from the shell see the
+/// original value.
+fn configure_fish_startup(cmd: &mut CommandBuilder) -> Result<(), PtyError> {
+    let home = shim_home(cmd)?;
+    let data_dir = Path::new(&home).join(".dispatcher").join("fish");
+    let conf_dir = data_dir.join("fish").join("vendor_conf.d");
+    fs::create_dir_all(&conf_dir).map_err(PtyError::from)?;
+
+    write_shim_file(
+        &conf_dir.join("dispatcher.fish"),
+        &format!(
+            "if status is-interactive\n    {}\nend\n{}{}",
+            INTEGRATION_ANNOUNCEMENT,
+            r#"if set -q DISPATCHER_ORIG_XDG_DATA_DIRS
+    set -gx XDG_DATA_DIRS $DISPATCHER_ORIG_XDG_DATA_DIRS
+    set -e DISPATCHER_ORIG_XDG_DATA_DIRS
+else
+    set -e XDG_DATA_DIRS
+end
+"#,
+            FISH_INTEGRATION
+        ),
+    )?;
+
+    // The XDG default applies when the variable is unset or empty.
+    let original = cmd
+        .get_env("XDG_DATA_DIRS")
+        .and_then(|v| v.to_str())
+        .filter(|v| !v.is_empty())
+        .map(ToOwned::to_owned);
+    let search_dirs = original.as_deref().unwrap_or("/usr/local/share:/usr/share");
+    cmd.env(
+        "XDG_DATA_DIRS",
+        format!("{}:{}", data_dir.to_string_lossy(), search_dirs),
+    );
+    match original {
+        Some(original) => cmd.env("DISPATCHER_ORIG_XDG_DATA_DIRS", original),
+        None => cmd.env_remove("DISPATCHER_ORIG_XDG_DATA_DIRS"),
+    }
+    Ok(())
+}
+
+/// Save a hibernating terminal's output so it survives a quit.  Incognito
+/// output is only kept in memory, for when the terminal wakes.
+fn save_hibernated_scrollback(
+    dir: Option<&Path>,
+    terminal_id: &str,
+    options: &TerminalSpawnOptions,
+    scrollback: &[u8],
+) {
+    if options.incognito {
+        return;
+    }
+    if let Some(dir) = dir {
+        let _ = save_scrollback(dir, terminal_id, scrollback);
+    }
+}
+
+/// True when the shell itself owns the terminal's foreground process group,
+/// i.e. it is waiting at a prompt rather than running a command.
+fn foreground_is_shell(session: &PtySession) -> bool {
+    #[cfg(unix)]
+    {
+        let Some(fd) = session.master.as_raw_fd() else {
+            return false;
+        };
+        let pgid = unsafe { libc::tcgetpgrp(fd) };
+        let shell_pid = session
+            .child
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|child| child.process_id());
+        pgid > 0 && shell_pid == Some(pgid as u32)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = session;
+        false
+    }
+}
+
+/// Echo and line-editing flags of a PTY, read from its termios.
+#[derive(Clone, Copy)]
+struct InputMode {
+    echo: bool,
+    canonical: bool,
+}
+
+impl InputMode {
+    /// sudo, ssh and friends read passwords with echo off and line editing
+    /// still on; full-screen programs turn both off.
+    fn is_secure(self) -> bool {
+        !self.echo && self.canonical
+    }
+}
+
+#[cfg(unix)]
+fn read_input_mode(fd: std::os::fd::RawFd) -> Option<InputMode> {
+    let mut termios = unsafe { std::mem::MaybeUninit::<libc::termios>::zeroed().assume_init() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return None;
+    }
+    Some(InputMode {
+        echo: termios.c_lflag & libc::ECHO != 0,
+        canonical: termios.c_lflag & libc::ICANON != 0,
+    })
+}
+
+fn master_input_mode(master: &dyn MasterPty) -> Option<InputMode> {
+    #[cfg(unix)]
+    {
+        master.as_raw_fd().and_then(read_input_mode)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = master;
+        None
+    }
+}
+
+/// Whether the terminal echoes input.  Password prompts turn echo off, and
+/// nothing should be typed on the user's behalf while they are up.
+fn input_echo_enabled(master: &dyn MasterPty) -> bool {
+    #[cfg(unix)]
+    {
+        master_input_mode(master).is_some_and(|mode| mode.echo)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = master;
+        true
+    }
+}
+
+/// Duplicate of a PTY master fd, so the reader thread can check the input
+/// mode after each chunk without locking the session.
+struct InputModeProbe {
+    #[cfg(unix)]
+    fd: Option<std::os::fd::OwnedFd>,
+}
+
+impl InputModeProbe {
+    fn new(master: &dyn MasterPty) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::fd::FromRawFd;
+            let fd = master.as_raw_fd().and_then(|fd| {
+                let duplicate = unsafe { libc::dup(fd) };
+                (duplicate >= 0).then(|| unsafe { std::os::fd::OwnedFd::from_raw_fd(duplicate) })
+            });
+            InputModeProbe { fd }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = master;
+            InputModeProbe {}
+        }
+    }
+
+    fn read(&self) -> Option<InputMode> {
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            self.fd.as_ref().and_then(|fd| read_input_mode(fd.as_raw_fd()))
+        }
+
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+}
+
+fn env_scrub_rules_path(app_handle: &AppHandle) -> Result<PathBuf, PtyError> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|err| PtyError::from(err.to_string()))?;
+    Ok(dir.join(ENV_SCRUB_FILE_NAME))
+}
+
+fn hibernation_config_path(app_handle: &AppHandle) -> Result<PathBuf, PtyError> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|err| PtyError::from(err.to_string()))?;
+    Ok(dir.join(HIBERNATION_CONFIG_FILE_NAME))
+}
+
+fn project_env_configs_path(app_handle: &AppHandle) -> Result<PathBuf, PtyError> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|err| PtyError::from(err.to_string()))?;
+    Ok(dir.join(PROJECT_ENV_FILE_NAME))
+}
+
+/// Creates the history directory, so the shell can write the file.
+fn project_history_path(app_handle: &AppHandle, project_id: &str) -> Result<PathBuf, PtyError> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|err| PtyError::from(err.to_string()))?
+        .join(PROJECT_HISTORY_DIR);
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(history_file_name(project_id)))
+}
+
+/// A notice in the terminal itself, for problems the user should see
+/// without opening the debug log.
+fn environment_notice_output(terminal_id: &str, notice: &str) -> TerminalOutput {
+    TerminalOutput {
+        terminal_id: terminal_id.to_owned(),
+        data: format!(
+            "\x1b[31m[{}]\x1b[0m\r\n",
+            notice.replace(['\n', '\r'], " ")
+        ),
+    }
+}
+
 fn clear_problematic_control_chars(master: &dyn MasterPty) {
     #[cfg(unix)]
     if let Some(fd) = master.as_raw_fd() {
@@ -382,6 +830,275 @@ enum OutputMode {
 struct OutputRouter {
     mode: OutputMode,
     assigned_id: Option<String>,
+    shell: ShellIntegrationParser,
+    metadata: ShellMetadata,
+    metadata_updated_at_ms: u64,
+    scrollback: ScrollbackBuffer,
+    /// Scrollback offsets of the most recent command's output (OSC 133 C to
+    /// D); the end is open while the command is still running.
+    last_command_output: Option<(u64, Option<u64>)>,
+    last_activity_at: Instant,
+    activity: ActivityTracker,
+    screen: ScreenModel,
+    /// Project of the assigned terminal, for project-scoped watchers.
+    project_id: Option<String>,
+    patterns: PatternScanner,
+    responders: ResponderSet,
+    /// The terminal is reading a password; input must not be logged.
+    secure_input: bool,
+    /// Output and input of this terminal stay out of every log.
+    incognito: bool,
+    /// Set when the terminal uses a legacy encoding; output is converted to
+    /// UTF-8 before anything else sees it.
+    transcoder: Option<TerminalTranscoder>,
+    /// The shell's startup shim marks prompts; otherwise any output counts
+    /// as the first prompt.
+    prompt_marks: bool,
+    /// The shim announced itself, so the prompt marks will come once the
+    /// user's startup files are done.
+    integration_loaded: bool,
+    prompt_seen: bool,
+    /// Restored command to type, without Enter, at the first marked prompt.
+    offered_command: Option<String>,
+    /// The renderer was told no prompt appeared in time.
+    startup_stalled: bool,
+    /// `Some` once the shell has exited, holding its exit code if it has
+    /// one.
+    exit: Option<Option<i32>>,
+}
+
+/// Follow-up work for one chunk of output, done once the router lock is
+/// released.
+#[derive(Default)]
+struct RouteEffects {
+    pattern_matches: Vec<PatternMatch>,
+    responses: Vec<PendingResponse>,
+    secure_input: Option<SecureInputPayload>,
+    startup_stall: Option<StartupStalledPayload>,
+    offered_command: Option<String>,
+}
+
+impl OutputRouter {
+    fn new(mode: OutputMode, assigned_id: Option<String>, cols: u16, rows: u16) -> Self {
+        OutputRouter {
+            mode,
+            assigned_id,
+            shell: ShellIntegrationParser::default(),
+            metadata: ShellMetadata::default(),
+            metadata_updated_at_ms: now_ms(),
+            scrollback: ScrollbackBuffer::default(),
+            last_command_output: None,
+            last_activity_at: Instant::now(),
+            activity: ActivityTracker::new(Instant::now()),
+            screen: ScreenModel::new(cols, rows),
+            project_id: None,
+            patterns: PatternScanner::default(),
+            responders: ResponderSet::default(),
+            secure_input: false,
+            incognito: false,
+            transcoder: None,
+            prompt_marks: false,
+            integration_loaded: false,
+            prompt_seen: false,
+            offered_command: None,
+            startup_stalled: false,
+            exit: None,
+        }
+    }
+
+    /// Take on the per-terminal settings of the terminal this PTY now
+    /// serves.
+    fn apply_spawn_options(&mut self, terminal_id: &str, options: &TerminalSpawnOptions) {
+        self.project_id = options.project_id.clone();
+        self.incognito = options.incognito;
+        if let Err(err) = self.responders.set_rules(options.auto_responders.clone()) {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:auto_respond] terminal_id={} ignoring invalid rules error={}",
+                terminal_id, err
+            ));
+        }
+        let encoding = match options.encoding.as_deref().map(lookup_encoding) {
+            Some(Ok(encoding)) => encoding,
+            Some(Err(err)) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:encoding] terminal_id={} using UTF-8 error={}",
+                    terminal_id, err
+                ));
+                None
+            }
+            None => None,
+        };
+        self.transcoder = encoding.map(TerminalTranscoder::new);
+    }
+
+    /// Input in the terminal's encoding.  Everything written to the shell
+    /// goes through here so the encoder sees one continuous stream.
+    fn encode_input<'a>(&mut self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.transcoder.as_mut() {
+            Some(transcoder) => Cow::Owned(transcoder.encode(data)),
+            None => Cow::Borrowed(data),
+        }
+    }
+
+    /// Record the terminal's current input mode.  Returns the change event
+    /// when secure input started or ended.
+    fn update_secure_input(&mut self, mode: Option<InputMode>) -> Option<SecureInputPayload> {
+        let active = mode.is_some_and(InputMode::is_secure);
+        if active == self.secure_input {
+            return None;
+        }
+        self.secure_input = active;
+        let terminal_id = self.assigned_id.clone()?;
+        Some(SecureInputPayload {
+            terminal_id,
+            active,
+        })
+    }
+
+    fn route(&mut self, bytes: &[u8], watchers: &WatcherSet) -> RouteEffects {
+        let events = self.shell.feed(bytes);
+        if events
+            .iter()
+            .any(|(_, event)| *event == ShellEvent::IntegrationLoaded)
+        {
+            self.integration_loaded = true;
+        }
+        let prompted = if self.prompt_marks {
+            events.iter().any(|(_, event)| *event == ShellEvent::PromptStart)
+        } else {
+            !bytes.is_empty()
+        };
+        let mut recovered = false;
+        let mut offered_command = None;
+        if prompted {
+            self.prompt_seen = true;
+            recovered = std::mem::take(&mut self.startup_stalled);
+            offered_command = self.offered_command.take();
+        }
+        if !events.is_empty() {
+            let base = self.scrollback.end_offset();
+            let streaming = matches!(self.mode, OutputMode::Streaming { .. });
+            for (offset, event) in &events {
+                self.metadata.apply(event);
+                if !streaming {
+                    continue;
+                }
+                let position = base + *offset as u64;
+                match event {
+                    ShellEvent::CommandStart => self.last_command_output = Some((position, None)),
+                    ShellEvent::CommandFinished(_) => {
+                        if let Some((_, end @ None)) = &mut self.last_command_output {
+                            *end = Some(position);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.metadata_updated_at_ms = now_ms();
+        }
+        self.screen.advance(bytes);
+
+        match &mut self.mode {
+            OutputMode::Buffering(buffer) => {
+                buffer.extend_from_slice(bytes);
+            }
+            OutputMode::Streaming {
+                channel,
+                terminal_id,
+            } => {
+                let received_at_ms = now_ms();
+                self.scrollback.push(bytes, received_at_ms);
+                self.last_activity_at = Instant::now();
+                self.activity
+                    .record_output(bytes, self.last_activity_at, received_at_ms);
+                let data = String::from_utf8_lossy(bytes).to_string();
+                if !self.incognito {
+                    log_protocol_output_chunk(terminal_id, &data);
+                }
+                let _ = channel.send(TerminalOutput {
+                    terminal_id: terminal_id.clone(),
+                    data,
+                });
+                let mut effects = RouteEffects {
+                    offered_command,
+                    ..RouteEffects::default()
+                };
+                if recovered {
+                    effects.startup_stall = Some(StartupStalledPayload {
+                        terminal_id: terminal_id.clone(),
+                        stalled: false,
+                    });
+                }
+                if !watchers.is_empty() {
+                    effects.pattern_matches = self.patterns.scan(
+                        bytes,
+                        watchers,
+                        terminal_id,
+                        self.project_id.as_deref(),
+                        self.last_activity_at,
+                    );
+                }
+                if !self.responders.is_empty() {
+                    effects.responses = self.responders.scan(bytes, self.last_activity_at);
+                }
+                return effects;
+            }
+        }
+        RouteEffects::default()
+    }
+}
+
+fn emit_secure_input_changed(app_handle: &AppHandle, payload: SecureInputPayload) {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:secure_input] terminal_id={} active={}",
+        payload.terminal_id, payload.active
+    ));
+    let _ = app_handle.emit("secure-input-changed", payload);
+}
+
+fn emit_startup_stalled(app_handle: &AppHandle, payload: StartupStalledPayload) {
+    let _ = crate::debug_log::append_debug_log(&format!(
+        "[backend:startup] terminal_id={} stalled={}",
+        payload.terminal_id, payload.stalled
+    ));
+    let _ = app_handle.emit("terminal-startup-stalled", payload);
+}
+
+fn dispatch_route_effects(app_handle: &AppHandle, terminal_id: Option<&str>, effects: RouteEffects) {
+    if let Some(payload) = effects.secure_input {
+        emit_secure_input_changed(app_handle, payload);
+    }
+    if let Some(payload) = effects.startup_stall {
+        emit_startup_stalled(app_handle, payload);
+    }
+    if let (Some(terminal_id), Some(command)) = (terminal_id, effects.offered_command) {
+        let _ = app_handle.state::<PtyManager>().write_input(
+            app_handle,
+            terminal_id,
+            command.as_bytes(),
+        );
+    }
+    if let (Some(terminal_id), false) = (terminal_id, effects.responses.is_empty()) {
+        app_handle
+            .state::<PtyManager>()
+            .send_auto_responses(terminal_id, effects.responses);
+    }
+
+    for matched in effects.pattern_matches {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:pattern_watch] watcher_id={} terminal_id={} notify={} needs_attention={}",
+            matched.watcher_id, matched.terminal_id, matched.notify, matched.needs_attention
+        ));
+        if matched.notify {
+            let title = if matched.watcher_name.is_empty() {
+                "Dispatcher"
+            } else {
+                matched.watcher_name.as_str()
+            };
+            show_desktop_notification(title, &matched.line);
+        }
+        let _ = app_handle.emit("terminal-pattern-matched", matched);
+    }
 }
 
 // -- Session types --
@@ -390,6 +1107,13 @@ struct PtySession {
     master: Box<dyn MasterPty + Send>,
     writer: Box<dyn Write + Send>,
     child: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>>,
+    router: Arc<Mutex<OutputRouter>>,
+    created_at: Instant,
+    spawn_cwd: Option<String>,
+    options: TerminalSpawnOptions,
+    /// Kept from the pool only to be removed when the session ends, for
+    /// shells that never read it.
+    _identity_file: Option<IdentityFile>,
 }
 
 struct PoolEntry {
@@ -397,11 +1121,113 @@ struct PoolEntry {
     writer: Box<dyn Write + Send>,
     child: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>>,
     router: Arc<Mutex<OutputRouter>>,
+    /// Set for shells started with a project's environment; they are only
+    /// handed to terminals of that project.
+    project: Option<ProjectKey>,
+    /// The project's env and rules files when the shell started.
+    stamps: FileStamps,
+    /// Where the shell looks for its identity once assigned.
+    identity_file: Option<IdentityFile>,
+}
+
+/// A terminal whose shell could not be started, kept until the user asks
+/// for safe mode or closes it.
+struct FailedSpawn {
+    cwd: Option<String>,
+    cols: u16,
+    rows: u16,
+    options: TerminalSpawnOptions,
+    channel: Channel<TerminalOutput>,
+    shell: String,
+    reason: String,
+}
+
+impl PoolEntry {
+    fn kill(self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+        }
+        // Dropping master/writer closes the PTY fds; the reader thread
+        // will see EOF and exit on its own.  The identity file goes with
+        // the entry.
+    }
+}
+
+/// A sidebar project as the backend sees it: an id for stored settings and
+/// a root directory for files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectKey {
+    pub id: Option<String>,
+    pub root: String,
+}
+
+impl ProjectKey {
+    fn from_options(options: &TerminalSpawnOptions) -> Option<ProjectKey> {
+        let root = options.project_root.clone().filter(|root| !root.is_empty())?;
+        Some(ProjectKey {
+            id: options.project_id.clone(),
+            root,
+        })
+    }
+}
+
+/// Everything `apply_shell_env` needs besides the command itself.
+struct SpawnEnv {
+    scrubber: EnvScrubber,
+    /// direnv / nix variables, applied before the project's own.
+    activation: ActivationVars,
+    project_env: Vec<EnvAssignment>,
+    /// Parts of the project's environment that could not be applied, to be
+    /// shown in the terminal.
+    notices: Vec<String>,
+    /// The project's own HISTFILE, when it keeps a separate history.
+    history_file: Option<PathBuf>,
+    identity: Option<ShellIdentity>,
+}
+
+/// Which terminal a shell belongs to.
+enum ShellIdentity {
+    /// The identity variables of the shell's terminal.
+    Terminal(Vec<(&'static str, String)>),
+    /// A pooled shell; its identity is written to this file on assignment.
+    Pending(PathBuf),
+}
+
+fn terminal_identity(
+    terminal_id: &str,
+    options: &TerminalSpawnOptions,
+) -> Vec<(&'static str, String)> {
+    identity_vars(
+        terminal_id,
+        options.project_id.as_deref(),
+        options.project_root.as_deref(),
+    )
 }
 
 pub struct PtyManager {
     sessions: Mutex<HashMap<String, PtySession>>,
     pool: Mutex<Vec<PoolEntry>>,
+    spawn_queue: Arc<SpawnQueue>,
+    orphans: Mutex<OrphanTracker>,
+    session_store: Mutex<SessionStore>,
+    hibernation_config: Mutex<HibernationConfig>,
+    hibernated: Mutex<HashMap<String, HibernatedTerminal>>,
+    watchers: Arc<RwLock<WatcherSet>>,
+    /// Terminals created with the incognito flag, whether live, deferred or
+    /// hibernated.
+    incognito: Mutex<HashSet<String>>,
+    /// User rules for inherited variables; projects may extend them.
+    env_scrub: Mutex<EnvScrubRules>,
+    /// Stored project environments, keyed by project id.
+    project_env: Mutex<HashMap<String, ProjectEnvConfig>>,
+    activation: Arc<ActivationCache>,
+    exit_waiters: Arc<ExitWaiters>,
+    /// Input typed into terminals whose shell is still being started, kept
+    /// in order until the shell exists.
+    pending_input: Mutex<HashMap<String, Vec<u8>>>,
+    /// Projects with a thread waiting on their activation.
+    awaiting_activation: Mutex<Vec<ProjectKey>>,
+    failed_spawns: Mutex<HashMap<String, FailedSpawn>>,
 }
 
 impl PtyManager {
@@ -409,110 +1235,1429 @@ impl PtyManager {
         PtyManager {
             sessions: Mutex::new(HashMap::new()),
             pool: Mutex::new(Vec::new()),
+            spawn_queue: Arc::new(SpawnQueue::new()),
+            orphans: Mutex::new(OrphanTracker::default()),
+            session_store: Mutex::new(SessionStore::default()),
+            hibernation_config: Mutex::new(HibernationConfig::default()),
+            hibernated: Mutex::new(HashMap::new()),
+            watchers: Arc::new(RwLock::new(WatcherSet::default())),
+            incognito: Mutex::new(HashSet::new()),
+            env_scrub: Mutex::new(EnvScrubRules::default()),
+            project_env: Mutex::new(HashMap::new()),
+            activation: Arc::new(ActivationCache::default()),
+            exit_waiters: Arc::new(ExitWaiters::default()),
+            pending_input: Mutex::new(HashMap::new()),
+            awaiting_activation: Mutex::new(Vec::new()),
+            failed_spawns: Mutex::new(HashMap::new()),
         }
     }
 
-    /// Pre-spawn PTYs into the pool, up to MAX_POOL_SIZE total.
-    pub fn warm_pool(&self, app_handle: &AppHandle, count: usize) -> Result<(), PtyError> {
-        let current = self.pool.lock().unwrap().len();
-        let to_spawn = count.min(MAX_POOL_SIZE.saturating_sub(current));
-        for _ in 0..to_spawn {
-            self.spawn_to_pool(app_handle)?;
+    pub fn hibernation_config(&self) -> HibernationConfig {
+        *self.hibernation_config.lock().unwrap()
+    }
+
+    pub fn is_incognito(&self, terminal_id: &str) -> bool {
+        self.incognito.lock().unwrap().contains(terminal_id)
+    }
+
+    pub fn incognito_terminals(&self) -> HashSet<String> {
+        self.incognito.lock().unwrap().clone()
+    }
+
+    fn register_incognito(&self, terminal_id: &str, options: &TerminalSpawnOptions) {
+        if options.incognito {
+            self.incognito.lock().unwrap().insert(terminal_id.to_owned());
         }
+    }
+
+    pub fn pattern_watchers(&self) -> Vec<PatternWatcher> {
+        self.watchers.read().unwrap().watchers()
+    }
+
+    /// Replace all watchers.  Nothing changes if any pattern fails to compile.
+    pub fn set_pattern_watchers(&self, watchers: Vec<PatternWatcher>) -> Result<(), PtyError> {
+        let compiled = WatcherSet::compile(watchers)?;
+        *self.watchers.write().unwrap() = compiled;
         Ok(())
     }
 
-    /// Drain all pooled PTYs and spawn fresh replacements so that shell
-    /// history, environment variables, etc. are up-to-date.
-    pub fn refresh_pool(&self, app_handle: &AppHandle) -> Result<(), PtyError> {
-        let old: Vec<PoolEntry> = {
-            let mut pool = self.pool.lock().unwrap();
-            pool.drain(..).collect()
-        };
-        // Kill old shell processes.
-        for entry in old {
-            if let Some(mut child) = entry.child.lock().unwrap().take() {
-                let _ = child.kill();
+    /// Load the user's scrub rules.  Called at startup, before the renderer
+    /// warms the pool; a broken file keeps the built-in rules.
+    pub fn load_env_scrub_rules(&self, app_handle: &AppHandle) {
+        let loaded = env_scrub_rules_path(app_handle).and_then(|path| load_rules(&path));
+        let loaded = loaded.and_then(|rules| {
+            if let Some(ref rules) = rules {
+                rules.compile()?;
+            }
+            Ok(rules)
+        });
+        match loaded {
+            Ok(Some(rules)) => *self.env_scrub.lock().unwrap() = rules,
+            Ok(None) => {}
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:pty_env:error] keeping built-in scrub rules error={}",
+                    err
+                ));
             }
-            // Dropping master/writer closes the PTY fds; the reader thread
-            // will see EOF and exit on its own.
         }
-        self.warm_pool(app_handle, MAX_POOL_SIZE)
     }
 
-    fn spawn_to_pool(&self, app_handle: &AppHandle) -> Result<(), PtyError> {
-        let pty_system = native_pty_system();
-        let pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 80,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(PtyError::from)?;
-        clear_problematic_control_chars(&*pair.master);
+    pub fn env_scrub_rules(&self) -> EnvScrubRules {
+        self.env_scrub.lock().unwrap().clone()
+    }
 
-        let mut cmd = CommandBuilder::new_default_prog();
-        apply_shell_env(&mut cmd);
+    /// Validate, save and apply new user rules.  Pooled shells were started
+    /// under the old rules, so the pool is refreshed.
+    pub fn set_env_scrub_rules(
+        &self,
+        app_handle: &AppHandle,
+        rules: EnvScrubRules,
+    ) -> Result<(), PtyError> {
+        rules.compile()?;
+        save_rules(&env_scrub_rules_path(app_handle)?, &rules)?;
+        *self.env_scrub.lock().unwrap() = rules;
+        self.refresh_pool(app_handle)
+    }
 
-        let child = pair.slave.spawn_command(cmd).map_err(PtyError::from)?;
+    /// Load stored project environments.  Called at startup next to the
+    /// scrub rules.
+    pub fn load_project_env_configs(&self, app_handle: &AppHandle) {
+        match project_env_configs_path(app_handle).and_then(|path| load_configs(&path)) {
+            Ok(configs) => *self.project_env.lock().unwrap() = configs,
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:project_env:error] failed to load project environments error={}",
+                    err
+                ));
+            }
+        }
+    }
+
+    pub fn project_env_config(&self, project_id: &str) -> ProjectEnvConfig {
+        self.project_env
+            .lock()
+            .unwrap()
+            .get(project_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Validate, save and apply a project's stored environment, then
+    /// replace the project's pooled shells.
+    pub fn set_project_env_config(
+        &self,
+        app_handle: &AppHandle,
+        project_id: &str,
+        config: ProjectEnvConfig,
+    ) -> Result<(), PtyError> {
+        parse_config_env(&config).map_err(|err| PtyError::from(err.to_string()))?;
+        let configs = {
+            let mut configs = self.project_env.lock().unwrap();
+            if config == ProjectEnvConfig::default() {
+                configs.remove(project_id);
+            } else {
+                configs.insert(project_id.to_owned(), config);
+            }
+            configs.clone()
+        };
+        save_configs(&project_env_configs_path(app_handle)?, &configs)?;
+        self.refresh_project_pool(app_handle, |key| key.id.as_deref() == Some(project_id));
+        Ok(())
+    }
+
+    fn project_config_for(&self, project: &ProjectKey) -> ProjectEnvConfig {
+        project
+            .id
+            .as_deref()
+            .map(|id| self.project_env_config(id))
+            .unwrap_or_default()
+    }
+
+    /// Env, activation and scrub rules files of a project, for change
+    /// checks.
+    fn project_file_stamps(&self, project: &ProjectKey) -> FileStamps {
+        let mut paths = self.project_config_for(project).watched_paths(&project.root);
+        paths.push(project_rules_path(&project.root));
+        file_stamps(&paths)
+    }
+
+    /// Whether terminals of this project need a shell started with the
+    /// project's settings rather than a generic pooled one.
+    fn project_has_environment(&self, project: &ProjectKey) -> bool {
+        let config = self.project_config_for(project);
+        config.activation.is_some()
+            || config.project_history
+            || !config.env.is_empty()
+            || self.project_file_stamps(project).iter().any(Option::is_some)
+    }
+
+    /// The project's direnv / nix activation, waiting up to `wait` when it
+    /// is not cached yet.
+    fn project_activation(
+        &self,
+        project: &ProjectKey,
+        activation: &EnvActivation,
+        wait: Duration,
+    ) -> Result<ActivationVars, String> {
+        match self.activation.lookup(Path::new(&project.root), activation, wait) {
+            ActivationLookup::Ready(vars) => Ok(vars),
+            ActivationLookup::Failed(err) => Err(err),
+            ActivationLookup::Pending => Err(format!(
+                "{} is still running; this shell started without it",
+                activation.describe()
+            )),
+        }
+    }
+
+    /// Evaluate a project's activation ahead of spawning, so a later spawn
+    /// finds it cached.  Blocks; call from a background thread.
+    fn prepare_activation(&self, project: &ProjectKey) {
+        if let Some(activation) = self.project_config_for(project).activation {
+            let _ = self.project_activation(project, &activation, ACTIVATION_TIMEOUT);
+        }
+    }
+
+    fn activation_pending(&self, project: &ProjectKey) -> bool {
+        self.project_config_for(project)
+            .activation
+            .is_some_and(|activation| {
+                matches!(
+                    self.activation
+                        .lookup(Path::new(&project.root), &activation, Duration::ZERO),
+                    ActivationLookup::Pending
+                )
+            })
+    }
+
+    /// Wait for a project's activation on a thread of its own, then run
+    /// `then`.  A project that already has a waiter gets no second one.
+    fn after_activation(
+        &self,
+        app_handle: &AppHandle,
+        project: ProjectKey,
+        then: impl FnOnce(&AppHandle, &PtyManager) + Send + 'static,
+    ) {
+        {
+            let mut awaiting = self.awaiting_activation.lock().unwrap();
+            if awaiting.contains(&project) {
+                return;
+            }
+            awaiting.push(project.clone());
+        }
+        let handle = app_handle.clone();
+        let waiting = project.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-activation-wait".to_string())
+            .spawn(move || {
+                let manager = handle.state::<PtyManager>();
+                manager.prepare_activation(&waiting);
+                manager
+                    .awaiting_activation
+                    .lock()
+                    .unwrap()
+                    .retain(|awaiting| *awaiting != waiting);
+                then(&handle, &manager);
+            });
+        if let Err(err) = result {
+            self.awaiting_activation
+                .lock()
+                .unwrap()
+                .retain(|awaiting| *awaiting != project);
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:project_env:error] failed to start activation thread error={}",
+                err
+            ));
+        }
+    }
+
+    /// Scrub rules and project environment for a new shell.  Broken project
+    /// files and failed activations are logged and skipped rather than
+    /// failing the spawn; the caller shows `notices` in the terminal.
+    fn spawn_env(
+        &self,
+        app_handle: &AppHandle,
+        project: Option<&ProjectKey>,
+        activation_wait: Duration,
+    ) -> SpawnEnv {
+        let root = project.map(|project| project.root.as_str());
+        let scrubber = match self.compile_env_scrubber(root) {
+            Ok(scrubber) => scrubber,
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:pty_env:error] ignoring project scrub rules project_root={:?} error={}",
+                    root, err
+                ));
+                self.env_scrub_rules()
+                    .compile()
+                    .unwrap_or_else(|_| EnvScrubber::default())
+            }
+        };
+
+        let mut spawn_env = SpawnEnv {
+            scrubber,
+            activation: Vec::new(),
+            project_env: Vec::new(),
+            notices: Vec::new(),
+            history_file: None,
+            identity: None,
+        };
+        let Some(project) = project else {
+            return spawn_env;
+        };
+        let config = self.project_config_for(project);
+
+        if let (true, Some(id)) = (config.project_history, project.id.as_deref()) {
+            match project_history_path(app_handle, id) {
+                Ok(path) => spawn_env.history_file = Some(path),
+                Err(err) => {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:project_env:error] project history unavailable project_id={} error={}",
+                        id, err
+                    ));
+                    spawn_env
+                        .notices
+                        .push(format!("project history not available: {}", err));
+                }
+            }
+        }
+
+        if let Some(ref activation) = config.activation {
+            match self.project_activation(project, activation, activation_wait) {
+                Ok(vars) => spawn_env.activation = vars,
+                Err(err) => {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:env_activation:error] project_root={} error={}",
+                        project.root, err
+                    ));
+                    spawn_env.notices.push(err);
+                }
+            }
+        }
+
+        match load_project_env(&project.root, &config) {
+            Ok(project_env) => {
+                for warning in &project_env.warnings {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:project_env:warning] project_root={} warning={}",
+                        project.root, warning
+                    ));
+                }
+                spawn_env.notices.extend(project_env.warnings);
+                spawn_env.project_env = project_env.assignments;
+            }
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:project_env:error] project_root={} error={}",
+                    project.root, err
+                ));
+                spawn_env
+                    .notices
+                    .push(format!("project environment not applied: {}", err));
+            }
+        }
+        spawn_env
+    }
+
+    fn compile_env_scrubber(&self, project_root: Option<&str>) -> Result<EnvScrubber, PtyError> {
+        let project = match project_root {
+            Some(root) => load_rules(&project_rules_path(root))?,
+            None => None,
+        };
+        self.env_scrub_rules().merged(project.as_ref()).compile()
+    }
+
+    /// What a new terminal in the project would inherit and what the scrub
+    /// rules would remove.  Values pass through secret redaction.  Broken
+    /// project files are reported as errors here.
+    pub fn preview_spawn_environment(
+        &self,
+        project_id: Option<String>,
+        project_root: Option<String>,
+        env: HashMap<String, String>,
+    ) -> Result<SpawnEnvironmentPreview, PtyError> {
+        let project = project_root
+            .filter(|root| !root.is_empty())
+            .map(|root| ProjectKey {
+                id: project_id,
+                root,
+            });
+        let scrubber =
+            self.compile_env_scrubber(project.as_ref().map(|project| project.root.as_str()))?;
+        let config = project
+            .as_ref()
+            .map(|project| self.project_config_for(project))
+            .unwrap_or_default();
+        let activation = match (&project, &config.activation) {
+            (Some(project), Some(activation)) => self
+                .project_activation(project, activation, SPAWN_ACTIVATION_WAIT)
+                .map_err(PtyError::from)?,
+            _ => Vec::new(),
+        };
+        let project_env = match project {
+            Some(ref project) => load_project_env(&project.root, &config)
+                .map_err(|err| PtyError::from(err.to_string()))?
+                .assignments,
+            None => Vec::new(),
+        };
+
+        let mut cmd = CommandBuilder::new_default_prog();
+        crate::login_env::apply_to(&mut cmd);
+        let mut removed = remove_agent_environment(&mut cmd, &scrubber);
+        apply_activation_vars(&mut cmd, &activation);
+        let project_vars = project_env_vars(&cmd, &project_env);
+        for (name, value) in &project_vars {
+            cmd.env(name, value);
+        }
+        for (name, value) in &env {
+            cmd.env(name, value);
+        }
+
+        let redact = |value: &str| crate::redaction::redact_text(value).into_owned();
+        for var in &mut removed {
+            var.value = redact(&var.value);
+        }
+        let mut inherited: Vec<EnvVarPreview> = cmd
+            .iter_full_env_as_str()
+            .map(|(name, value)| EnvVarPreview {
+                name: name.to_owned(),
+                value: redact(value),
+                overridden: env.contains_key(name),
+                source: if env.contains_key(name) {
+                    "terminal"
+                } else if project_vars.iter().any(|(project_name, _)| project_name == name) {
+                    "project"
+                } else if activation.iter().any(|(activation_name, _)| activation_name == name) {
+                    "activation"
+                } else {
+                    "inherited"
+                },
+            })
+            .collect();
+        inherited.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(SpawnEnvironmentPreview {
+            shell: cmd.get_shell(),
+            project_rules_path: project
+                .map(|project| project_rules_path(&project.root))
+                .filter(|path| path.exists())
+                .map(|path| path.display().to_string()),
+            inherited,
+            removed,
+        })
+    }
+
+    /// Load the saved hibernation config.  Called at startup; a broken
+    /// file leaves hibernation off.
+    pub fn load_hibernation_config(&self, app_handle: &AppHandle) {
+        match hibernation_config_path(app_handle).and_then(|path| load_hibernation_config(&path)) {
+            Ok(Some(config)) => *self.hibernation_config.lock().unwrap() = config,
+            Ok(None) => {}
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:hibernate:error] keeping default config error={}",
+                    err
+                ));
+            }
+        }
+    }
+
+    pub fn set_hibernation_config(
+        &self,
+        app_handle: &AppHandle,
+        config: HibernationConfig,
+    ) -> Result<(), PtyError> {
+        save_hibernation_config(&hibernation_config_path(app_handle)?, &config)?;
+        *self.hibernation_config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Hibernate every local terminal that has been idle longer than the
+    /// configured threshold and is sitting at its shell prompt.
+    fn hibernate_idle_sessions(&self, app_handle: &AppHandle) {
+        let config = self.hibernation_config();
+        if !config.enabled {
+            return;
+        }
+
+        let idle_after = config.idle_after();
+        let candidates: Vec<String> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, session)| {
+                session.router.lock().unwrap().last_activity_at.elapsed() >= idle_after
+                    && foreground_is_shell(session)
+            })
+            .map(|(terminal_id, _)| terminal_id.clone())
+            .collect();
+
+        for terminal_id in candidates {
+            if let Err(err) = self.hibernate_terminal(app_handle, &terminal_id) {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:hibernate:error] terminal_id={} error={}",
+                    terminal_id, err.message
+                ));
+            }
+        }
+    }
+
+    /// Shut down a terminal's shell and PTY while keeping its output channel,
+    /// cwd, metadata and scrollback so it can be respawned in place.
+    fn hibernate_terminal(&self, app_handle: &AppHandle, terminal_id: &str) -> Result<(), PtyError> {
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            // Re-check under the lock: a command may have started since the
+            // idle scan.
+            match sessions.get(terminal_id) {
+                Some(session) if foreground_is_shell(session) => {}
+                _ => return Ok(()),
+            }
+            sessions
+                .remove(terminal_id)
+                .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?
+        };
+
+        let size = session.master.get_size().ok();
+        let (channel, metadata, metadata_updated_at_ms, scrollback) = {
+            let mut router = session.router.lock().unwrap();
+            // Detach the reader so the dying shell's last output is dropped
+            // and no terminal-exit event reaches the renderer.
+            let mode = std::mem::replace(&mut router.mode, OutputMode::Buffering(Vec::new()));
+            router.assigned_id = None;
+            let OutputMode::Streaming { channel, .. } = mode else {
+                return Err(PtyError::from(format!(
+                    "Terminal {} has no output channel",
+                    terminal_id
+                )));
+            };
+            (
+                channel,
+                router.metadata.clone(),
+                router.metadata_updated_at_ms,
+                router.scrollback.contents(),
+            )
+        };
+
+        if let Some(mut child) = session.child.lock().unwrap().take() {
+            let _ = child.kill();
+        }
+
+        let scrollback_dir = self.session_store.lock().unwrap().scrollback_dir();
+        save_hibernated_scrollback(
+            scrollback_dir.as_deref(),
+            terminal_id,
+            &session.options,
+            &scrollback,
+        );
+
+        let cwd = metadata.cwd.clone().or(session.spawn_cwd.clone());
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:hibernate] terminal_id={} cwd={:?} scrollback_bytes={}",
+            terminal_id,
+            cwd,
+            scrollback.len()
+        ));
+
+        self.hibernated.lock().unwrap().insert(
+            terminal_id.to_owned(),
+            HibernatedTerminal {
+                cwd,
+                cols: size.map_or(80, |size| size.cols),
+                rows: size.map_or(24, |size| size.rows),
+                options: session.options,
+                channel,
+                metadata,
+                metadata_updated_at_ms,
+                scrollback,
+                hibernated_at: SystemTime::now(),
+            },
+        );
+        let _ = app_handle.emit(
+            "terminal-hibernation-changed",
+            TerminalHibernationPayload {
+                terminal_id: terminal_id.to_owned(),
+                hibernated: true,
+            },
+        );
+        Ok(())
+    }
+
+    /// Respawn a hibernated terminal in its old directory.  The renderer
+    /// still shows the old output, so nothing is replayed; the backend keeps
+    /// the saved scrollback and metadata for persistence.
+    fn resume_terminal(&self, app_handle: &AppHandle, terminal_id: &str) -> Result<bool, PtyError> {
+        let Some(hibernated) = self.hibernated.lock().unwrap().remove(terminal_id) else {
+            return Ok(false);
+        };
+
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:resume] terminal_id={} cwd={:?} hibernated_secs={}",
+            terminal_id,
+            hibernated.cwd,
+            hibernated
+                .hibernated_at
+                .elapsed()
+                .unwrap_or_default()
+                .as_secs()
+        ));
+
+        self.spawn_fresh(
+            app_handle,
+            terminal_id.to_owned(),
+            hibernated.cwd,
+            hibernated.cols,
+            hibernated.rows,
+            hibernated.options,
+            hibernated.channel,
+        )?;
+
+        if let Some(session) = self.sessions.lock().unwrap().get(terminal_id) {
+            let mut router = session.router.lock().unwrap();
+            router.metadata.history = hibernated.metadata.history;
+            router.metadata.last_command = hibernated.metadata.last_command;
+            router.scrollback.seed(&hibernated.scrollback);
+        }
+
+        let _ = app_handle.emit(
+            "terminal-hibernation-changed",
+            TerminalHibernationPayload {
+                terminal_id: terminal_id.to_owned(),
+                hibernated: false,
+            },
+        );
+        Ok(true)
+    }
+
+    /// Load the previous run's local session records and start saving the
+    /// current ones periodically.
+    pub fn start_session_persistence(&self, app_handle: &AppHandle) {
+        match app_handle.path().app_data_dir() {
+            Ok(dir) => {
+                *self.session_store.lock().unwrap() =
+                    SessionStore::load(dir.join(SESSION_STORE_FILE_NAME));
+            }
+            Err(err) => {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:session_store:error] no app data dir error={}",
+                    err
+                ));
+                return;
+            }
+        }
+
+        let handle = app_handle.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-session-persistence".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(SESSION_PERSIST_INTERVAL);
+                handle.state::<PtyManager>().persist_sessions();
+            });
+
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:session_store:error] failed to start persistence thread error={}",
+                err
+            ));
+        }
+    }
+
+    pub fn persist_sessions(&self) {
+        let mut records: Vec<LocalSessionRecord> = Vec::new();
+        let mut dirty_scrollback: Vec<(String, Vec<u8>, u64)> = Vec::new();
+        for (terminal_id, session) in self.sessions.lock().unwrap().iter() {
+            // Incognito terminals are never restorable; leaving them out also
+            // lets the prune below delete any scrollback on disk.
+            if session.options.incognito {
+                continue;
+            }
+            let router = session.router.lock().unwrap();
+            records.push(LocalSessionRecord {
+                terminal_id: terminal_id.clone(),
+                cwd: router
+                    .metadata
+                    .cwd
+                    .clone()
+                    .or_else(|| session.spawn_cwd.clone()),
+                profile: session.options.profile.clone(),
+                env: session.options.env.clone(),
+                encoding: session.options.encoding.clone(),
+                last_command: router.metadata.last_command.clone(),
+                history: router.metadata.history.clone(),
+                updated_at_ms: router.metadata_updated_at_ms,
+            });
+            if router.scrollback.is_dirty() {
+                dirty_scrollback.push((
+                    terminal_id.clone(),
+                    router.scrollback.contents(),
+                    router.scrollback.generation(),
+                ));
+            }
+        }
+
+        for (terminal_id, hibernated) in self.hibernated.lock().unwrap().iter() {
+            if hibernated.options.incognito {
+                continue;
+            }
+            records.push(LocalSessionRecord {
+                terminal_id: terminal_id.clone(),
+                cwd: hibernated.cwd.clone(),
+                profile: hibernated.options.profile.clone(),
+                env: hibernated.options.env.clone(),
+                encoding: hibernated.options.encoding.clone(),
+                last_command: hibernated.metadata.last_command.clone(),
+                history: hibernated.metadata.history.clone(),
+                updated_at_ms: hibernated.metadata_updated_at_ms,
+            });
+        }
+
+        let mut keep_ids: HashSet<String> =
+            records.iter().map(|record| record.terminal_id.clone()).collect();
+        let scrollback_dir = {
+            let mut store = self.session_store.lock().unwrap();
+            if let Err(err) = store.save(records) {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:session_store:error] failed to save sessions error={}",
+                    err.message
+                ));
+            }
+            keep_ids.extend(store.restorable().into_iter().map(|record| record.terminal_id));
+            store.scrollback_dir()
+        };
+        let Some(scrollback_dir) = scrollback_dir else {
+            return;
+        };
+
+        // Compress and write outside the sessions lock; output keeps flowing
+        // while we are busy.
+        for (terminal_id, data, generation) in dirty_scrollback {
+            match save_scrollback(&scrollback_dir, &terminal_id, &data) {
+                Ok(()) => {
+                    if let Some(session) = self.sessions.lock().unwrap().get(&terminal_id) {
+                        session.router.lock().unwrap().scrollback.mark_saved(generation);
+                    }
+                }
+                Err(err) => {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:scrollback:error] terminal_id={} error={}",
+                        terminal_id, err.message
+                    ));
+                }
+            }
+        }
+        prune_scrollback(&scrollback_dir, &keep_ids);
+    }
+
+    /// Records from the previous run that have not been re-created yet.
+    pub fn restorable_terminals(&self) -> Vec<LocalSessionRecord> {
+        self.session_store.lock().unwrap().restorable()
+    }
+
+    /// Compare the backend's sessions with the ids the renderer still owns
+    /// and return every session that has no owner.  Orphans stay alive so
+    /// they can be re-adopted; the periodic check closes them once they
+    /// expire.
+    pub fn reconcile_terminals(
+        &self,
+        app_handle: &AppHandle,
+        live_ids: Vec<String>,
+    ) -> Vec<OrphanedTerminal> {
+        let live_ids: HashSet<String> = live_ids.into_iter().collect();
+        self.session_store
+            .lock()
+            .unwrap()
+            .retain_restorable(&live_ids);
+        self.orphans
+            .lock()
+            .unwrap()
+            .record_live_ids(live_ids, Instant::now());
+        self.check_orphans(app_handle)
+    }
+
+    /// Point an orphaned terminal's output at a new channel so a renderer
+    /// that lost track of it can pick it back up.  Hibernated and deferred
+    /// terminals keep the channel for when their shell starts.
+    pub fn adopt_terminal(
+        &self,
+        terminal_id: &str,
+        channel: Channel<TerminalOutput>,
+    ) -> Result<(), PtyError> {
+        let channel = match self.sessions.lock().unwrap().get(terminal_id) {
+            Some(session) => {
+                let mut router = session.router.lock().unwrap();
+                router.mode = OutputMode::Streaming {
+                    channel,
+                    terminal_id: terminal_id.to_owned(),
+                };
+                router.assigned_id = Some(terminal_id.to_owned());
+                None
+            }
+            None => Some(channel),
+        };
+        if let Some(channel) = channel {
+            let adopted = match self.hibernated.lock().unwrap().get_mut(terminal_id) {
+                Some(hibernated) => {
+                    hibernated.channel = channel;
+                    true
+                }
+                None => self.spawn_queue.replace_channel(terminal_id, channel),
+            };
+            if !adopted {
+                return Err(PtyError::from(format!("Terminal {} not found", terminal_id)));
+            }
+        }
+
+        self.orphans.lock().unwrap().adopt(terminal_id);
+        Ok(())
+    }
+
+    /// Periodic housekeeping: close expired orphans and hibernate idle tabs.
+    pub fn start_session_monitor(&self, app_handle: &AppHandle) {
+        let handle = app_handle.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-session-monitor".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(SESSION_MONITOR_INTERVAL);
+                let manager = handle.state::<PtyManager>();
+                let _ = manager.check_orphans(&handle);
+                manager.hibernate_idle_sessions(&handle);
+            });
+
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:session_monitor:error] failed to start monitor thread error={}",
+                err
+            ));
+        }
+    }
+
+    /// Sample every session's output activity once a second and emit
+    /// `terminal-activity-changed` for each active/stale/long-idle
+    /// transition, so tab status does not depend on the renderer polling.
+    pub fn start_activity_monitor(&self, app_handle: &AppHandle) {
+        let handle = app_handle.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-activity-monitor".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(ACTIVITY_CHECK_INTERVAL);
+                let changed = handle.state::<PtyManager>().sample_activity();
+                for activity in changed {
+                    let _ = handle.emit("terminal-activity-changed", activity);
+                }
+            });
+
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:activity_monitor:error] failed to start monitor thread error={}",
+                err
+            ));
+        }
+    }
+
+    fn sample_activity(&self) -> Vec<TerminalActivity> {
+        let now = Instant::now();
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .iter()
+            .filter_map(|(terminal_id, session)| {
+                let mut router = session.router.lock().unwrap();
+                router.activity.sample(now)?;
+                Some(router.activity.snapshot(terminal_id))
+            })
+            .collect()
+    }
+
+    /// Whether the terminal is running, deferred or hibernated.
+    pub fn has_terminal(&self, terminal_id: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(terminal_id)
+            || self.hibernated.lock().unwrap().contains_key(terminal_id)
+            || self.failed_spawns.lock().unwrap().contains_key(terminal_id)
+            || self.spawn_queue.cwd(terminal_id).is_some()
+    }
+
+    /// Every terminal the backend knows, sorted by id.
+    pub fn list_terminals(&self) -> Vec<TerminalListing> {
+        let mut listing: Vec<TerminalListing> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(terminal_id, session)| {
+                let router = session.router.lock().unwrap();
+                TerminalListing {
+                    terminal_id: terminal_id.clone(),
+                    state: if router.exit.is_some() { "exited" } else { "running" },
+                    project_id: session.options.project_id.clone(),
+                    cwd: router.metadata.cwd.clone().or(session.spawn_cwd.clone()),
+                    exit_code: router.exit.flatten(),
+                }
+            })
+            .collect();
+        listing.extend(self.hibernated.lock().unwrap().iter().map(
+            |(terminal_id, hibernated)| TerminalListing {
+                terminal_id: terminal_id.clone(),
+                state: "hibernated",
+                project_id: hibernated.options.project_id.clone(),
+                cwd: hibernated.metadata.cwd.clone().or(hibernated.cwd.clone()),
+                exit_code: None,
+            },
+        ));
+        listing.extend(self.failed_spawns.lock().unwrap().iter().map(
+            |(terminal_id, failed)| TerminalListing {
+                terminal_id: terminal_id.clone(),
+                state: "failed",
+                project_id: failed.options.project_id.clone(),
+                cwd: failed.cwd.clone(),
+                exit_code: None,
+            },
+        ));
+        self.spawn_queue.for_each_queued(|deferred| {
+            listing.push(TerminalListing {
+                terminal_id: deferred.terminal_id.clone(),
+                state: "deferred",
+                project_id: deferred.options.project_id.clone(),
+                cwd: deferred.cwd.clone(),
+                exit_code: None,
+            })
+        });
+        listing.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+        listing
+    }
+
+    /// Block until the terminal's shell exits and return its exit code.
+    /// Returns at once when it already has.
+    pub fn wait_for_exit(
+        &self,
+        terminal_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Option<i32>, PtyError> {
+        let (waiter, exited) = self.exit_waiters.register(terminal_id);
+        let exit = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(terminal_id)
+            .map(|session| session.router.lock().unwrap().exit);
+        match exit {
+            Some(Some(exit_code)) => {
+                self.exit_waiters.unregister(terminal_id, waiter);
+                return Ok(exit_code);
+            }
+            Some(None) => {}
+            None if self.has_terminal(terminal_id) => {}
+            None => {
+                self.exit_waiters.unregister(terminal_id, waiter);
+                return Err(PtyError::from(format!("Terminal {} not found", terminal_id)));
+            }
+        }
+        let exit_code = match timeout {
+            Some(timeout) => exited.recv_timeout(timeout).ok(),
+            None => exited.recv().ok(),
+        };
+        exit_code.ok_or_else(|| {
+            self.exit_waiters.unregister(terminal_id, waiter);
+            PtyError::from(format!("Terminal {} is still running", terminal_id))
+        })
+    }
+
+    /// Whether the terminal's shell has shown its first prompt, so typed
+    /// input reaches the shell rather than its startup files.
+    pub fn has_prompted(&self, terminal_id: &str) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(terminal_id)
+            .is_some_and(|session| session.router.lock().unwrap().prompt_seen)
+    }
+
+    /// Current output activity of every running session.
+    pub fn terminal_activity(&self) -> Vec<TerminalActivity> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut activity: Vec<TerminalActivity> = sessions
+            .iter()
+            .map(|(terminal_id, session)| session.router.lock().unwrap().activity.snapshot(terminal_id))
+            .collect();
+        activity.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+        activity
+    }
+
+    fn check_orphans(&self, app_handle: &AppHandle) -> Vec<OrphanedTerminal> {
+        // Hibernated and deferred terminals hold a channel to a tab too.
+        let now = Instant::now();
+        let mut sessions: Vec<(String, Instant)> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, session)| (id.clone(), session.created_at))
+            .collect();
+        sessions.extend(self.hibernated.lock().unwrap().iter().map(|(id, hibernated)| {
+            let age = hibernated.hibernated_at.elapsed().unwrap_or_default();
+            (id.clone(), now.checked_sub(age).unwrap_or(now))
+        }));
+        self.spawn_queue.for_each_queued(|deferred| {
+            sessions.push((deferred.terminal_id.clone(), deferred.queued_at));
+        });
+
+        let (newly_orphaned, expired) = {
+            let mut orphans = self.orphans.lock().unwrap();
+            let newly_orphaned = orphans.update(&sessions, now);
+            (newly_orphaned, orphans.expired(now))
+        };
+
+        if !newly_orphaned.is_empty() {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:orphans] sessions without a frontend owner terminal_ids={}",
+                newly_orphaned.join(",")
+            ));
+            let orphans = self.orphans.lock().unwrap().orphans(now);
+            let _ = app_handle.emit("terminal-orphans-detected", orphans);
+        }
+
+        for terminal_id in expired {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:orphans] closing expired orphan terminal_id={}",
+                terminal_id
+            ));
+            let _ = self.close_terminal(&terminal_id);
+        }
+
+        self.orphans.lock().unwrap().orphans(now)
+    }
+
+    /// Create many terminals in one call.  Eager entries are spawned right
+    /// away; deferred entries only get their id and output channel registered
+    /// and are forked later, either when the tab is activated or when a
+    /// background warm-up slot frees up.  Failures are reported per entry so
+    /// one broken cwd does not abort a whole restore.
+    pub fn create_terminals(
+        &self,
+        app_handle: &AppHandle,
+        webview: &Webview,
+        requests: Vec<TerminalSpawnRequest>,
+    ) -> Vec<TerminalSpawnResult> {
+        let mut results = Vec::with_capacity(requests.len());
+        let mut start_worker = false;
+
+        for request in requests {
+            let channel = request.on_output.channel_on(webview.clone());
+            let terminal_id = request.terminal_id;
+
+            if request.deferred {
+                self.register_incognito(&terminal_id, &request.options);
+                start_worker |= self.spawn_queue.push(DeferredTerminal {
+                    terminal_id: terminal_id.clone(),
+                    cwd: request.cwd,
+                    cols: request.cols,
+                    rows: request.rows,
+                    options: request.options,
+                    channel,
+                    queued_at: Instant::now(),
+                });
+                results.push(TerminalSpawnResult {
+                    terminal_id,
+                    status: "deferred",
+                    error: None,
+                });
+                continue;
+            }
+
+            match self.create_terminal(
+                app_handle,
+                terminal_id.clone(),
+                request.cwd,
+                request.cols,
+                request.rows,
+                request.options,
+                channel,
+            ) {
+                Ok(()) => results.push(TerminalSpawnResult {
+                    terminal_id,
+                    status: "spawned",
+                    error: None,
+                }),
+                Err(err) => results.push(TerminalSpawnResult {
+                    terminal_id,
+                    status: "failed",
+                    error: Some(err.message),
+                }),
+            }
+        }
+
+        if start_worker {
+            self.start_background_spawner(app_handle);
+        }
+
+        results
+    }
+
+    /// Fork a deferred or hibernated terminal now.  Returns false when the
+    /// terminal was not waiting to be spawned (already running, or unknown).
+    pub fn activate_terminal(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+    ) -> Result<bool, PtyError> {
+        if self.resume_terminal(app_handle, terminal_id)? {
+            return Ok(true);
+        }
+        let Some(deferred) = self.spawn_queue.take(terminal_id) else {
+            // The background spawner may have just taken it off the queue.
+            self.spawn_queue.wait_for_spawn(terminal_id, BACKGROUND_SPAWN_WAIT);
+            return Ok(false);
+        };
+        self.create_terminal(
+            app_handle,
+            deferred.terminal_id,
+            deferred.cwd,
+            deferred.cols,
+            deferred.rows,
+            deferred.options,
+            deferred.channel,
+        )?;
+        Ok(true)
+    }
+
+    fn start_background_spawner(&self, app_handle: &AppHandle) {
+        let handle = app_handle.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-background-spawner".to_string())
+            .spawn(move || {
+                let manager = handle.state::<PtyManager>();
+                while let Some(deferred) = manager.spawn_queue.next_for_warmup() {
+                    let terminal_id = deferred.terminal_id.clone();
+                    // Background spawns bypass the pool so a large restore
+                    // does not drain the shells kept ready for new tabs.
+                    let result = manager.start_unpooled(
+                        &handle,
+                        deferred.terminal_id,
+                        deferred.cwd,
+                        deferred.cols,
+                        deferred.rows,
+                        deferred.options,
+                        deferred.channel,
+                    );
+                    manager.spawn_queue.finish_spawn(&terminal_id);
+                    if let Err(err) = result {
+                        manager.spawn_queue.release(&terminal_id);
+                        let _ = crate::debug_log::append_debug_log(&format!(
+                            "[backend:background_spawn:error] terminal_id={} error={}",
+                            terminal_id, err.message
+                        ));
+                        let _ = handle.emit(
+                            "terminal-spawn-failed",
+                            TerminalSpawnFailedPayload {
+                                terminal_id,
+                                error: err.message,
+                            },
+                        );
+                    }
+                }
+            });
+
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:background_spawn:error] failed to start spawner thread error={}",
+                err
+            ));
+        }
+    }
+
+    /// Pre-spawn PTYs into the pool, up to MAX_POOL_SIZE total.  A project
+    /// with its own environment gets one pooled shell of its own instead;
+    /// up to MAX_POOL_SIZE projects keep one, the least recent is dropped.
+    pub fn warm_pool(
+        &self,
+        app_handle: &AppHandle,
+        count: usize,
+        project: Option<ProjectKey>,
+    ) -> Result<(), PtyError> {
+        let project = project.filter(|project| self.project_has_environment(project));
+        let Some(project) = project else {
+            let current = self
+                .pool
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.project.is_none())
+                .count();
+            let to_spawn = count.min(MAX_POOL_SIZE.saturating_sub(current));
+            for _ in 0..to_spawn {
+                self.spawn_to_pool(app_handle, None)?;
+            }
+            return Ok(());
+        };
+
+        let evicted = {
+            let mut pool = self.pool.lock().unwrap();
+            if count == 0 || pool.iter().any(|entry| entry.project.as_ref() == Some(&project)) {
+                return Ok(());
+            }
+            let project_entries = pool.iter().filter(|entry| entry.project.is_some()).count();
+            if project_entries >= MAX_POOL_SIZE {
+                pool.iter()
+                    .position(|entry| entry.project.is_some())
+                    .map(|index| pool.remove(index))
+            } else {
+                None
+            }
+        };
+        if let Some(entry) = evicted {
+            entry.kill();
+        }
+        self.spawn_to_pool(app_handle, Some(project))
+    }
+
+    /// Drain all pooled PTYs and spawn fresh replacements so that shell
+    /// history, environment variables, etc. are up-to-date.
+    pub fn refresh_pool(&self, app_handle: &AppHandle) -> Result<(), PtyError> {
+        let old: Vec<PoolEntry> = {
+            let mut pool = self.pool.lock().unwrap();
+            pool.drain(..).collect()
+        };
+        let mut projects: Vec<ProjectKey> = Vec::new();
+        // Kill old shell processes.
+        for entry in old {
+            if let Some(ref project) = entry.project {
+                projects.push(project.clone());
+            }
+            entry.kill();
+        }
+        self.warm_pool(app_handle, MAX_POOL_SIZE, None)?;
+        for project in projects {
+            self.warm_pool(app_handle, 1, Some(project))?;
+        }
+        Ok(())
+    }
+
+    /// Replace pooled project shells matching `filter`, e.g. after the
+    /// project's stored environment changed.
+    fn refresh_project_pool(&self, app_handle: &AppHandle, filter: impl Fn(&ProjectKey) -> bool) {
+        let stale: Vec<PoolEntry> = {
+            let mut pool = self.pool.lock().unwrap();
+            let (stale, keep) = pool
+                .drain(..)
+                .partition(|entry| entry.project.as_ref().is_some_and(&filter));
+            *pool = keep;
+            stale
+        };
+        for entry in stale {
+            let Some(project) = entry.project.clone() else {
+                continue;
+            };
+            entry.kill();
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:project_env] refreshing pooled shell project_root={}",
+                project.root
+            ));
+            if let Err(err) = self.warm_pool(app_handle, 1, Some(project)) {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:project_env:error] failed to refresh pooled shell error={}",
+                    err
+                ));
+            }
+        }
+    }
+
+    /// Watch the env and rules files of pooled project shells and replace a
+    /// shell once its project's files change.
+    pub fn start_project_env_watcher(&self, app_handle: &AppHandle) {
+        let handle = app_handle.clone();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-project-env-watcher".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(PROJECT_ENV_CHECK_INTERVAL);
+                let manager = handle.state::<PtyManager>();
+                let pooled: Vec<(ProjectKey, FileStamps)> = manager
+                    .pool
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter_map(|entry| Some((entry.project.clone()?, entry.stamps.clone())))
+                    .collect();
+                let changed: Vec<ProjectKey> = pooled
+                    .into_iter()
+                    .filter(|(project, stamps)| manager.project_file_stamps(project) != *stamps)
+                    .map(|(project, _)| project)
+                    .collect();
+                // Each project waits for its activation on its own thread so
+                // a slow one does not hold back the others.
+                for project in changed {
+                    let refreshed = project.clone();
+                    manager.after_activation(&handle, project, move |handle, manager| {
+                        manager.refresh_project_pool(handle, |project| *project == refreshed);
+                    });
+                }
+            });
+
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:project_env:error] failed to start watcher thread error={}",
+                err
+            ));
+        }
+    }
+
+    /// Take a pooled shell for a terminal of `project`, or a generic one
+    /// when the project has no environment of its own.  A project shell
+    /// whose files changed since it started is discarded.
+    fn take_pooled(&self, project: Option<&ProjectKey>) -> Option<PoolEntry> {
+        let entry = {
+            let mut pool = self.pool.lock().unwrap();
+            let index = pool
+                .iter()
+                .rposition(|entry| entry.project.as_ref() == project)?;
+            pool.remove(index)
+        };
+        match project {
+            Some(project) if self.project_file_stamps(project) != entry.stamps => {
+                entry.kill();
+                None
+            }
+            _ => Some(entry),
+        }
+    }
+
+    fn spawn_to_pool(
+        &self,
+        app_handle: &AppHandle,
+        project: Option<ProjectKey>,
+    ) -> Result<(), PtyError> {
+        let stamps = project
+            .as_ref()
+            .map(|project| self.project_file_stamps(project))
+            .unwrap_or_default();
+        // Pooling never waits for an activation.  A broken or unfinished
+        // project environment is reported when a terminal spawns instead; a
+        // pooled shell would hide the message.
+        let mut spawn_env = self.spawn_env(app_handle, project.as_ref(), Duration::ZERO);
+        if !spawn_env.notices.is_empty() {
+            // An unfinished activation is retried once it finishes.
+            if let Some(project) = project.filter(|project| self.activation_pending(project)) {
+                let retried = project.clone();
+                self.after_activation(app_handle, project, move |handle, manager| {
+                    if let Err(err) = manager.warm_pool(handle, 1, Some(retried)) {
+                        let _ = crate::debug_log::append_debug_log(&format!(
+                            "[backend:project_env:error] failed to refill pool error={}",
+                            err
+                        ));
+                    }
+                });
+            }
+            return Ok(());
+        }
+        let identity_file = crate::control_socket::new_identity_file();
+        spawn_env.identity = identity_file
+            .as_ref()
+            .map(|file| ShellIdentity::Pending(file.path().to_path_buf()));
+
+        let pty_system = native_pty_system();
+        let pair = pty_system
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(PtyError::from)?;
+        clear_problematic_control_chars(&*pair.master);
+
+        let mut cmd = CommandBuilder::new_default_prog();
+        crate::login_env::apply_to(&mut cmd);
+        apply_shell_env(&mut cmd, &spawn_env);
+        let prompt_marks = reports_prompts(&cmd.get_shell());
+
+        let child = pair.slave.spawn_command(cmd).map_err(PtyError::from)?;
         drop(pair.slave);
 
         let writer = pair.master.take_writer().map_err(PtyError::from)?;
-        let mut reader = pair.master.try_clone_reader().map_err(PtyError::from)?;
+        let reader = pair.master.try_clone_reader().map_err(PtyError::from)?;
+        let input_probe = InputModeProbe::new(&*pair.master);
 
         let child_arc: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>> =
             Arc::new(Mutex::new(Some(child)));
 
-        let router = Arc::new(Mutex::new(OutputRouter {
-            mode: OutputMode::Buffering(Vec::with_capacity(4096)),
-            assigned_id: None,
-        }));
+        let mut router = OutputRouter::new(
+            OutputMode::Buffering(Vec::with_capacity(4096)),
+            None,
+            80,
+            24,
+        );
+        router.prompt_marks = prompt_marks;
+        let router = Arc::new(Mutex::new(router));
 
         let entry = PoolEntry {
             master: pair.master,
             writer,
             child: Arc::clone(&child_arc),
             router: Arc::clone(&router),
+            project,
+            stamps,
+            identity_file,
         };
 
         self.pool.lock().unwrap().push(entry);
 
-        // Reader thread: buffers output while pooled, streams when assigned.
-        // Uses a carry buffer to avoid corrupting multi-byte UTF-8 characters
-        // that straddle 4096-byte read boundaries.
+        // Buffers output while pooled, streams once assigned.
+        self.spawn_output_reader(app_handle, reader, router, child_arc, input_probe);
+
+        Ok(())
+    }
+
+    /// Start the reader thread for a PTY.  Output goes wherever the router
+    /// currently points, so pooled shells, fresh shells and re-adopted
+    /// sessions all share one read loop.  Output in a legacy encoding is
+    /// converted to UTF-8 first.  Uses a carry buffer to avoid corrupting
+    /// multi-byte UTF-8 characters that straddle 4096-byte read boundaries.
+    fn spawn_output_reader(
+        &self,
+        app_handle: &AppHandle,
+        mut reader: Box<dyn Read + Send>,
+        router: Arc<Mutex<OutputRouter>>,
+        child_arc: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>>,
+        input_probe: InputModeProbe,
+    ) {
         let handle = app_handle.clone();
+        let spawn_queue = Arc::clone(&self.spawn_queue);
+        let watchers = Arc::clone(&self.watchers);
+        let exit_waiters = Arc::clone(&self.exit_waiters);
         std::thread::spawn(move || {
             let mut buf = [0u8; 4096];
             let mut carry: Vec<u8> = Vec::new();
+            let mut warmup_released = false;
 
             loop {
                 match reader.read(&mut buf) {
                     Ok(0) => break,
                     Ok(n) => {
-                        carry.extend_from_slice(&buf[..n]);
+                        let mut r = router.lock().unwrap();
+                        match r.transcoder.as_mut() {
+                            Some(transcoder) => transcoder.decode(&buf[..n], &mut carry),
+                            None => carry.extend_from_slice(&buf[..n]),
+                        }
 
                         let split = utf8_split_point(&carry);
 
                         if split > 0 {
-                            let mut r = router.lock().unwrap();
-                            match &mut r.mode {
-                                OutputMode::Buffering(buffer) => {
-                                    buffer.extend_from_slice(&carry[..split]);
-                                }
-                                OutputMode::Streaming {
-                                    channel,
-                                    terminal_id,
-                                } => {
-                                    let data =
-                                        String::from_utf8_lossy(&carry[..split]).to_string();
-                                    log_protocol_output_chunk(terminal_id, &data);
-                                    let _ = channel.send(TerminalOutput {
-                                        terminal_id: terminal_id.clone(),
-                                        data,
-                                    });
+                            let mut effects =
+                                r.route(&carry[..split], &watchers.read().unwrap());
+                            // Programs switch echo off right before printing
+                            // their password prompt.
+                            if r.assigned_id.is_some() {
+                                effects.secure_input = r.update_secure_input(input_probe.read());
+                            }
+                            // First output means the shell is up; let the
+                            // background spawner start the next deferred tab.
+                            if !warmup_released {
+                                if let Some(ref tid) = r.assigned_id {
+                                    spawn_queue.release(tid);
+                                    warmup_released = true;
                                 }
                             }
+                            let terminal_id = r.assigned_id.clone();
+                            drop(r);
+                            dispatch_route_effects(&handle, terminal_id.as_deref(), effects);
                         }
 
                         // Keep only incomplete trailing bytes.
@@ -525,23 +2670,10 @@ impl PtyManager {
             // Flush any remaining carry bytes at EOF.
             if !carry.is_empty() {
                 let mut r = router.lock().unwrap();
-                match &mut r.mode {
-                    OutputMode::Buffering(buffer) => {
-                        buffer.extend_from_slice(&carry);
-                    }
-                    OutputMode::Streaming {
-                        channel,
-                        terminal_id,
-                    } => {
-                        let data = String::from_utf8_lossy(&carry).to_string();
-                        log_protocol_output_chunk(terminal_id, &data);
-                        let _ = channel.send(TerminalOutput {
-                            terminal_id: terminal_id.clone(),
-                            data,
-                        });
-                    }
-                }
+                let effects = r.route(&carry, &watchers.read().unwrap());
+                let terminal_id = r.assigned_id.clone();
                 drop(r);
+                dispatch_route_effects(&handle, terminal_id.as_deref(), effects);
             }
 
             // EOF — get exit code
@@ -555,8 +2687,11 @@ impl PtyManager {
             };
 
             // Only emit exit event if this PTY was assigned to a terminal
-            let r = router.lock().unwrap();
+            let mut r = router.lock().unwrap();
+            r.exit = Some(exit_code);
             if let Some(ref tid) = r.assigned_id {
+                spawn_queue.release(tid);
+                exit_waiters.notify(tid, exit_code);
                 let _ = handle.emit(
                     "terminal-exit",
                     TerminalExitPayload {
@@ -566,10 +2701,9 @@ impl PtyManager {
                 );
             }
         });
-
-        Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_terminal(
         &self,
         app_handle: &AppHandle,
@@ -577,12 +2711,35 @@ impl PtyManager {
         cwd: Option<String>,
         cols: u16,
         rows: u16,
+        options: TerminalSpawnOptions,
         channel: Channel<TerminalOutput>,
     ) -> Result<(), PtyError> {
+        // An explicit create supersedes any deferred registration.
+        let _ = self.spawn_queue.take(&terminal_id);
+        self.register_incognito(&terminal_id, &options);
+
+        // Restored tabs and tabs with their own environment or scrub rules
+        // cannot reuse a pooled shell, which was started before we knew any
+        // of them.
+        let restorable = self
+            .session_store
+            .lock()
+            .unwrap()
+            .is_restorable(&terminal_id);
+        let project_rules = options
+            .project_root
+            .as_deref()
+            .is_some_and(|root| project_rules_path(root).exists());
+        if restorable || !options.env.is_empty() || project_rules {
+            return self.start_unpooled(app_handle, terminal_id, cwd, cols, rows, options, channel);
+        }
+
         let has_cwd = cwd.as_ref().map_or(false, |d| !d.is_empty());
+        let project = ProjectKey::from_options(&options)
+            .filter(|project| self.project_has_environment(project));
 
         // Try pool first — even when cwd is specified we can cd into it
-        let entry = self.pool.lock().unwrap().pop();
+        let entry = self.take_pooled(project.as_ref());
         if let Some(entry) = entry {
             // Resize to actual dimensions FIRST, before replaying buffered
             // output.  The pool PTY starts at 80×24; if the frontend is a
@@ -597,6 +2754,7 @@ impl PtyManager {
             // Switch router from buffering to streaming
             {
                 let mut r = entry.router.lock().unwrap();
+                r.screen.resize(cols, rows);
                 if !has_cwd {
                     // No custom cwd — replay buffered output (initial prompt etc.)
                     if let OutputMode::Buffering(ref buffer) = r.mode {
@@ -616,12 +2774,30 @@ impl PtyManager {
                     terminal_id: terminal_id.clone(),
                 };
                 r.assigned_id = Some(terminal_id.clone());
+                r.apply_spawn_options(&terminal_id, &options);
+            }
+
+            // The shell integration picks this up before the next command.
+            if let Some(ref file) = entry.identity_file {
+                let vars = terminal_identity(&terminal_id, &options);
+                if let Err(err) = file.write(&vars) {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:pty_env:error] failed to write identity terminal_id={} error={}",
+                        terminal_id, err
+                    ));
+                }
             }
 
+            self.watch_startup(app_handle, &terminal_id, Arc::clone(&entry.router));
             let mut session = PtySession {
                 master: entry.master,
                 writer: entry.writer,
                 child: entry.child,
+                router: entry.router,
+                created_at: Instant::now(),
+                spawn_cwd: cwd.clone().filter(|dir| !dir.is_empty()),
+                options,
+                _identity_file: entry.identity_file,
             };
 
             // cd into the requested directory and clear the screen so the
@@ -631,7 +2807,8 @@ impl PtyManager {
                 if !dir.is_empty() {
                     let escaped = dir.replace('\'', "'\\''");
                     let cmd = format!(" cd '{}' && clear\n", escaped);
-                    let _ = session.writer.write_all(cmd.as_bytes());
+                    let encoded = session.router.lock().unwrap().encode_input(cmd.as_bytes());
+                    let _ = session.writer.write_all(&encoded);
                     let _ = session.writer.flush();
                 }
             }
@@ -641,9 +2818,121 @@ impl PtyManager {
         }
 
         // Pool empty — spawn fresh
-        self.spawn_fresh(app_handle, terminal_id, cwd, cols, rows, channel)
+        self.spawn_fresh(app_handle, terminal_id, cwd, cols, rows, options, channel)
     }
 
+    /// Spawn without the pool, restoring the previous run's cwd, environment
+    /// and history when a saved record exists for this terminal id.
+    #[allow(clippy::too_many_arguments)]
+    fn start_unpooled(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: String,
+        cwd: Option<String>,
+        cols: u16,
+        rows: u16,
+        mut options: TerminalSpawnOptions,
+        channel: Channel<TerminalOutput>,
+    ) -> Result<(), PtyError> {
+        let restored = self.session_store.lock().unwrap().claim(&terminal_id);
+        let Some(record) = restored else {
+            return self.spawn_fresh(app_handle, terminal_id, cwd, cols, rows, options, channel);
+        };
+
+        // The saved directory may have been deleted since the last run.
+        let cwd = record
+            .cwd
+            .clone()
+            .filter(|dir| Path::new(dir).is_dir())
+            .or(cwd);
+        if options.profile.is_none() {
+            options.profile = record.profile.clone();
+        }
+        if options.encoding.is_none() {
+            options.encoding = record.encoding.clone();
+        }
+        for (name, value) in &record.env {
+            options
+                .env
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        // Replay what the terminal printed last time as dimmed history above
+        // the new prompt.
+        let saved_output = self
+            .session_store
+            .lock()
+            .unwrap()
+            .scrollback_dir()
+            .and_then(|dir| load_scrollback(&dir, &terminal_id));
+        if let Some(history) = saved_output
+            .as_deref()
+            .and_then(|data| format_restored_history(data, "restored from previous session"))
+        {
+            let _ = channel.send(TerminalOutput {
+                terminal_id: terminal_id.clone(),
+                data: history,
+            });
+        }
+
+        // Offer the last command by typing it at the new prompt without
+        // pressing Enter.  Commands with control characters (older records
+        // kept line breaks) are only mentioned.
+        let offered_command = record
+            .last_command
+            .clone()
+            .filter(|_| options.offer_last_command);
+        if let Some(ref command) = offered_command {
+            let _ = channel.send(TerminalOutput {
+                terminal_id: terminal_id.clone(),
+                data: format!(
+                    "\x1b[2m[restored in {}; last command: {}]\x1b[0m\r\n",
+                    cwd.as_deref().unwrap_or("~"),
+                    command.replace(['\n', '\r'], " ")
+                ),
+            });
+        }
+
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:restore_terminal] terminal_id={} cwd={:?} history={}",
+            terminal_id,
+            cwd,
+            record.history.len()
+        ));
+
+        self.spawn_fresh(app_handle, terminal_id.clone(), cwd, cols, rows, options, channel)?;
+
+        let offered_command =
+            offered_command.filter(|command| !command.chars().any(char::is_control));
+        let type_now = {
+            let sessions = self.sessions.lock().unwrap();
+            let Some(session) = sessions.get(&terminal_id) else {
+                return Ok(());
+            };
+            let mut router = session.router.lock().unwrap();
+            router.metadata.history = record.history;
+            router.metadata.last_command = record.last_command;
+            if let Some(data) = saved_output {
+                router.scrollback.seed(&data);
+            }
+            // Only a marked prompt shows the shell is reading a command
+            // line; without marks the command is never typed.
+            match offered_command.filter(|_| router.prompt_marks) {
+                Some(command) if router.prompt_seen => Some(command),
+                command => {
+                    router.offered_command = command;
+                    None
+                }
+            }
+        };
+        if let Some(command) = type_now {
+            let _ = self.write_input(app_handle, &terminal_id, command.as_bytes());
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn spawn_fresh(
         &self,
         app_handle: &AppHandle,
@@ -651,6 +2940,7 @@ impl PtyManager {
         cwd: Option<String>,
         cols: u16,
         rows: u16,
+        options: TerminalSpawnOptions,
         channel: Channel<TerminalOutput>,
     ) -> Result<(), PtyError> {
         let pty_system = native_pty_system();
@@ -662,31 +2952,200 @@ impl PtyManager {
                 pixel_width: 0,
                 pixel_height: 0,
             })
-            .map_err(|e| PtyError::from(e))?;
+            .map_err(PtyError::from)?;
         clear_problematic_control_chars(&*pair.master);
 
+        let mut spawn_env = self.spawn_env(
+            app_handle,
+            ProjectKey::from_options(&options).as_ref(),
+            SPAWN_ACTIVATION_WAIT,
+        );
+        spawn_env.identity = Some(ShellIdentity::Terminal(terminal_identity(
+            &terminal_id,
+            &options,
+        )));
+        for notice in &spawn_env.notices {
+            let _ = channel.send(environment_notice_output(&terminal_id, notice));
+        }
+
         let mut cmd = CommandBuilder::new_default_prog();
-        apply_shell_env(&mut cmd);
+        crate::login_env::apply_to(&mut cmd);
+        apply_shell_env(&mut cmd, &spawn_env);
+        for (name, value) in &options.env {
+            cmd.env(name, value);
+        }
         if let Some(ref dir) = cwd {
             cmd.cwd(dir);
         }
 
-        let child = pair.slave.spawn_command(cmd).map_err(|e| PtyError::from(e))?;
+        let shell = cmd.get_shell();
+        let child = match pair.slave.spawn_command(cmd) {
+            Ok(child) => child,
+            Err(err) => {
+                // A missing or broken shell would leave the tab with nothing
+                // but an error.  The spawn still fails, but the terminal is
+                // kept so the user can choose safe mode for it.
+                let reason = format!("{} could not be started: {}", shell, err);
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:startup:error] terminal_id={} error={}",
+                    terminal_id, reason
+                ));
+                self.failed_spawns.lock().unwrap().insert(
+                    terminal_id.clone(),
+                    FailedSpawn {
+                        cwd,
+                        cols,
+                        rows,
+                        options,
+                        channel,
+                        shell,
+                        reason: reason.clone(),
+                    },
+                );
+                emit_startup_stalled(
+                    app_handle,
+                    StartupStalledPayload {
+                        terminal_id,
+                        stalled: true,
+                    },
+                );
+                return Err(PtyError::from(spawn_failed_message(&reason)));
+            }
+        };
         drop(pair.slave);
 
-        let writer = pair.master.take_writer().map_err(|e| PtyError::from(e))?;
-        let mut reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| PtyError::from(e))?;
+        self.start_session(
+            app_handle,
+            terminal_id,
+            pair.master,
+            child,
+            cwd,
+            (cols, rows),
+            options,
+            channel,
+            reports_prompts(&shell),
+        )
+    }
+
+    /// Start a shell that skips the user's startup files, printing why
+    /// above its prompt.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_safe_mode(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: String,
+        cwd: Option<String>,
+        cols: u16,
+        rows: u16,
+        options: TerminalSpawnOptions,
+        channel: Channel<TerminalOutput>,
+        shell: &str,
+        reason: &str,
+    ) -> Result<(), PtyError> {
+        let argv = safe_mode_argv(shell);
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:startup] safe mode terminal_id={} argv={:?}",
+            terminal_id, argv
+        ));
+
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(PtyError::from)?;
+        clear_problematic_control_chars(&*pair.master);
+
+        // Activations are not waited for; they may be what hangs.
+        let mut spawn_env = self.spawn_env(
+            app_handle,
+            ProjectKey::from_options(&options).as_ref(),
+            Duration::ZERO,
+        );
+        spawn_env.identity = Some(ShellIdentity::Terminal(terminal_identity(
+            &terminal_id,
+            &options,
+        )));
+        let mut cmd = CommandBuilder::from_argv(argv.iter().map(OsString::from).collect());
+        crate::login_env::apply_to(&mut cmd);
+        apply_shell_env(&mut cmd, &spawn_env);
+        // No shim runs to switch to the project's history, so start there.
+        if let Some(ref path) = spawn_env.history_file {
+            cmd.env("HISTFILE", path);
+        }
+        for (name, value) in &options.env {
+            cmd.env(name, value);
+        }
+        if let Some(ref dir) = cwd {
+            cmd.cwd(dir);
+        }
+
+        let _ = channel.send(TerminalOutput {
+            terminal_id: terminal_id.clone(),
+            data: safe_mode_banner(reason, &argv),
+        });
+        let child = pair.slave.spawn_command(cmd).map_err(PtyError::from)?;
+        drop(pair.slave);
+
+        self.start_session(
+            app_handle,
+            terminal_id,
+            pair.master,
+            child,
+            cwd,
+            (cols, rows),
+            options,
+            channel,
+            false,
+        )
+    }
+
+    /// Register a freshly spawned shell as the terminal's session and start
+    /// reading its output.
+    #[allow(clippy::too_many_arguments)]
+    fn start_session(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: String,
+        master: Box<dyn MasterPty + Send>,
+        child: Box<dyn Child + Send + Sync>,
+        cwd: Option<String>,
+        (cols, rows): (u16, u16),
+        options: TerminalSpawnOptions,
+        channel: Channel<TerminalOutput>,
+        prompt_marks: bool,
+    ) -> Result<(), PtyError> {
+        let writer = master.take_writer().map_err(PtyError::from)?;
+        let reader = master.try_clone_reader().map_err(PtyError::from)?;
+        let input_probe = InputModeProbe::new(&*master);
 
         let child_arc: Arc<Mutex<Option<Box<dyn Child + Send + Sync>>>> =
             Arc::new(Mutex::new(Some(child)));
 
+        let mut router = OutputRouter::new(
+            OutputMode::Streaming {
+                channel,
+                terminal_id: terminal_id.clone(),
+            },
+            Some(terminal_id.clone()),
+            cols,
+            rows,
+        );
+        router.apply_spawn_options(&terminal_id, &options);
+        router.prompt_marks = prompt_marks;
+        let router = Arc::new(Mutex::new(router));
+
         let session = PtySession {
-            master: pair.master,
+            master,
             writer,
             child: Arc::clone(&child_arc),
+            router: Arc::clone(&router),
+            created_at: Instant::now(),
+            spawn_cwd: cwd,
+            options,
+            _identity_file: None,
         };
 
         {
@@ -694,83 +3153,438 @@ impl PtyManager {
             sessions.insert(terminal_id.clone(), session);
         }
 
-        let tid = terminal_id.clone();
-        let handle = app_handle.clone();
-        std::thread::spawn(move || {
-            let mut buf = [0u8; 4096];
-            let mut carry: Vec<u8> = Vec::new();
-
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        carry.extend_from_slice(&buf[..n]);
-
-                        let split = utf8_split_point(&carry);
+        self.watch_startup(app_handle, &terminal_id, Arc::clone(&router));
+        self.spawn_output_reader(app_handle, reader, router, child_arc, input_probe);
 
-                        if split > 0 {
-                            let data = String::from_utf8_lossy(&carry[..split]).to_string();
-                            log_protocol_output_chunk(&tid, &data);
-                            let _ = channel.send(TerminalOutput {
-                                terminal_id: tid.clone(),
-                                data,
-                            });
-                        }
+        Ok(())
+    }
 
-                        // Keep only incomplete trailing bytes.
-                        carry.drain(..split);
+    /// Tell the renderer when a terminal shows no prompt within
+    /// `STARTUP_PROMPT_TIMEOUT`, typically because an rc file hangs.  The
+    /// router reports the prompt if it shows up later.
+    fn watch_startup(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+        router: Arc<Mutex<OutputRouter>>,
+    ) {
+        if router.lock().unwrap().prompt_seen {
+            return;
+        }
+        let handle = app_handle.clone();
+        let terminal_id = terminal_id.to_owned();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-startup-watch".to_string())
+            .spawn(move || {
+                std::thread::sleep(STARTUP_PROMPT_TIMEOUT);
+                {
+                    let mut r = router.lock().unwrap();
+                    // Closed, hibernated or restarted in the meantime.
+                    if r.prompt_seen || r.assigned_id.as_deref() != Some(terminal_id.as_str()) {
+                        return;
                     }
-                    Err(_) => break,
+                    // A shim that never ran sends no prompt marks at all;
+                    // waiting for one would flag every such shell.
+                    if r.prompt_marks && !r.integration_loaded {
+                        let _ = crate::debug_log::append_debug_log(&format!(
+                            "[backend:startup] terminal_id={} no integration, not watching",
+                            terminal_id
+                        ));
+                        return;
+                    }
+                    r.startup_stalled = true;
                 }
-            }
+                emit_startup_stalled(
+                    &handle,
+                    StartupStalledPayload {
+                        terminal_id,
+                        stalled: true,
+                    },
+                );
+            });
+        if let Err(err) = result {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:startup:error] failed to start watch thread error={}",
+                err
+            ));
+        }
+    }
 
-            // Flush any remaining carry bytes at EOF.
-            if !carry.is_empty() {
-                let data = String::from_utf8_lossy(&carry).to_string();
-                log_protocol_output_chunk(&tid, &data);
-                let _ = channel.send(TerminalOutput {
-                    terminal_id: tid.clone(),
-                    data,
-                });
-            }
+    /// Replace a terminal's shell with one that skips the user's startup
+    /// files, keeping its output channel, directory, size, scrollback and
+    /// command history.  A terminal whose shell failed to start gets its
+    /// first one this way.
+    pub fn restart_terminal_safe_mode(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+    ) -> Result<(), PtyError> {
+        let failed = self.failed_spawns.lock().unwrap().remove(terminal_id);
+        if let Some(failed) = failed {
+            self.spawn_safe_mode(
+                app_handle,
+                terminal_id.to_owned(),
+                failed.cwd,
+                failed.cols,
+                failed.rows,
+                failed.options,
+                failed.channel,
+                &failed.shell,
+                &failed.reason,
+            )?;
+            emit_startup_stalled(
+                app_handle,
+                StartupStalledPayload {
+                    terminal_id: terminal_id.to_owned(),
+                    stalled: false,
+                },
+            );
+            return Ok(());
+        }
 
-            let exit_code = {
-                let mut guard = child_arc.lock().unwrap();
-                if let Some(ref mut child) = *guard {
-                    child
-                        .wait()
-                        .ok()
-                        .map(|status| status.exit_code() as i32)
-                } else {
-                    None
-                }
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+
+        let size = session.master.get_size().ok();
+        let (channel, cwd, stalled, metadata, scrollback) = {
+            let mut router = session.router.lock().unwrap();
+            // Detach the reader so the old shell's exit is not reported.
+            let mode = std::mem::replace(&mut router.mode, OutputMode::Buffering(Vec::new()));
+            router.assigned_id = None;
+            let OutputMode::Streaming { channel, .. } = mode else {
+                return Err(PtyError::from(format!(
+                    "Terminal {} has no output channel",
+                    terminal_id
+                )));
             };
+            (
+                channel,
+                router.metadata.cwd.clone().or(session.spawn_cwd.clone()),
+                router.startup_stalled,
+                std::mem::take(&mut router.metadata),
+                router.scrollback.contents(),
+            )
+        };
+        if let Some(mut child) = session.child.lock().unwrap().take() {
+            let _ = child.kill();
+        }
+
+        let reason = if stalled {
+            format!(
+                "no prompt appeared within {}s; a startup file may be hanging",
+                STARTUP_PROMPT_TIMEOUT.as_secs()
+            )
+        } else {
+            String::from("restarted without startup files")
+        };
+        let mut probe = CommandBuilder::new_default_prog();
+        crate::login_env::apply_to(&mut probe);
+        self.spawn_safe_mode(
+            app_handle,
+            terminal_id.to_owned(),
+            cwd,
+            size.map_or(80, |size| size.cols),
+            size.map_or(24, |size| size.rows),
+            session.options,
+            channel,
+            &probe.get_shell(),
+            &reason,
+        )?;
+
+        if let Some(session) = self.sessions.lock().unwrap().get(terminal_id) {
+            let mut router = session.router.lock().unwrap();
+            router.metadata.history = metadata.history;
+            router.metadata.last_command = metadata.last_command;
+            router.scrollback.seed(&scrollback);
+        }
 
-            let _ = handle.emit(
-                "terminal-exit",
-                TerminalExitPayload {
-                    terminal_id: tid,
-                    exit_code,
+        if stalled {
+            emit_startup_stalled(
+                app_handle,
+                StartupStalledPayload {
+                    terminal_id: terminal_id.to_owned(),
+                    stalled: false,
                 },
             );
-        });
-
+        }
         Ok(())
     }
 
-    pub fn write_terminal(&self, terminal_id: &str, data: &[u8]) -> Result<(), PtyError> {
+    /// Write input to a terminal.  Deferred terminals are spawned first since
+    /// typing into a tab means the user is looking at it.  That spawn may wait
+    /// on a project activation, so it runs on its own thread and the input is
+    /// held until the shell exists.
+    pub fn write_terminal(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+        data: &[u8],
+    ) -> Result<(), PtyError> {
+        // No shell to type into until the user picks safe mode.
+        if self.failed_spawns.lock().unwrap().contains_key(terminal_id) {
+            return Ok(());
+        }
+        {
+            let mut pending = self.pending_input.lock().unwrap();
+            if let Some(queued) = pending.get_mut(terminal_id) {
+                queued.extend_from_slice(data);
+                return Ok(());
+            }
+            let running = self.sessions.lock().unwrap().contains_key(terminal_id);
+            if !running && self.has_terminal(terminal_id) {
+                pending.insert(terminal_id.to_string(), data.to_vec());
+                drop(pending);
+                self.start_for_input(app_handle, terminal_id);
+                return Ok(());
+            }
+        }
+        self.write_input(app_handle, terminal_id, data)
+    }
+
+    /// Start a deferred or hibernated terminal off the caller's thread, then
+    /// flush the input typed meanwhile.
+    fn start_for_input(&self, app_handle: &AppHandle, terminal_id: &str) {
+        let handle = app_handle.clone();
+        let id = terminal_id.to_string();
+        let result = std::thread::Builder::new()
+            .name("dispatcher-terminal-start".to_string())
+            .spawn(move || {
+                let manager = handle.state::<PtyManager>();
+                let mut result = manager.activate_terminal(&handle, &id).map(|_| ());
+                loop {
+                    let data = {
+                        let mut pending = manager.pending_input.lock().unwrap();
+                        match pending.get_mut(&id) {
+                            Some(queued) if result.is_ok() && !queued.is_empty() => {
+                                std::mem::take(queued)
+                            }
+                            _ => {
+                                pending.remove(&id);
+                                break;
+                            }
+                        }
+                    };
+                    result = manager.write_input(&handle, &id, &data);
+                }
+                if let Err(err) = result {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:write_terminal:error] terminal_id={} error={}",
+                        id, err.message
+                    ));
+                }
+            });
+        if let Err(err) = result {
+            self.pending_input.lock().unwrap().remove(terminal_id);
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:write_terminal:error] terminal_id={} error={}",
+                terminal_id, err
+            ));
+        }
+    }
+
+    /// Write input to a running terminal, encoded for its shell.
+    fn write_input(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+        data: &[u8],
+    ) -> Result<(), PtyError> {
         let mut sessions = self.sessions.lock().unwrap();
         let session = sessions
             .get_mut(terminal_id)
             .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        let (secure_input_changed, encoded) = {
+            let mut router = session.router.lock().unwrap();
+            router.last_activity_at = Instant::now();
+            let encoded = router.encode_input(data);
+            (
+                router.update_secure_input(master_input_mode(&*session.master)),
+                encoded,
+            )
+        };
+        if let Some(payload) = secure_input_changed {
+            emit_secure_input_changed(app_handle, payload);
+        }
         session
             .writer
-            .write_all(data)
+            .write_all(&encoded)
             .map_err(|e| PtyError::from(e))?;
         session.writer.flush().map_err(|e| PtyError::from(e))?;
         Ok(())
     }
 
+    /// Switch the encoding a terminal's output is decoded from and its input
+    /// encoded to.  `None` or a UTF-8 label means UTF-8, which is stored by
+    /// name so a restore does not fall back to the saved encoding.
+    /// Hibernated terminals use it when they respawn.
+    pub fn set_terminal_encoding(
+        &self,
+        terminal_id: &str,
+        encoding: Option<String>,
+    ) -> Result<(), PtyError> {
+        let encoding = match encoding {
+            Some(label) => lookup_encoding(&label)?,
+            None => None,
+        };
+        let name = Some(encoding.map_or(UTF_8_NAME, |encoding| encoding.name()).to_owned());
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(terminal_id) {
+            session.router.lock().unwrap().transcoder = encoding.map(TerminalTranscoder::new);
+            session.options.encoding = name;
+            return Ok(());
+        }
+        drop(sessions);
+
+        let mut hibernated = self.hibernated.lock().unwrap();
+        let entry = hibernated
+            .get_mut(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        entry.options.encoding = name;
+        Ok(())
+    }
+
+    /// The encoding a terminal was last given, if any.
+    pub fn terminal_encoding(&self, terminal_id: &str) -> Result<Option<String>, PtyError> {
+        if let Some(session) = self.sessions.lock().unwrap().get(terminal_id) {
+            return Ok(session.options.encoding.clone());
+        }
+        self.hibernated
+            .lock()
+            .unwrap()
+            .get(terminal_id)
+            .map(|entry| entry.options.encoding.clone())
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))
+    }
+
+    /// Replace a terminal's auto-responders.  Hibernated terminals keep them
+    /// for when they respawn.
+    pub fn set_auto_responders(
+        &self,
+        terminal_id: &str,
+        rules: Vec<AutoResponder>,
+    ) -> Result<(), PtyError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(terminal_id) {
+            session
+                .router
+                .lock()
+                .unwrap()
+                .responders
+                .set_rules(rules.clone())?;
+            session.options.auto_responders = rules;
+            return Ok(());
+        }
+        drop(sessions);
+
+        let mut hibernated = self.hibernated.lock().unwrap();
+        let entry = hibernated
+            .get_mut(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        ResponderSet::default().set_rules(rules.clone())?;
+        entry.options.auto_responders = rules;
+        Ok(())
+    }
+
+    pub fn auto_responders(&self, terminal_id: &str) -> Result<Vec<AutoResponderStatus>, PtyError> {
+        if let Some(session) = self.sessions.lock().unwrap().get(terminal_id) {
+            return Ok(session.router.lock().unwrap().responders.status());
+        }
+        let hibernated = self.hibernated.lock().unwrap();
+        let entry = hibernated
+            .get(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        Ok(entry
+            .options
+            .auto_responders
+            .iter()
+            .map(|rule| AutoResponderStatus {
+                rule: rule.clone(),
+                fire_count: 0,
+            })
+            .collect())
+    }
+
+    /// Type the responses the reader thread matched, through the same path
+    /// as user input.  Skipped while the terminal has echo off, since that
+    /// is a password prompt no rule should answer.
+    fn send_auto_responses(&self, terminal_id: &str, responses: Vec<PendingResponse>) {
+        let incognito = self.is_incognito(terminal_id);
+        for pending in responses {
+            let line = if incognito {
+                String::from("<redacted: incognito>")
+            } else {
+                preview_terminal_data(&pending.line, 120)
+            };
+            let mut sessions = self.sessions.lock().unwrap();
+            let Some(session) = sessions.get_mut(terminal_id) else {
+                return;
+            };
+            if !input_echo_enabled(&*session.master) {
+                drop(sessions);
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:auto_respond] terminal_id={} rule_id={} skipped reason=no_echo line={}",
+                    terminal_id,
+                    pending.rule_id, line
+                ));
+                continue;
+            }
+
+            // Count and write the answer under the router lock: the reader
+            // thread cannot scan the echoed answer or a repeated prompt until
+            // the rule's interval and limit include this fire.
+            let mut router = session.router.lock().unwrap();
+            let now = Instant::now();
+            let Some(fire_count) = router.responders.claim_fire(&pending.rule_id, now) else {
+                drop(router);
+                drop(sessions);
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:auto_respond] terminal_id={} rule_id={} skipped reason=limit line={}",
+                    terminal_id, pending.rule_id, line
+                ));
+                continue;
+            };
+            router.last_activity_at = now;
+            let encoded = router.encode_input(pending.response.as_bytes());
+            let written = session
+                .writer
+                .write_all(&encoded)
+                .and_then(|()| session.writer.flush());
+            drop(router);
+            drop(sessions);
+            if let Err(err) = written {
+                let _ = crate::debug_log::append_debug_log(&format!(
+                    "[backend:auto_respond] terminal_id={} rule_id={} write failed error={}",
+                    terminal_id, pending.rule_id, err
+                ));
+                continue;
+            }
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:auto_respond] terminal_id={} rule_id={} fired fire_count={} response_bytes={} line={}",
+                terminal_id,
+                pending.rule_id,
+                fire_count,
+                pending.response.len(),
+                line
+            ));
+        }
+    }
+
+    /// Whether input to this terminal must be kept out of logs right now.
+    /// Reads the input mode fresh, since the prompt may have just appeared.
+    pub fn secure_input_active(&self, terminal_id: &str) -> bool {
+        let sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.get(terminal_id) else {
+            return false;
+        };
+        let router_flag = session.router.lock().unwrap().secure_input;
+        router_flag || master_input_mode(&*session.master).is_some_and(InputMode::is_secure)
+    }
+
     pub fn get_terminal_debug_info(&self, terminal_id: &str) -> Result<TerminalDebugInfo, PtyError> {
         let sessions = self.sessions.lock().unwrap();
         let session = sessions
@@ -788,7 +3602,7 @@ impl PtyManager {
                 let pgid = unsafe { libc::tcgetpgrp(fd) };
                 if pgid > 0 {
                     foreground_pgid = Some(pgid);
-                    foreground_command = Command::new("ps")
+                    foreground_command = crate::login_env::command("ps")
                         .args(["-o", "comm=", "-p", &pgid.to_string()])
                         .output()
                         .ok()
@@ -835,6 +3649,14 @@ impl PtyManager {
         cols: u16,
         rows: u16,
     ) -> Result<(), PtyError> {
+        if self.spawn_queue.update_size(terminal_id, cols, rows) {
+            return Ok(());
+        }
+        if let Some(hibernated) = self.hibernated.lock().unwrap().get_mut(terminal_id) {
+            hibernated.cols = cols;
+            hibernated.rows = rows;
+            return Ok(());
+        }
         let sessions = self.sessions.lock().unwrap();
         let session = sessions
             .get(terminal_id)
@@ -848,10 +3670,211 @@ impl PtyManager {
                 pixel_height: 0,
             })
             .map_err(|e| PtyError::from(e))?;
+        session.router.lock().unwrap().screen.resize(cols, rows);
         Ok(())
     }
 
+    /// Write a terminal's output to a file and return its path.  Without a
+    /// path the file goes to `dispatcher-exports` in the app data dir.
+    pub fn export_terminal_output(
+        &self,
+        app_handle: &AppHandle,
+        terminal_id: &str,
+        format: ExportFormat,
+        range: ExportRange,
+        path: Option<String>,
+        theme: ExportTheme,
+    ) -> Result<String, PtyError> {
+        theme.validate()?;
+        let (data, cols, rows) = self.export_source(terminal_id, range)?;
+        let data = crate::redaction::redact_bytes(&data);
+        let content = render_export(&data, cols, rows, format, terminal_id, &theme);
+
+        let path = match path.filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => app_handle
+                .path()
+                .app_data_dir()
+                .map_err(|err| PtyError::from(err.to_string()))?
+                .join(EXPORT_DIR_NAME)
+                .join(export_file_name(terminal_id, now_ms(), format)),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:export_terminal] terminal_id={} format={:?} range={:?} bytes={} path={}",
+            terminal_id,
+            format,
+            range,
+            data.len(),
+            path.display()
+        ));
+        Ok(path.display().to_string())
+    }
+
+    fn export_source(
+        &self,
+        terminal_id: &str,
+        range: ExportRange,
+    ) -> Result<(Vec<u8>, u16, u16), PtyError> {
+        if let Some(hibernated) = self.hibernated.lock().unwrap().get(terminal_id) {
+            return match range {
+                ExportRange::All => Ok((hibernated.scrollback.clone(), hibernated.cols, hibernated.rows)),
+                ExportRange::LastCommand => Err(PtyError::from(format!(
+                    "Terminal {} is hibernated; only its full output can be exported",
+                    terminal_id
+                ))),
+            };
+        }
+
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        let size = session.master.get_size().ok();
+        let router = session.router.lock().unwrap();
+        let data = match range {
+            ExportRange::All => router.scrollback.contents(),
+            ExportRange::LastCommand => {
+                let (start, end) = router.last_command_output.ok_or_else(|| {
+                    PtyError::from(format!(
+                        "Terminal {} has no command output to export",
+                        terminal_id
+                    ))
+                })?;
+                router
+                    .scrollback
+                    .range(start, end.unwrap_or_else(|| router.scrollback.end_offset()))
+            }
+        };
+        Ok((data, size.map_or(80, |size| size.cols), size.map_or(24, |size| size.rows)))
+    }
+
+    /// Search the retained output of every terminal: running sessions,
+    /// hibernated ones, and saved scrollback of tabs not restored yet.  Each
+    /// terminal's matches are also sent over `on_matches` as soon as they are
+    /// found so results can be shown while the search continues.
+    pub fn search_terminals(
+        &self,
+        query: &str,
+        options: SearchOptions,
+        on_matches: Channel<Vec<TerminalSearchMatch>>,
+    ) -> Result<Vec<TerminalSearchMatch>, PtyError> {
+        let matcher = build_matcher(query, options)?;
+
+        let mut sources: Vec<SearchSource> = Vec::new();
+        for (terminal_id, session) in self.sessions.lock().unwrap().iter() {
+            let router = session.router.lock().unwrap();
+            sources.push(SearchSource {
+                terminal_id: terminal_id.clone(),
+                data: router.scrollback.contents(),
+                timestamps: router.scrollback.timestamps(),
+                origin: "live",
+            });
+        }
+        for (terminal_id, hibernated) in self.hibernated.lock().unwrap().iter() {
+            sources.push(SearchSource {
+                terminal_id: terminal_id.clone(),
+                data: hibernated.scrollback.clone(),
+                timestamps: Vec::new(),
+                origin: "hibernated",
+            });
+        }
+        let (saved_ids, scrollback_dir) = {
+            let store = self.session_store.lock().unwrap();
+            let ids: Vec<String> = store
+                .restorable()
+                .into_iter()
+                .map(|record| record.terminal_id)
+                .filter(|id| !sources.iter().any(|source| &source.terminal_id == id))
+                .collect();
+            (ids, store.scrollback_dir())
+        };
+        sources.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+
+        let mut results: Vec<TerminalSearchMatch> = Vec::new();
+        let emit = |matches: Vec<TerminalSearchMatch>, results: &mut Vec<TerminalSearchMatch>| {
+            if !matches.is_empty() {
+                let _ = on_matches.send(matches.clone());
+                results.extend(matches);
+            }
+        };
+
+        for source in &sources {
+            let limit = MAX_SEARCH_RESULTS - results.len();
+            emit(search_source(source, &matcher, limit), &mut results);
+            if results.len() >= MAX_SEARCH_RESULTS {
+                return Ok(results);
+            }
+        }
+        // Saved scrollback is read from disk one terminal at a time.
+        if let Some(dir) = scrollback_dir {
+            for terminal_id in saved_ids {
+                let Some(data) = load_scrollback(&dir, &terminal_id) else {
+                    continue;
+                };
+                let source = SearchSource {
+                    terminal_id,
+                    data,
+                    timestamps: Vec::new(),
+                    origin: "saved",
+                };
+                let limit = MAX_SEARCH_RESULTS - results.len();
+                emit(search_source(&source, &matcher, limit), &mut results);
+                if results.len() >= MAX_SEARCH_RESULTS {
+                    break;
+                }
+            }
+        }
+
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:search_terminals] regex={} case_sensitive={} terminals={} matches={}",
+            options.regex,
+            options.case_sensitive,
+            sources.len(),
+            results.len()
+        ));
+        Ok(results)
+    }
+
+    /// What the terminal currently shows, from the backend's own screen
+    /// model.  Hibernated terminals are rebuilt from their saved output.
+    pub fn get_terminal_screen(
+        &self,
+        terminal_id: &str,
+        history_lines: usize,
+    ) -> Result<TerminalScreen, PtyError> {
+        if let Some(hibernated) = self.hibernated.lock().unwrap().get(terminal_id) {
+            let mut screen = ScreenModel::new(hibernated.cols, hibernated.rows);
+            screen.advance(&hibernated.scrollback);
+            return Ok(screen.snapshot(history_lines));
+        }
+        if self.spawn_queue.cwd(terminal_id).is_some() {
+            return Err(PtyError::from(format!(
+                "Terminal {} has not been spawned yet",
+                terminal_id
+            )));
+        }
+
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(terminal_id)
+            .ok_or_else(|| PtyError::from(format!("Terminal {} not found", terminal_id)))?;
+        let snapshot = session.router.lock().unwrap().screen.snapshot(history_lines);
+        Ok(snapshot)
+    }
+
     pub fn get_terminal_cwd(&self, terminal_id: &str) -> Result<Option<String>, PtyError> {
+        if let Some(cwd) = self.spawn_queue.cwd(terminal_id) {
+            return Ok(cwd);
+        }
+        if let Some(hibernated) = self.hibernated.lock().unwrap().get(terminal_id) {
+            return Ok(hibernated.cwd.clone());
+        }
+
         // Extract the PID while holding the lock, then drop it before running
         // lsof.  Previously the sessions lock was held across the lsof call,
         // blocking all other PTY operations (create, write, resize, close).
@@ -866,7 +3889,7 @@ impl PtyManager {
 
         match pid {
             Some(pid) => {
-                let output = std::process::Command::new("lsof")
+                let output = crate::login_env::command("lsof")
                     .args(["-a", "-p", &pid.to_string(), "-d", "cwd", "-Fn"])
                     .output();
 
@@ -888,12 +3911,29 @@ impl PtyManager {
     }
 
     pub fn close_terminal(&self, terminal_id: &str) -> Result<(), PtyError> {
-        let mut sessions = self.sessions.lock().unwrap();
-        if let Some(session) = sessions.remove(terminal_id) {
-            let mut guard = session.child.lock().unwrap();
-            if let Some(ref mut child) = *guard {
-                let _ = child.kill();
+        let _ = self.spawn_queue.take(terminal_id);
+        self.failed_spawns.lock().unwrap().remove(terminal_id);
+        self.incognito.lock().unwrap().remove(terminal_id);
+        self.hibernated.lock().unwrap().remove(terminal_id);
+        self.orphans.lock().unwrap().forget(terminal_id);
+        {
+            let mut store = self.session_store.lock().unwrap();
+            store.forget(terminal_id);
+            if let Some(dir) = store.scrollback_dir() {
+                remove_scrollback(&dir, terminal_id);
+            }
+        }
+        let session = self.sessions.lock().unwrap().remove(terminal_id);
+        match session {
+            Some(session) => {
+                let mut guard = session.child.lock().unwrap();
+                if let Some(ref mut child) = *guard {
+                    let _ = child.kill();
+                }
             }
+            // A deferred, hibernated or failed terminal has no reader to
+            // report its exit, so release anyone waiting on it here.
+            None => self.exit_waiters.notify(terminal_id, None),
         }
         Ok(())
     }
@@ -905,6 +3945,70 @@ pub struct TerminalOutput {
     pub data: String,
 }
 
+/// Per-terminal spawn settings beyond cwd and size.
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TerminalSpawnOptions {
+    /// Opaque profile name chosen by the renderer; persisted for restore.
+    pub profile: Option<String>,
+    /// Environment overrides applied on top of the shell environment.
+    pub env: HashMap<String, String>,
+    /// When restoring, type the previous run's last command at the prompt.
+    pub offer_last_command: bool,
+    /// Sidebar project the terminal belongs to; scopes pattern watchers.
+    pub project_id: Option<String>,
+    /// The project's root directory, where project config files live.
+    pub project_root: Option<String>,
+    /// Expect-style rules answering prompts in this terminal.
+    pub auto_responders: Vec<AutoResponder>,
+    /// Keep the terminal out of logs, persisted scrollback, the state
+    /// backup and debug artifacts.
+    pub incognito: bool,
+    /// Encoding label for output and input, e.g. "shift_jis"; UTF-8 when
+    /// unset.
+    pub encoding: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalSpawnRequest {
+    pub terminal_id: String,
+    pub cwd: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    #[serde(default)]
+    pub deferred: bool,
+    #[serde(default, flatten)]
+    pub options: TerminalSpawnOptions,
+    pub on_output: JavaScriptChannelId,
+}
+
+#[derive(Clone, Serialize)]
+pub struct EnvVarPreview {
+    pub name: String,
+    pub value: String,
+    /// Set by the terminal's own environment overrides.
+    pub overridden: bool,
+    /// "inherited", "activation" (direnv / nix), "project" or "terminal"
+    /// (the terminal's own overrides).
+    pub source: &'static str,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SpawnEnvironmentPreview {
+    pub shell: String,
+    pub inherited: Vec<EnvVarPreview>,
+    pub removed: Vec<RemovedEnvVar>,
+    pub project_rules_path: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TerminalSpawnResult {
+    pub terminal_id: String,
+    pub status: &'static str,
+    pub error: Option<String>,
+}
+
 #[derive(Clone, Serialize)]
 pub struct TerminalDebugInfo {
     pub terminal_id: String,
@@ -914,8 +4018,43 @@ pub struct TerminalDebugInfo {
     pub vreprint: Option<u8>,
 }
 
+/// One row of `dispatcher list`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalListing {
+    pub terminal_id: String,
+    /// "running", "exited", "hibernated", "deferred" or "failed".
+    pub state: &'static str,
+    pub project_id: Option<String>,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+}
+
+/// Payload of `terminal-startup-stalled`: no prompt appeared within
+/// `STARTUP_PROMPT_TIMEOUT`, or it finally did.
+#[derive(Clone, Serialize)]
+pub struct StartupStalledPayload {
+    pub terminal_id: String,
+    pub stalled: bool,
+}
+
+/// Payload of `secure-input-changed`.
+#[derive(Clone, Serialize)]
+pub struct SecureInputPayload {
+    pub terminal_id: String,
+    pub active: bool,
+}
+
 #[derive(Clone, serde::Serialize)]
 pub struct TerminalExitPayload {
     pub terminal_id: String,
     pub exit_code: Option<i32>,
 }
+
+/// Payload of `terminal-spawn-failed`: a deferred terminal's background
+/// spawn failed, so no shell and no exit event will follow.
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalSpawnFailedPayload {
+    pub terminal_id: String,
+    pub error: String,
+}
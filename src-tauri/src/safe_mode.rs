@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::time::Duration;
+
+/// How long a new shell may take to show its first prompt before the
+/// renderer offers a safe-mode restart.
+pub const STARTUP_PROMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn shell_name(shell: &str) -> &str {
+    shell.rsplit('/').next().unwrap_or(shell)
+}
+
+/// Shells whose startup shims mark each prompt (OSC 133 A).  For others,
+/// any output counts as a prompt.
+pub fn reports_prompts(shell: &str) -> bool {
+    matches!(shell_name(shell), "bash" | "zsh" | "fish")
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// A command line for `shell` that skips the user's startup files, or
+/// `/bin/sh` when the shell itself cannot run.
+pub fn safe_mode_argv(shell: &str) -> Vec<String> {
+    argv_for(shell, is_executable(Path::new(shell)))
+}
+
+fn argv_for(shell: &str, executable: bool) -> Vec<String> {
+    let flags: &[&str] = match shell_name(shell) {
+        _ if !executable => return vec![String::from("/bin/sh")],
+        "zsh" => &["-f"],
+        "bash" => &["--noprofile", "--norc"],
+        "fish" => &["--no-config"],
+        _ => return vec![String::from("/bin/sh")],
+    };
+    std::iter::once(shell)
+        .chain(flags.iter().copied())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Printed above the safe-mode shell's prompt.
+pub fn safe_mode_banner(reason: &str, argv: &[String]) -> String {
+    format!(
+        "\x1b[33m[Dispatcher safe mode: {}]\r\n[Started `{}` without your startup files. Fix them, then open a new terminal.]\x1b[0m\r\n",
+        reason.replace(['\n', '\r'], " "),
+        argv.join(" ")
+    )
+}
+
+/// The error for a shell that could not be started.
+pub fn spawn_failed_message(reason: &str) -> String {
+    format!(
+        "{}. Use Safe mode in the tab header to start a shell without your startup files.",
+        reason.replace(['\n', '\r'], " ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_startup_files_or_falls_back_to_sh() {
+        assert_eq!(argv_for("/bin/zsh", true), vec!["/bin/zsh", "-f"]);
+        assert_eq!(
+            argv_for("/opt/homebrew/bin/bash", true),
+            vec!["/opt/homebrew/bin/bash", "--noprofile", "--norc"]
+        );
+        assert_eq!(argv_for("/usr/bin/fish", true), vec!["/usr/bin/fish", "--no-config"]);
+        assert_eq!(argv_for("/usr/bin/nu", true), vec!["/bin/sh"]);
+        assert_eq!(argv_for("/usr/local/bin/zsh", false), vec!["/bin/sh"]);
+        assert!(reports_prompts("/bin/zsh"));
+        assert!(!reports_prompts("/bin/sh"));
+    }
+}
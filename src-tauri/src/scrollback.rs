@@ -0,0 +1,318 @@
+use crate::errors::PtyError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Raw output retained per session, and the most we write to disk for it.
+const MAX_SCROLLBACK_BYTES: usize = 512 * 1024;
+/// Lines replayed above the prompt of a restored terminal.
+const MAX_RESTORED_LINES: usize = 2000;
+/// Output timestamps are recorded at most this often.
+const TIMESTAMP_INTERVAL_MS: u64 = 1000;
+const MAX_TIMESTAMPS: usize = 4096;
+pub const SCROLLBACK_DIR_NAME: &str = "dispatcher-scrollback";
+
+/// Recent raw PTY output for one session, trimmed from the front once it
+/// exceeds the cap.
+#[derive(Default)]
+pub struct ScrollbackBuffer {
+    bytes: VecDeque<u8>,
+    /// Bytes ever pushed; output offsets are measured against this.
+    total: u64,
+    /// (offset, unix ms) when output at that offset arrived, at most one
+    /// per `TIMESTAMP_INTERVAL_MS`.
+    timestamps: VecDeque<(u64, u64)>,
+    generation: u64,
+    saved_generation: u64,
+}
+
+impl ScrollbackBuffer {
+    pub fn push(&mut self, data: &[u8], now_ms: u64) {
+        let recent = self
+            .timestamps
+            .back()
+            .is_some_and(|(_, at)| now_ms < at + TIMESTAMP_INTERVAL_MS);
+        if !recent {
+            self.timestamps.push_back((self.total, now_ms));
+            if self.timestamps.len() > MAX_TIMESTAMPS {
+                self.timestamps.pop_front();
+            }
+        }
+        self.append(data);
+    }
+
+    /// Seed a restored session with the previous run's output so history
+    /// survives more than one restart.  Seeded output has no timestamps.
+    pub fn seed(&mut self, data: &[u8]) {
+        self.append(data);
+        self.saved_generation = self.generation;
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        self.bytes.extend(data);
+        self.total += data.len() as u64;
+        if self.bytes.len() > MAX_SCROLLBACK_BYTES {
+            let excess = self.bytes.len() - MAX_SCROLLBACK_BYTES;
+            self.bytes.drain(..excess);
+        }
+        let first = self.total - self.bytes.len() as u64;
+        // Keep the newest timestamp at or before the first retained byte; it
+        // still dates the start of the buffer.
+        while self.timestamps.len() > 1 && self.timestamps[1].0 <= first {
+            self.timestamps.pop_front();
+        }
+        self.generation += 1;
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.bytes.iter().copied().collect()
+    }
+
+    /// Timestamps with offsets relative to `contents()`.
+    pub fn timestamps(&self) -> Vec<(usize, u64)> {
+        let first = self.total - self.bytes.len() as u64;
+        self.timestamps
+            .iter()
+            .map(|(offset, at)| (offset.saturating_sub(first) as usize, *at))
+            .collect()
+    }
+
+    /// Offset just past the newest byte.
+    pub fn end_offset(&self) -> u64 {
+        self.total
+    }
+
+    /// Output between two offsets, clipped to what is still retained.
+    pub fn range(&self, start: u64, end: u64) -> Vec<u8> {
+        let first = self.total - self.bytes.len() as u64;
+        let start = start.clamp(first, self.total);
+        let end = end.clamp(start, self.total);
+        self.bytes
+            .range((start - first) as usize..(end - first) as usize)
+            .copied()
+            .collect()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.generation != self.saved_generation
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn mark_saved(&mut self, generation: u64) {
+        self.saved_generation = generation;
+    }
+}
+
+fn scrollback_file_name(terminal_id: &str) -> String {
+    let sanitized: String = terminal_id
+        .chars()
+        .map(|ch| match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => ch,
+            _ => '_',
+        })
+        .collect();
+    format!("{}.gz", sanitized)
+}
+
+pub fn scrollback_path(dir: &Path, terminal_id: &str) -> PathBuf {
+    dir.join(scrollback_file_name(terminal_id))
+}
+
+pub fn save_scrollback(dir: &Path, terminal_id: &str, data: &[u8]) -> Result<(), PtyError> {
+    fs::create_dir_all(dir)?;
+    let start = data.len().saturating_sub(MAX_SCROLLBACK_BYTES);
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&crate::redaction::redact_bytes(&data[start..]))?;
+    let compressed = encoder.finish()?;
+
+    let path = scrollback_path(dir, terminal_id);
+    let tmp_path = path.with_extension("gz.tmp");
+    fs::write(&tmp_path, compressed)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+pub fn load_scrollback(dir: &Path, terminal_id: &str) -> Option<Vec<u8>> {
+    let compressed = fs::read(scrollback_path(dir, terminal_id)).ok()?;
+    let mut data = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .take(MAX_SCROLLBACK_BYTES as u64)
+        .read_to_end(&mut data)
+        .ok()?;
+    Some(data)
+}
+
+pub fn remove_scrollback(dir: &Path, terminal_id: &str) {
+    let _ = fs::remove_file(scrollback_path(dir, terminal_id));
+}
+
+/// Delete saved scrollback for terminals that are neither live nor waiting
+/// to be restored.
+pub fn prune_scrollback(dir: &Path, keep_ids: &HashSet<String>) {
+    let keep: HashSet<String> = keep_ids
+        .iter()
+        .map(|id| scrollback_file_name(id))
+        .collect();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".gz") && !keep.contains(&name) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Reduce raw terminal output to the plain lines a user saw scroll by.
+/// Escape sequences are dropped, carriage-return overwrites and backspaces
+/// are applied, and anything drawn on the alternate screen (editors,
+/// pagers) is skipped because it never was part of the scrollback.
+pub fn render_history_lines(data: &[u8]) -> Vec<String> {
+    history_lines(data, MAX_RESTORED_LINES)
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// The newest `max_lines` lines of `render_history_lines`, each paired with
+/// the offset in `data` where the line started.
+pub fn history_lines(data: &[u8], max_lines: usize) -> Vec<(usize, String)> {
+    let mut lines: VecDeque<(usize, String)> = VecDeque::new();
+    let mut line = String::new();
+    let mut line_start = 0;
+    let mut in_alternate_screen = false;
+    let mut chars = decode_with_offsets(data).into_iter().peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '\u{1b}' => match chars.next().map(|(_, next)| next) {
+                Some('[') => {
+                    let mut params = String::new();
+                    for (_, next) in chars.by_ref() {
+                        if ('\u{40}'..='\u{7e}').contains(&next) {
+                            if params == "?1049" || params == "?1047" || params == "?47" {
+                                match next {
+                                    'h' => in_alternate_screen = true,
+                                    'l' => in_alternate_screen = false,
+                                    _ => {}
+                                }
+                            }
+                            break;
+                        }
+                        params.push(next);
+                    }
+                }
+                Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                    while let Some((_, next)) = chars.next() {
+                        if next == '\u{7}' {
+                            break;
+                        }
+                        if next == '\u{1b}' && chars.peek().map(|(_, c)| *c) == Some('\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                Some('(') | Some(')') | Some('*') | Some('+') | Some('#') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            _ if in_alternate_screen => {}
+            '\n' => {
+                lines.push_back((line_start, std::mem::take(&mut line)));
+                line_start = index + 1;
+                if lines.len() > max_lines {
+                    lines.pop_front();
+                }
+            }
+            '\r' => {
+                if chars.peek().map(|(_, c)| *c) != Some('\n') {
+                    line.clear();
+                }
+            }
+            '\u{8}' => {
+                line.pop();
+            }
+            '\t' => line.push('\t'),
+            c if c.is_control() => {}
+            c => line.push(c),
+        }
+    }
+
+    if !line.trim().is_empty() {
+        lines.push_back((line_start, line));
+    }
+    while lines.back().is_some_and(|(_, last)| last.trim().is_empty()) {
+        lines.pop_back();
+    }
+    while lines.len() > max_lines {
+        lines.pop_front();
+    }
+    lines.into_iter().collect()
+}
+
+/// The characters of `data`, each with its byte offset in `data`.  Invalid
+/// sequences become U+FFFD as in `String::from_utf8_lossy`, without shifting
+/// the offsets that follow.
+fn decode_with_offsets(data: &[u8]) -> Vec<(usize, char)> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut offset = 0;
+    for chunk in data.utf8_chunks() {
+        let valid = chunk.valid();
+        decoded.extend(valid.char_indices().map(|(index, ch)| (offset + index, ch)));
+        offset += valid.len();
+        if !chunk.invalid().is_empty() {
+            decoded.push((offset, char::REPLACEMENT_CHARACTER));
+            offset += chunk.invalid().len();
+        }
+    }
+    decoded
+}
+
+/// Format saved output as dimmed, read-only history followed by a separator,
+/// ready to be written to the renderer before the new shell's prompt.
+pub fn format_restored_history(data: &[u8], label: &str) -> Option<String> {
+    let lines = render_history_lines(data);
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut output = String::from("\x1b[0m\x1b[2m");
+    for line in lines {
+        output.push_str(&line);
+        output.push_str("\r\n");
+    }
+    output.push_str(&format!("── {} ──\x1b[0m\r\n", label));
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_plain_history_without_escapes_or_alternate_screen() {
+        let data = b"\x1b]7;file://h/tmp\x07\x1b[32m$\x1b[0m make\r\n\
+            building 10%\rbuilding 100%\r\n\
+            \x1b[?1049hvim screen\x1b[?1049l\
+            done\x08E\r\n\r\n";
+
+        assert_eq!(
+            render_history_lines(data),
+            vec![
+                "$ make".to_string(),
+                "building 100%".to_string(),
+                "donE".to_string(),
+            ]
+        );
+    }
+}
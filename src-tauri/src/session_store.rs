@@ -0,0 +1,164 @@
+use crate::errors::PtyError;
+use crate::scrollback::SCROLLBACK_DIR_NAME;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SESSION_STORE_FILE_NAME: &str = "dispatcher-local-sessions.json";
+pub const SESSION_PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Everything needed to bring a local terminal back after an app restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalSessionRecord {
+    pub terminal_id: String,
+    pub cwd: Option<String>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Non-UTF-8 encoding label, when the terminal uses one.
+    #[serde(default)]
+    pub encoding: Option<String>,
+    pub last_command: Option<String>,
+    #[serde(default)]
+    pub history: Vec<String>,
+    pub updated_at_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionStoreFile {
+    version: u32,
+    sessions: Vec<LocalSessionRecord>,
+}
+
+/// Persists local session records next to `dispatcher-state-backup.json`.
+///
+/// Records from the previous run stay "restorable" until the renderer
+/// creates the same terminal id again (the record is claimed), closes it, or
+/// stops reporting it as live.  Unclaimed records are written back on every
+/// save so tabs that have not been opened yet in this run are not lost.
+#[derive(Default)]
+pub struct SessionStore {
+    path: Option<PathBuf>,
+    restorable: HashMap<String, LocalSessionRecord>,
+    last_written: Option<String>,
+}
+
+impl SessionStore {
+    pub fn load(path: PathBuf) -> Self {
+        let restorable = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<SessionStoreFile>(&content) {
+                Ok(file) => file
+                    .sessions
+                    .into_iter()
+                    .map(|record| (record.terminal_id.clone(), record))
+                    .collect(),
+                Err(err) => {
+                    let _ = crate::debug_log::append_debug_log(&format!(
+                        "[backend:session_store:error] failed to parse {} error={}",
+                        path.display(),
+                        err
+                    ));
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+
+        SessionStore {
+            path: Some(path),
+            restorable,
+            last_written: None,
+        }
+    }
+
+    /// Saved scrollback lives in a directory next to the session records.
+    pub fn scrollback_dir(&self) -> Option<PathBuf> {
+        let dir = self.path.as_ref()?.parent()?;
+        Some(dir.join(SCROLLBACK_DIR_NAME))
+    }
+
+    pub fn restorable(&self) -> Vec<LocalSessionRecord> {
+        let mut records: Vec<LocalSessionRecord> = self.restorable.values().cloned().collect();
+        records.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+        records
+    }
+
+    pub fn is_restorable(&self, terminal_id: &str) -> bool {
+        self.restorable.contains_key(terminal_id)
+    }
+
+    /// Take the previous run's record for a terminal that is being created.
+    pub fn claim(&mut self, terminal_id: &str) -> Option<LocalSessionRecord> {
+        self.restorable.remove(terminal_id)
+    }
+
+    pub fn forget(&mut self, terminal_id: &str) {
+        self.restorable.remove(terminal_id);
+    }
+
+    /// Drop restorable records for tabs the renderer no longer has.
+    pub fn retain_restorable(&mut self, live_ids: &HashSet<String>) {
+        self.restorable.retain(|id, _| live_ids.contains(id));
+    }
+
+    /// Write live records plus unclaimed restorable records.  Skips the
+    /// write when nothing changed since the last save.
+    pub fn save(&mut self, live: Vec<LocalSessionRecord>) -> Result<(), PtyError> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+
+        let mut sessions = live;
+        for (id, record) in &self.restorable {
+            if !sessions.iter().any(|live| &live.terminal_id == id) {
+                sessions.push(record.clone());
+            }
+        }
+        sessions.sort_by(|a, b| a.terminal_id.cmp(&b.terminal_id));
+        // Commands typed at a prompt are a transcript.  Secret-looking
+        // variables are left out of the environment rather than restored
+        // as placeholders; the restored shell inherits them again from the
+        // app or the project env instead.
+        for record in &mut sessions {
+            record
+                .env
+                .retain(|name, value| !crate::redaction::is_secret_env_var(name, value));
+            let commands = record.last_command.iter_mut().chain(record.history.iter_mut());
+            for command in commands {
+                *command = crate::redaction::redact_text(command).into_owned();
+            }
+        }
+
+        let content = serde_json::to_string_pretty(&SessionStoreFile {
+            version: 1,
+            sessions,
+        })
+        .map_err(|err| PtyError::from(err.to_string()))?;
+        if self.last_written.as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+
+        write_atomically(&path, &content)?;
+        self.last_written = Some(content);
+        Ok(())
+    }
+}
+
+fn write_atomically(path: &Path, content: &str) -> Result<(), PtyError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
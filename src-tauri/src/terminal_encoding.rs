@@ -0,0 +1,136 @@
+use crate::errors::PtyError;
+use encoding_rs::{CoderResult, Decoder, Encoder, EncoderResult, Encoding, UTF_8};
+
+/// The name stored for a terminal switched back to UTF-8, so a restore
+/// can tell it from one that never chose.
+pub const UTF_8_NAME: &str = "UTF-8";
+
+/// Look up an encoding by WHATWG label ("latin1", "shift_jis", "gb18030",
+/// ...).  `None` means the terminal's default, UTF-8.
+pub fn lookup_encoding(label: &str) -> Result<Option<&'static Encoding>, PtyError> {
+    let encoding = Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| PtyError::from(format!("Unknown encoding {}", label)))?;
+    // UTF-16 and "replacement" decode one thing and encode another.
+    if encoding.output_encoding() != encoding {
+        return Err(PtyError::from(format!(
+            "Encoding {} cannot be used for a terminal",
+            encoding.name()
+        )));
+    }
+    Ok(Some(encoding).filter(|encoding| *encoding != UTF_8))
+}
+
+/// Converts between a terminal's legacy encoding and the UTF-8 the rest of
+/// the backend and the renderer work in.  Both directions are streaming, so
+/// a multi-byte character split across reads or writes survives.
+pub struct TerminalTranscoder {
+    decoder: Decoder,
+    encoder: Encoder,
+    /// The start of a UTF-8 character whose remaining bytes are still to
+    /// be written.
+    pending_input: Vec<u8>,
+}
+
+impl TerminalTranscoder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        TerminalTranscoder {
+            decoder: encoding.new_decoder_without_bom_handling(),
+            encoder: encoding.new_encoder(),
+            pending_input: Vec::new(),
+        }
+    }
+
+    /// Append the UTF-8 form of PTY output to `out`.  Malformed bytes become
+    /// U+FFFD; an incomplete trailing sequence waits for the next read.
+    pub fn decode(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        let capacity = self
+            .decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len() * 3 + 16);
+        let start = out.len();
+        out.resize(start + capacity, 0);
+        let (result, _, written, _) = self.decoder.decode_to_utf8(bytes, &mut out[start..], false);
+        debug_assert!(matches!(result, CoderResult::InputEmpty));
+        out.truncate(start + written);
+    }
+
+    /// Encode input for the PTY.  Malformed input and characters the
+    /// encoding cannot represent are sent as `?`; an incomplete trailing
+    /// character waits for the next write.
+    pub fn encode(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut input = std::mem::take(&mut self.pending_input);
+        input.extend_from_slice(bytes);
+        let mut out = Vec::with_capacity(input.len());
+        let mut rest: &[u8] = &input;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.encode_str(text, &mut out);
+                    return out;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    self.encode_str(std::str::from_utf8(valid).unwrap_or_default(), &mut out);
+                    match err.error_len() {
+                        Some(len) => {
+                            out.push(b'?');
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.pending_input = invalid.to_vec();
+                            return out;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_str(&mut self, mut src: &str, out: &mut Vec<u8>) {
+        loop {
+            let capacity = self
+                .encoder
+                .max_buffer_length_from_utf8_without_replacement(src.len())
+                .unwrap_or(src.len() * 4 + 16);
+            let mut buffer = vec![0u8; capacity];
+            let (result, read, written) =
+                self.encoder
+                    .encode_from_utf8_without_replacement(src, &mut buffer, false);
+            out.extend_from_slice(&buffer[..written]);
+            src = &src[read..];
+            match result {
+                EncoderResult::InputEmpty => return,
+                EncoderResult::Unmappable(_) => out.push(b'?'),
+                EncoderResult::OutputFull => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcodes_split_sequences_both_ways() {
+        let encoding = lookup_encoding("shift_jis").unwrap().unwrap();
+        let mut transcoder = TerminalTranscoder::new(encoding);
+
+        // "日本" in Shift_JIS, split inside the second character.
+        let mut out = Vec::new();
+        transcoder.decode(&[0x93, 0xfa, 0x96], &mut out);
+        transcoder.decode(&[0x7b, b'\r', b'\n'], &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "日本\r\n");
+
+        assert_eq!(transcoder.encode("ls 日本".as_bytes()), b"ls \x93\xfa\x96\x7b");
+        assert_eq!(transcoder.encode("é🙂".as_bytes()), b"??");
+        // A character split across writes, then a stray continuation byte.
+        assert_eq!(transcoder.encode(&[b'a', 0xe6, 0x97]), b"a");
+        assert_eq!(transcoder.encode(&[0xa5, 0x80]), b"\x93\xfa?");
+
+        assert!(lookup_encoding("utf8").unwrap().is_none());
+        assert_eq!(lookup_encoding("latin1").unwrap().unwrap().name(), "windows-1252");
+        assert!(lookup_encoding("utf-16le").is_err());
+        assert!(lookup_encoding("klingon").is_err());
+    }
+}
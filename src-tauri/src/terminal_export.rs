@@ -0,0 +1,351 @@
+use crate::errors::PtyError;
+use crate::terminal_screen::{CellStyle, ScreenColor, ScreenLine, ScreenModel};
+use serde::Deserialize;
+
+/// Scrollback rendered for HTML and text exports.  Raw output is capped by
+/// the scrollback buffer, so this only guards against pathological input.
+const EXPORT_HISTORY_LINES: usize = 20_000;
+pub const EXPORT_DIR_NAME: &str = "dispatcher-exports";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Html,
+    Ansi,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Text => "txt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportRange {
+    All,
+    LastCommand,
+}
+
+/// Terminal colors of the active color scheme, in the renderer's
+/// `TerminalColors` shape.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportTheme {
+    pub background: String,
+    pub foreground: String,
+    pub black: String,
+    pub red: String,
+    pub green: String,
+    pub yellow: String,
+    pub blue: String,
+    pub magenta: String,
+    pub cyan: String,
+    pub white: String,
+    pub bright_black: String,
+    pub bright_red: String,
+    pub bright_green: String,
+    pub bright_yellow: String,
+    pub bright_blue: String,
+    pub bright_magenta: String,
+    pub bright_cyan: String,
+    pub bright_white: String,
+}
+
+impl Default for ExportTheme {
+    fn default() -> Self {
+        let color = |value: &str| value.to_string();
+        ExportTheme {
+            background: color("#1e1e1e"),
+            foreground: color("#d4d4d4"),
+            black: color("#000000"),
+            red: color("#cd3131"),
+            green: color("#0dbc79"),
+            yellow: color("#e5e510"),
+            blue: color("#2472c8"),
+            magenta: color("#bc3fbc"),
+            cyan: color("#11a8cd"),
+            white: color("#e5e5e5"),
+            bright_black: color("#666666"),
+            bright_red: color("#f14c4c"),
+            bright_green: color("#23d18b"),
+            bright_yellow: color("#f5f543"),
+            bright_blue: color("#3b8eea"),
+            bright_magenta: color("#d670d6"),
+            bright_cyan: color("#29b8db"),
+            bright_white: color("#e5e5e5"),
+        }
+    }
+}
+
+impl ExportTheme {
+    /// Colors end up inside a `<style>` block and `style` attributes, so
+    /// only plain `#hex`, `rgb()` and `rgba()` values are accepted.
+    pub fn validate(&self) -> Result<(), PtyError> {
+        let colors = [
+            ("background", &self.background),
+            ("foreground", &self.foreground),
+            ("black", &self.black),
+            ("red", &self.red),
+            ("green", &self.green),
+            ("yellow", &self.yellow),
+            ("blue", &self.blue),
+            ("magenta", &self.magenta),
+            ("cyan", &self.cyan),
+            ("white", &self.white),
+            ("brightBlack", &self.bright_black),
+            ("brightRed", &self.bright_red),
+            ("brightGreen", &self.bright_green),
+            ("brightYellow", &self.bright_yellow),
+            ("brightBlue", &self.bright_blue),
+            ("brightMagenta", &self.bright_magenta),
+            ("brightCyan", &self.bright_cyan),
+            ("brightWhite", &self.bright_white),
+        ];
+        for (name, value) in colors {
+            if !is_css_color(value) {
+                return Err(PtyError::from(format!(
+                    "Invalid theme color {}: {:?}",
+                    name, value
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn css_color(&self, color: &ScreenColor) -> String {
+        match color {
+            ScreenColor::Rgb(hex) => hex.clone(),
+            ScreenColor::Indexed(index) => {
+                let palette = [
+                    &self.black,
+                    &self.red,
+                    &self.green,
+                    &self.yellow,
+                    &self.blue,
+                    &self.magenta,
+                    &self.cyan,
+                    &self.white,
+                    &self.bright_black,
+                    &self.bright_red,
+                    &self.bright_green,
+                    &self.bright_yellow,
+                    &self.bright_blue,
+                    &self.bright_magenta,
+                    &self.bright_cyan,
+                    &self.bright_white,
+                ];
+                match *index {
+                    0..=15 => palette[*index as usize].clone(),
+                    16..=231 => {
+                        let levels = [0u8, 95, 135, 175, 215, 255];
+                        let cube = index - 16;
+                        format!(
+                            "#{:02x}{:02x}{:02x}",
+                            levels[(cube / 36) as usize],
+                            levels[(cube / 6 % 6) as usize],
+                            levels[(cube % 6) as usize]
+                        )
+                    }
+                    _ => {
+                        let gray = 8 + (index - 232) * 10;
+                        format!("#{:02x}{:02x}{:02x}", gray, gray, gray)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Render raw PTY output in the requested format.  HTML and text replay the
+/// output through a screen model at the terminal's size so cursor movement,
+/// progress bars and line wrapping come out the way they were displayed.
+pub fn render_export(
+    data: &[u8],
+    cols: u16,
+    rows: u16,
+    format: ExportFormat,
+    title: &str,
+    theme: &ExportTheme,
+) -> Vec<u8> {
+    if let ExportFormat::Ansi = format {
+        return data.to_vec();
+    }
+
+    let mut screen = ScreenModel::with_history(cols, rows, EXPORT_HISTORY_LINES);
+    screen.advance(data);
+    let mut lines = screen.snapshot(EXPORT_HISTORY_LINES).lines;
+    while lines.last().is_some_and(|line| line.text.is_empty()) {
+        lines.pop();
+    }
+
+    match format {
+        ExportFormat::Html => render_html(&lines, title, theme).into_bytes(),
+        _ => render_text(&lines).into_bytes(),
+    }
+}
+
+fn render_text(lines: &[ScreenLine]) -> String {
+    let mut output = String::new();
+    for line in lines {
+        output.push_str(&line.text);
+        if !line.wrapped {
+            output.push('\n');
+        }
+    }
+    output
+}
+
+fn render_html(lines: &[ScreenLine], title: &str, theme: &ExportTheme) -> String {
+    let mut body = String::new();
+    for line in lines {
+        for span in &line.spans {
+            let style = span_css(&span.style, theme);
+            if style.is_empty() {
+                body.push_str(&escape_html(&span.text));
+            } else {
+                body.push_str(&format!(
+                    "<span style=\"{}\">{}</span>",
+                    style,
+                    escape_html(&span.text)
+                ));
+            }
+        }
+        if !line.wrapped {
+            body.push('\n');
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\nbody {{ margin: 0; background: {bg}; color: {fg}; }}\n\
+         pre {{ margin: 0; padding: 16px; font-family: Menlo, Monaco, \"Courier New\", monospace; \
+         font-size: 13px; line-height: 1.35; white-space: pre-wrap; word-break: break-all; }}\n\
+         </style>\n</head>\n<body>\n<pre>{body}</pre>\n</body>\n</html>\n",
+        title = escape_html(title),
+        bg = theme.background,
+        fg = theme.foreground,
+        body = body,
+    )
+}
+
+fn span_css(style: &CellStyle, theme: &ExportTheme) -> String {
+    let mut fg = style.fg.as_ref().map(|color| theme.css_color(color));
+    let mut bg = style.bg.as_ref().map(|color| theme.css_color(color));
+    if style.inverse {
+        let swapped_fg = bg.unwrap_or_else(|| theme.background.clone());
+        let swapped_bg = fg.unwrap_or_else(|| theme.foreground.clone());
+        fg = Some(swapped_fg);
+        bg = Some(swapped_bg);
+    }
+
+    let mut css = Vec::new();
+    if let Some(fg) = fg {
+        css.push(format!("color:{}", fg));
+    }
+    if let Some(bg) = bg {
+        css.push(format!("background:{}", bg));
+    }
+    if style.bold {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.dim {
+        css.push("opacity:0.6".to_string());
+    }
+    if style.italic {
+        css.push("font-style:italic".to_string());
+    }
+    match (style.underline, style.strikethrough) {
+        (true, true) => css.push("text-decoration:underline line-through".to_string()),
+        (true, false) => css.push("text-decoration:underline".to_string()),
+        (false, true) => css.push("text-decoration:line-through".to_string()),
+        (false, false) => {}
+    }
+    if style.hidden {
+        css.push("visibility:hidden".to_string());
+    }
+    css.join(";")
+}
+
+fn is_css_color(value: &str) -> bool {
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|ch| ch.is_ascii_hexdigit());
+    }
+    let args = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'));
+    args.is_some_and(|args| {
+        args.chars()
+            .all(|ch| ch.is_ascii_digit() || matches!(ch, ',' | '.' | ' ' | '%'))
+    })
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn export_file_name(terminal_id: &str, timestamp_ms: u64, format: ExportFormat) -> String {
+    let sanitized: String = terminal_id
+        .chars()
+        .map(|ch| match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => ch,
+            _ => '_',
+        })
+        .collect();
+    format!("{}-{}.{}", sanitized, timestamp_ms, format.extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_text_and_html_from_replayed_output() {
+        let data = b"$ make\r\nbuilding 10%\rbuilding 100%\r\n\x1b[31merror: <bad>\x1b[0m\r\n";
+        let theme = ExportTheme::default();
+
+        let text = render_export(data, 40, 5, ExportFormat::Text, "t", &theme);
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "$ make\nbuilding 100%\nerror: <bad>\n"
+        );
+
+        let html =
+            String::from_utf8(render_export(data, 40, 5, ExportFormat::Html, "t", &theme)).unwrap();
+        assert!(html.contains("<span style=\"color:#cd3131\">error: &lt;bad&gt;</span>"));
+        assert!(html.contains("background: #1e1e1e"));
+    }
+
+    #[test]
+    fn rejects_theme_colors_that_are_not_plain_colors() {
+        assert!(ExportTheme::default().validate().is_ok());
+
+        let mut theme = ExportTheme {
+            red: "rgba(255, 0, 0, 0.5)".to_string(),
+            ..ExportTheme::default()
+        };
+        assert!(theme.validate().is_ok());
+
+        theme.background = "#000; } body { background: url(x)".to_string();
+        assert!(theme.validate().is_err());
+        theme.background = "red\" onload=\"x".to_string();
+        assert!(theme.validate().is_err());
+    }
+}
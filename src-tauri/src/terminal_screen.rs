@@ -0,0 +1,303 @@
+use alacritty_terminal::event::VoidListener;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::{Config, TermMode};
+use alacritty_terminal::vte::ansi::{Color, NamedColor, Processor};
+use alacritty_terminal::Term;
+use serde::Serialize;
+
+/// Lines kept above the visible screen.  The renderer keeps far more; this
+/// only has to cover search and export of recent output.
+const SCREEN_HISTORY_LINES: usize = 1000;
+
+struct ScreenSize {
+    cols: usize,
+    rows: usize,
+}
+
+impl Dimensions for ScreenSize {
+    fn total_lines(&self) -> usize {
+        self.rows
+    }
+
+    fn screen_lines(&self) -> usize {
+        self.rows
+    }
+
+    fn columns(&self) -> usize {
+        self.cols
+    }
+}
+
+/// Headless copy of what a terminal shows, fed with the same bytes as the
+/// renderer.  Replies the emulator would send back to the shell (cursor
+/// position reports and the like) are dropped; xterm.js answers those.
+pub struct ScreenModel {
+    term: Term<VoidListener>,
+    parser: Processor,
+}
+
+impl ScreenModel {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self::with_history(cols, rows, SCREEN_HISTORY_LINES)
+    }
+
+    pub fn with_history(cols: u16, rows: u16, history_lines: usize) -> Self {
+        let config = Config {
+            scrolling_history: history_lines,
+            ..Config::default()
+        };
+        ScreenModel {
+            term: Term::new(config, &screen_size(cols, rows), VoidListener),
+            parser: Processor::new(),
+        }
+    }
+
+    pub fn advance(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.term, bytes);
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.term.resize(screen_size(cols, rows));
+    }
+
+    /// The visible screen plus up to `history_lines` lines of scrollback
+    /// above it.
+    pub fn snapshot(&self, history_lines: usize) -> TerminalScreen {
+        let grid = self.term.grid();
+        let rows = grid.screen_lines();
+        let history = history_lines.min(grid.history_size()) as i32;
+        let lines = (-history..rows as i32)
+            .map(|line| self.render_line(Line(line)))
+            .collect();
+        let cursor = grid.cursor.point;
+
+        TerminalScreen {
+            cols: grid.columns() as u16,
+            rows: rows as u16,
+            history_lines: history as u32,
+            cursor: ScreenCursor {
+                row: cursor.line.0.max(0) as u16,
+                col: cursor.column.0 as u16,
+                visible: self.term.mode().contains(TermMode::SHOW_CURSOR),
+            },
+            alternate_screen: self.term.mode().contains(TermMode::ALT_SCREEN),
+            lines,
+        }
+    }
+
+    fn render_line(&self, line: Line) -> ScreenLine {
+        let row = &self.term.grid()[line];
+        let mut text = String::new();
+        let mut spans: Vec<ScreenSpan> = Vec::new();
+        let mut current: Option<ScreenSpan> = None;
+
+        for col in 0..row.len() {
+            let cell = &row[Column(col)];
+            if cell
+                .flags
+                .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER)
+            {
+                continue;
+            }
+
+            let style = CellStyle::from_cell(cell);
+            let mut content = String::new();
+            content.push(cell.c);
+            if let Some(zerowidth) = cell.zerowidth() {
+                content.extend(zerowidth);
+            }
+            text.push_str(&content);
+
+            match current.as_mut() {
+                Some(span) if span.style == style => span.text.push_str(&content),
+                _ => {
+                    if let Some(span) = current.take() {
+                        spans.push(span);
+                    }
+                    current = Some(ScreenSpan {
+                        text: content,
+                        style,
+                    });
+                }
+            }
+        }
+        if let Some(span) = current {
+            spans.push(span);
+        }
+
+        // Trailing blank cells are padding, not output.
+        while let Some(last) = spans.last_mut() {
+            if !last.style.is_plain() {
+                break;
+            }
+            last.text.truncate(last.text.trim_end_matches(' ').len());
+            if !last.text.is_empty() {
+                break;
+            }
+            spans.pop();
+        }
+        text.truncate(text.trim_end_matches(' ').len());
+
+        ScreenLine {
+            text,
+            wrapped: row[Column(row.len().saturating_sub(1))]
+                .flags
+                .contains(Flags::WRAPLINE),
+            spans,
+        }
+    }
+}
+
+fn screen_size(cols: u16, rows: u16) -> ScreenSize {
+    ScreenSize {
+        cols: cols.max(2) as usize,
+        rows: rows.max(1) as usize,
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct TerminalScreen {
+    pub cols: u16,
+    pub rows: u16,
+    /// Scrollback lines at the start of `lines`, above the visible screen.
+    pub history_lines: u32,
+    pub cursor: ScreenCursor,
+    pub alternate_screen: bool,
+    pub lines: Vec<ScreenLine>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScreenCursor {
+    /// Row within the visible screen.
+    pub row: u16,
+    pub col: u16,
+    pub visible: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScreenLine {
+    pub text: String,
+    /// The line continues on the next one (soft wrap).
+    pub wrapped: bool,
+    pub spans: Vec<ScreenSpan>,
+}
+
+/// A run of cells sharing the same attributes.
+#[derive(Clone, Serialize)]
+pub struct ScreenSpan {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: CellStyle,
+}
+
+/// Palette colors are 0-255 indexes, true colors are `#rrggbb`, and the
+/// default foreground/background is omitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum ScreenColor {
+    Indexed(u8),
+    Rgb(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CellStyle {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<ScreenColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<ScreenColor>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dim: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub underline: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub inverse: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub strikethrough: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl CellStyle {
+    fn from_cell(cell: &Cell) -> Self {
+        let flags = cell.flags;
+        CellStyle {
+            fg: screen_color(&cell.fg),
+            bg: screen_color(&cell.bg),
+            bold: flags.contains(Flags::BOLD),
+            dim: flags.contains(Flags::DIM),
+            italic: flags.contains(Flags::ITALIC),
+            underline: flags.intersects(Flags::ALL_UNDERLINES),
+            inverse: flags.contains(Flags::INVERSE),
+            strikethrough: flags.contains(Flags::STRIKEOUT),
+            hidden: flags.contains(Flags::HIDDEN),
+        }
+    }
+
+    fn is_plain(&self) -> bool {
+        *self == CellStyle::default()
+    }
+}
+
+fn screen_color(color: &Color) -> Option<ScreenColor> {
+    match color {
+        Color::Spec(rgb) => Some(ScreenColor::Rgb(format!(
+            "#{:02x}{:02x}{:02x}",
+            rgb.r, rgb.g, rgb.b
+        ))),
+        Color::Indexed(index) => Some(ScreenColor::Indexed(*index)),
+        Color::Named(named) => {
+            let index = match named {
+                NamedColor::Black | NamedColor::DimBlack => 0,
+                NamedColor::Red | NamedColor::DimRed => 1,
+                NamedColor::Green | NamedColor::DimGreen => 2,
+                NamedColor::Yellow | NamedColor::DimYellow => 3,
+                NamedColor::Blue | NamedColor::DimBlue => 4,
+                NamedColor::Magenta | NamedColor::DimMagenta => 5,
+                NamedColor::Cyan | NamedColor::DimCyan => 6,
+                NamedColor::White | NamedColor::DimWhite => 7,
+                NamedColor::BrightBlack => 8,
+                NamedColor::BrightRed => 9,
+                NamedColor::BrightGreen => 10,
+                NamedColor::BrightYellow => 11,
+                NamedColor::BrightBlue => 12,
+                NamedColor::BrightMagenta => 13,
+                NamedColor::BrightCyan => 14,
+                NamedColor::BrightWhite => 15,
+                _ => return None,
+            };
+            Some(ScreenColor::Indexed(index))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_text_attributes_cursor_and_alternate_screen() {
+        let mut screen = ScreenModel::new(20, 4);
+        screen.advance(b"$ ls\r\n\x1b[1;31mred\x1b[0m file\r\n$ ");
+
+        let snapshot = screen.snapshot(0);
+        assert_eq!(snapshot.lines[0].text, "$ ls");
+        assert_eq!(snapshot.lines[1].text, "red file");
+        assert_eq!(snapshot.lines[1].spans[0].text, "red");
+        assert!(snapshot.lines[1].spans[0].style.bold);
+        assert_eq!(snapshot.lines[1].spans[0].style.fg, Some(ScreenColor::Indexed(1)));
+        assert_eq!(snapshot.lines[1].spans[1].text, " file");
+        assert_eq!((snapshot.cursor.row, snapshot.cursor.col), (2, 2));
+        assert!(!snapshot.alternate_screen);
+
+        screen.advance(b"\x1b[?1049h\x1b[Hvim");
+        let snapshot = screen.snapshot(0);
+        assert!(snapshot.alternate_screen);
+        assert_eq!(snapshot.lines[0].text, "vim");
+    }
+}
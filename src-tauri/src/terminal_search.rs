@@ -0,0 +1,167 @@
+use crate::errors::PtyError;
+use crate::scrollback::history_lines;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Searches stop after this many matches across all terminals.
+pub const MAX_SEARCH_RESULTS: usize = 1000;
+/// Only the newest lines of each terminal are searched.
+const MAX_SEARCH_LINES: usize = 20_000;
+const CONTEXT_LINES: usize = 2;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    /// Treat the query as a regular expression instead of literal text.
+    pub regex: bool,
+    pub case_sensitive: bool,
+}
+
+pub fn build_matcher(query: &str, options: SearchOptions) -> Result<Regex, PtyError> {
+    if query.is_empty() {
+        return Err(PtyError::from(String::from("Search query is empty")));
+    }
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|err| PtyError::from(format!("Invalid search pattern: {}", err)))
+}
+
+/// Retained output of one terminal, copied out of the session so the
+/// search runs without holding any locks.
+pub struct SearchSource {
+    pub terminal_id: String,
+    pub data: Vec<u8>,
+    /// (offset into `data`, unix ms) pairs, oldest first.
+    pub timestamps: Vec<(usize, u64)>,
+    /// Where the output came from: "live", "hibernated" or "saved".
+    pub origin: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalSearchMatch {
+    pub terminal_id: String,
+    /// Line index within the terminal's retained output, oldest first.
+    pub line_number: usize,
+    pub line: String,
+    /// Range of the first match within `line`, in UTF-16 code units so the
+    /// renderer can slice `line` with it directly.
+    pub match_start: usize,
+    pub match_end: usize,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+    /// When the line was printed, if the backend saw it arrive.
+    pub timestamp_ms: Option<u64>,
+    pub origin: &'static str,
+}
+
+pub fn search_source(
+    source: &SearchSource,
+    matcher: &Regex,
+    limit: usize,
+) -> Vec<TerminalSearchMatch> {
+    let lines = history_lines(&source.data, MAX_SEARCH_LINES);
+    let mut matches = Vec::new();
+
+    for (index, (offset, line)) in lines.iter().enumerate() {
+        if matches.len() >= limit {
+            break;
+        }
+        let Some(found) = matcher.find(line) else {
+            continue;
+        };
+
+        let before_start = index.saturating_sub(CONTEXT_LINES);
+        let after_end = (index + 1 + CONTEXT_LINES).min(lines.len());
+        matches.push(TerminalSearchMatch {
+            terminal_id: source.terminal_id.clone(),
+            line_number: index,
+            line: line.clone(),
+            match_start: line[..found.start()].encode_utf16().count(),
+            match_end: line[..found.end()].encode_utf16().count(),
+            context_before: lines[before_start..index]
+                .iter()
+                .map(|(_, line)| line.clone())
+                .collect(),
+            context_after: lines[index + 1..after_end]
+                .iter()
+                .map(|(_, line)| line.clone())
+                .collect(),
+            timestamp_ms: timestamp_at(&source.timestamps, *offset),
+            origin: source.origin,
+        });
+    }
+
+    matches
+}
+
+fn timestamp_at(timestamps: &[(usize, u64)], offset: usize) -> Option<u64> {
+    let index = timestamps.partition_point(|(start, _)| *start <= offset);
+    index.checked_sub(1).map(|index| timestamps[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_lines_with_context_and_timestamps() {
+        let source = SearchSource {
+            terminal_id: "t1".to_string(),
+            data: b"$ cargo test\r\nrunning 3 tests\r\n\x1b[31mpanicked at src/lib.rs\x1b[0m\r\ndone\r\n"
+                .to_vec(),
+            timestamps: vec![(0, 1_000), (30, 2_000)],
+            origin: "live",
+        };
+
+        let matcher = build_matcher("PANICKED", SearchOptions::default()).unwrap();
+        let matches = search_source(&source, &matcher, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, "panicked at src/lib.rs");
+        assert_eq!((matches[0].match_start, matches[0].match_end), (0, 8));
+        assert_eq!(
+            matches[0].context_before,
+            vec!["$ cargo test", "running 3 tests"]
+        );
+        assert_eq!(matches[0].context_after, vec!["done"]);
+        assert_eq!(matches[0].timestamp_ms, Some(2_000));
+
+        let case_sensitive = SearchOptions {
+            case_sensitive: true,
+            ..SearchOptions::default()
+        };
+        let matcher = build_matcher("PANICKED", case_sensitive).unwrap();
+        assert!(search_source(&source, &matcher, 10).is_empty());
+
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert!(build_matcher("tests?(", regex).is_err());
+    }
+
+    #[test]
+    fn ranges_and_timestamps_survive_wide_and_invalid_characters() {
+        // The invalid bytes must not shift the second line past the chunk
+        // that arrived after it; the emoji is two UTF-16 code units.
+        let source = SearchSource {
+            terminal_id: "t1".to_string(),
+            data: b"\xff\xfe build\r\n\xf0\x9f\x94\xa5 failed\r\n".to_vec(),
+            timestamps: vec![(0, 1_000), (12, 2_000)],
+            origin: "live",
+        };
+
+        let matcher = build_matcher("failed", SearchOptions::default()).unwrap();
+        let matches = search_source(&source, &matcher, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, "\u{1f525} failed");
+        assert_eq!((matches[0].match_start, matches[0].match_end), (3, 9));
+        assert_eq!(matches[0].timestamp_ms, Some(1_000));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::env_activation::run_captured;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// TERM for spawned shells.  Every host knows it, so ssh, sudo and root
+/// shells keep working; locally our extended entry of the same name is
+/// found first through TERMINFO_DIRS.
+pub const TERM: &str = "xterm-256color";
+const TIC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What xterm.js renders beyond xterm-256color: 24-bit color, styled and
+/// colored underlines, synchronized output (DEC 2026), cursor shapes,
+/// bracketed paste and focus reporting.  Built from the same pieces as the
+/// stock entry, since an entry cannot `use=` its own name.
+const TERMINFO_SOURCE: &str = r#"xterm-256color|Dispatcher terminal emulator,
+	Tc,
+	setrgbf=\E[38;2;%p1%d;%p2%d;%p3%dm,
+	setrgbb=\E[48;2;%p1%d;%p2%d;%p3%dm,
+	Smulx=\E[4:%p1%dm,
+	Setulc=\E[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%dm,
+	Sync=\E[?2026%?%p1%{1}%-%tl%eh%;,
+	Ss=\E[%p1%d q,
+	Se=\E[0 q,
+	BE=\E[?2004h,
+	BD=\E[?2004l,
+	PS=\E[200~,
+	PE=\E[201~,
+	fe=\E[?1004h,
+	fd=\E[?1004l,
+	kxIN=\E[I,
+	kxOUT=\E[O,
+	use=xterm+256color,
+	use=xterm-new,
+"#;
+
+/// The compiled database directory, once installed.
+static INSTALLED: OnceLock<PathBuf> = OnceLock::new();
+
+/// Install the entry in the background at startup.  Shells spawned before
+/// it finishes get the stock xterm-256color.
+pub fn start_install() {
+    let result = std::thread::Builder::new()
+        .name("dispatcher-terminfo".to_string())
+        .spawn(|| {
+            if let Some(dir) = install() {
+                let _ = INSTALLED.set(dir);
+            }
+        });
+    if let Err(err) = result {
+        let _ = crate::debug_log::append_debug_log(&format!(
+            "[backend:terminfo:error] failed to start install thread error={}",
+            err
+        ));
+    }
+}
+
+/// TERMINFO_DIRS for a new shell: our database ahead of `inherited`, or
+/// `None` while the entry is not installed.
+pub fn terminfo_dirs(inherited: Option<&str>) -> Option<String> {
+    INSTALLED
+        .get()
+        .map(|dir| prepend_terminfo_dir(dir, inherited))
+}
+
+/// An empty entry stands for the system database, so it is kept when
+/// nothing was inherited.
+fn prepend_terminfo_dir(dir: &Path, inherited: Option<&str>) -> String {
+    match inherited.filter(|dirs| !dirs.is_empty()) {
+        Some(dirs) => format!("{}:{}", dir.display(), dirs),
+        None => format!("{}:", dir.display()),
+    }
+}
+
+/// ncurses stores entries under their first letter, macOS under its hex
+/// code.
+fn compiled_entry_exists(terminfo_dir: &Path) -> bool {
+    let first = TERM.as_bytes()[0];
+    [
+        terminfo_dir.join((first as char).to_string()),
+        terminfo_dir.join(format!("{:x}", first)),
+    ]
+    .iter()
+    .any(|dir| dir.join(TERM).is_file())
+}
+
+/// Compile the entry into `~/.dispatcher/terminfo/db` unless the copy of
+/// the source from the last install matches, so an updated entry is
+/// installed once.
+fn install() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from)?;
+    let root = home.join(".dispatcher").join("terminfo");
+    let terminfo_dir = root.join("db");
+    let source_path = root.join(format!("{}.terminfo", TERM));
+
+    let installed_source = fs::read_to_string(&source_path).ok();
+    if installed_source.as_deref() == Some(TERMINFO_SOURCE) && compiled_entry_exists(&terminfo_dir)
+    {
+        return Some(terminfo_dir);
+    }
+
+    let result = (|| -> Result<(), String> {
+        fs::create_dir_all(&terminfo_dir).map_err(|err| err.to_string())?;
+        fs::write(&source_path, TERMINFO_SOURCE).map_err(|err| err.to_string())?;
+        let mut command = crate::login_env::command("tic");
+        command.arg("-x").arg("-o").arg(&terminfo_dir).arg(&source_path);
+        let (status, _, stderr) = run_captured(&mut command, TIC_TIMEOUT)?;
+        if !status.success() {
+            // Keep the next launch from skipping the install.
+            let _ = fs::remove_file(&source_path);
+            return Err(format!(
+                "tic exited with {}: {}",
+                status,
+                String::from_utf8_lossy(&stderr).trim()
+            ));
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) if compiled_entry_exists(&terminfo_dir) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:terminfo] installed name={} dir={}",
+                TERM,
+                terminfo_dir.display()
+            ));
+            Some(terminfo_dir)
+        }
+        Ok(()) => None,
+        Err(err) => {
+            let _ = crate::debug_log::append_debug_log(&format!(
+                "[backend:terminfo:error] install failed, using the system {} error={}",
+                TERM, err
+            ));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepends_our_database_and_keeps_the_system_one() {
+        let dir = Path::new("/home/me/.dispatcher/terminfo/db");
+        assert_eq!(
+            prepend_terminfo_dir(dir, None),
+            "/home/me/.dispatcher/terminfo/db:"
+        );
+        assert_eq!(
+            prepend_terminfo_dir(dir, Some("")),
+            "/home/me/.dispatcher/terminfo/db:"
+        );
+        assert_eq!(
+            prepend_terminfo_dir(dir, Some("/opt/terminfo:")),
+            "/home/me/.dispatcher/terminfo/db:/opt/terminfo:"
+        );
+    }
+}
@@ -2,12 +2,14 @@ import { useEffect, useCallback, useState, useRef } from "react";
 import { Sidebar } from "./components/Sidebar/Sidebar";
 import { ProjectView } from "./components/Layout/ProjectView";
 import { KeyDebugOverlay } from "./components/common/KeyDebugOverlay";
+import { OrphanedTerminalsDialog } from "./components/common/OrphanedTerminalsDialog";
 import { NameDialog } from "./components/common/NameDialog";
 import { useProjectStore } from "./stores/useProjectStore";
 import { useLayoutStore } from "./stores/useLayoutStore";
 import { useTerminalStore } from "./stores/useTerminalStore";
 import { useFontStore } from "./stores/useFontStore";
 import { useColorSchemeStore } from "./stores/useColorSchemeStore";
+import { usePatternWatcherStore } from "./stores/usePatternWatcherStore";
 import { applyUIColors } from "./lib/colorSchemes";
 import {
   isCloseTabShortcut,
@@ -16,8 +18,31 @@ import {
   shouldBypassAppShortcutsForTerminal,
 } from "./lib/keyboardShortcuts";
 import { findTerminalIds, findLayoutKeyForTerminal, findSiblingTerminalId } from "./lib/layoutUtils";
-import { closeTerminal, warmPool, refreshPool, getTerminalCwd, writeTerminal } from "./lib/tauriCommands";
-import { disposeTerminalInstance } from "./hooks/useTerminalBridge";
+import {
+  activateTerminal,
+  closeTerminal,
+  warmPool,
+  refreshPool,
+  reconcileTerminals,
+  getRestorableTerminals,
+  getTerminalActivity,
+  getTerminalCwd,
+  writeTerminal,
+  setPatternWatchers,
+  completeControlRequest,
+} from "./lib/tauriCommands";
+import type {
+  LocalSessionRecord,
+  OrphanedTerminal,
+  PatternWatcher,
+  TerminalActivity,
+} from "./lib/tauriCommands";
+import {
+  adoptTerminalBackend,
+  disposeTerminalInstance,
+  reportTerminalSpawnFailure,
+  startDeferredTerminals,
+} from "./hooks/useTerminalBridge";
 import { useFileDrop } from "./hooks/useFileDrop";
 import { useAppStateBackup } from "./hooks/useAppStateBackup";
 import { useRecoveryBootstrap } from "./hooks/useRecoveryBootstrap";
@@ -37,11 +62,24 @@ import {
   handleTmuxTerminalFocus,
   isDisconnectedTmuxPlaceholderTerminal,
   isLiveTmuxTerminal,
+  renameTmuxTerminal,
   resolvePreferredTerminalFocus,
   splitTmuxTerminal,
 } from "./lib/tmuxControl";
-import { onTerminalExit } from "./lib/terminalEvents";
+import {
+  onTerminalActivityChanged,
+  onTerminalControlRequest,
+  onTerminalExit,
+  onTerminalHibernationChanged,
+  onTerminalOrphansDetected,
+  onTerminalPatternMatched,
+  onTerminalSpawnFailed,
+  onSecureInputChanged,
+  onTerminalStartupStalled,
+  type TerminalControlRequest,
+} from "./lib/terminalEvents";
 import { collectVisibleTerminalRefs, findProjectIdForTerminal } from "./lib/treeUtils";
+import type { TerminalSession } from "./types/terminal";
 import "./App.css";
 
 function generateId(): string {
@@ -177,6 +215,7 @@ export default function App() {
   }, []);
 
   const [dialog, setDialog] = useState<DialogMode>(null);
+  const [orphans, setOrphans] = useState<OrphanedTerminal[]>([]);
   const [sidebarWidth, setSidebarWidth] = useState(240);
   const sidebarDividerRef = useRef<HTMLDivElement>(null);
 
@@ -215,6 +254,16 @@ export default function App() {
     return () => clearInterval(id);
   }, []);
 
+  // Report which terminals the renderer still owns so the backend can find
+  // PTYs that were dropped without close_terminal.
+  useEffect(() => {
+    const id = setInterval(() => {
+      const liveIds = Object.keys(useTerminalStore.getState().sessions);
+      reconcileTerminals(liveIds).catch(() => {});
+    }, 60 * 1000);
+    return () => clearInterval(id);
+  }, []);
+
   useEffect(() => {
     let unlisten: (() => void) | null = null;
     onTerminalExit((payload) => {
@@ -223,6 +272,104 @@ export default function App() {
     return () => { unlisten?.(); };
   }, []);
 
+  // Local tabs that are not on screen at startup get their shells from the
+  // backend's background spawner. Mounted panes have already created theirs.
+  // The backend's records of the last run carry each tab's final directory.
+  useEffect(() => {
+    getRestorableTerminals()
+      .catch((): LocalSessionRecord[] => [])
+      .then((records) => {
+        const { sessions, updateCwd } = useTerminalStore.getState();
+        for (const record of records) {
+          if (record.cwd && sessions[record.terminal_id]) {
+            updateCwd(record.terminal_id, record.cwd);
+          }
+        }
+        startDeferredTerminals(Object.keys(useTerminalStore.getState().sessions));
+      });
+    let unlisten: (() => void) | null = null;
+    onTerminalSpawnFailed(({ terminal_id, error }) => {
+      reportTerminalSpawnFailure(terminal_id, error);
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Local PTY output timestamps come from the backend reader threads, so
+  // tab status keeps updating for tabs that are not mounted or throttled.
+  // Changes arrive as events; a reloaded renderer reads the current state
+  // once so shells that were already running show their status.
+  useEffect(() => {
+    const applyActivity = (activity: TerminalActivity) => {
+      useTerminalStore
+        .getState()
+        .applyBackendActivity(activity.terminal_id, activity.last_output_at_ms);
+    };
+    getTerminalActivity()
+      .then((activity) => activity.forEach(applyActivity))
+      .catch(() => {});
+    let unlisten: (() => void) | null = null;
+    onTerminalActivityChanged(applyActivity).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // The backend watches each PTY's echo flag; while a password prompt is up
+  // the input bridge keeps keystrokes out of the debug logs.
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    onSecureInputChanged(({ terminal_id, active }) => {
+      useTerminalStore.getState().patchSession(terminal_id, { isSecureInput: active });
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Idle shells the backend shut down are dimmed in the sidebar until their
+  // tab is used again.
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    onTerminalHibernationChanged(({ terminal_id, hibernated }) => {
+      useTerminalStore.getState().patchSession(terminal_id, { isHibernated: hibernated });
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // A shell that fails to start or shows no prompt in time (a hanging rc
+  // file) gets a safe-mode button in its header until the prompt appears.
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    onTerminalStartupStalled(({ terminal_id, stalled }) => {
+      useTerminalStore.getState().patchSession(terminal_id, { isStartupStalled: stalled });
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Output pattern watchers run in the backend reader threads; keep them in
+  // sync with the persisted list and flag tabs whose watcher asks for it.
+  useEffect(() => {
+    const pushWatchers = (watchers: PatternWatcher[]) => {
+      setPatternWatchers(watchers).catch((err) => {
+        debugLog("app.patternWatchers", "failed to apply watchers", { error: String(err) });
+      });
+    };
+    pushWatchers(usePatternWatcherStore.getState().watchers);
+    const unsubscribe = usePatternWatcherStore.subscribe((state, prev) => {
+      if (state.watchers !== prev.watchers) {
+        pushWatchers(state.watchers);
+      }
+    });
+
+    let unlisten: (() => void) | null = null;
+    onTerminalPatternMatched((match) => {
+      const terminalState = useTerminalStore.getState();
+      if (match.needs_attention && terminalState.activeTerminalId !== match.terminal_id) {
+        terminalState.setNeedsAttention(match.terminal_id, true);
+      }
+    }).then((fn) => { unlisten = fn; });
+    return () => {
+      unsubscribe();
+      unlisten?.();
+    };
+  }, []);
+
   // Apply UI colors from color scheme on mount and subscribe to changes
   useEffect(() => {
     applyUIColors(useColorSchemeStore.getState().getActiveScheme().ui);
@@ -256,6 +403,19 @@ export default function App() {
     });
   }, []);
 
+  // Deferred and hibernated local terminals get their shell back once they
+  // are focused. The backend ignores terminals that are already running.
+  useEffect(() => {
+    let previousActiveId: string | null = null;
+    return useTerminalStore.subscribe((state) => {
+      const activeId = state.activeTerminalId;
+      if (!activeId || activeId === previousActiveId) return;
+      previousActiveId = activeId;
+      if (state.sessions[activeId]?.backendKind !== "local") return;
+      activateTerminal(activeId).catch(() => {});
+    });
+  }, []);
+
   useEffect(() => {
     if (resolvedActiveProjectId && resolvedActiveProjectId !== activeProjectId) {
       useProjectStore.getState().setActiveProject(resolvedActiveProjectId);
@@ -302,9 +462,15 @@ export default function App() {
 
 
   const createTerminalInProject = useCallback(
-    async (projectId: string, terminalName: string, sourceTerminalId?: string) => {
+    async (
+      projectId: string,
+      terminalName: string,
+      sourceTerminalId?: string,
+      sessionPatch?: Partial<TerminalSession>,
+      adoptedTerminalId?: string
+    ): Promise<string | undefined> => {
       const project = projects[projectId];
-      if (!project) return;
+      if (!project) return undefined;
 
       // Expand the project if it's minimized so the new terminal is visible
       if (!project.expanded) {
@@ -328,7 +494,7 @@ export default function App() {
         }
       }
 
-      const terminalId = generateId();
+      const terminalId = adoptedTerminalId ?? generateId();
       const nodeId = generateId();
 
       addNode({
@@ -340,13 +506,56 @@ export default function App() {
       });
       addChildToNode(project.rootGroupId, nodeId);
 
-      addSession(terminalId, terminalName, inheritedCwd);
+      addSession(terminalId, terminalName, inheritedCwd, sessionPatch);
       // Each tab terminal gets its own standalone layout
       initLayout(terminalId, terminalId);
+      return terminalId;
     },
     [projects, addNode, addChildToNode, addSession, initLayout, updateSessionCwd]
   );
 
+  // The backend reports shells no tab owns any more; offer to reopen them
+  // before they are closed on their own.
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    onTerminalOrphansDetected((detected) => {
+      setOrphans(detected);
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  const dropOrphan = useCallback((terminalId: string) => {
+    setOrphans((current) => current.filter((orphan) => orphan.terminal_id !== terminalId));
+  }, []);
+
+  const handleReopenOrphan = useCallback(
+    (terminalId: string) => {
+      dropOrphan(terminalId);
+      const { activeProjectId: projectId, projectOrder } = useProjectStore.getState();
+      const targetProjectId = projectId ?? projectOrder[0];
+      if (!targetProjectId) return;
+      adoptTerminalBackend(terminalId)
+        .then(() =>
+          createTerminalInProject(targetProjectId, "Recovered", undefined, undefined, terminalId)
+        )
+        .catch((err) => {
+          debugLog("app.orphans", "failed to reopen terminal", {
+            terminalId,
+            error: err instanceof Error ? err.message : String(err),
+          });
+        });
+    },
+    [createTerminalInProject, dropOrphan]
+  );
+
+  const handleCloseOrphan = useCallback(
+    (terminalId: string) => {
+      dropOrphan(terminalId);
+      closeTerminal(terminalId).catch(() => {});
+    },
+    [dropOrphan]
+  );
+
   const handleNewTerminal = useCallback(() => {
     const currentProjectId = useProjectStore.getState().activeProjectId;
     const currentProject = currentProjectId ? useProjectStore.getState().projects[currentProjectId] : null;
@@ -392,6 +601,13 @@ export default function App() {
     [createTerminalInProject]
   );
 
+  const handleNewIncognitoTerminalInProject = useCallback(
+    (projectId: string) => {
+      void createTerminalInProject(projectId, "Incognito", undefined, { isIncognito: true });
+    },
+    [createTerminalInProject]
+  );
+
   const handleMoveTerminal = useCallback(
     (
       terminalId: string,
@@ -505,19 +721,19 @@ export default function App() {
   );
 
   const handleSplitPane = useCallback(
-    (targetTerminalId: string, direction: "horizontal" | "vertical") => {
+    (targetTerminalId: string, direction: "horizontal" | "vertical"): Promise<string | null> => {
       if (isDisconnectedTmuxPlaceholderTerminal(targetTerminalId)) {
-        return;
+        return Promise.resolve(null);
       }
 
-      void splitTmuxTerminal(targetTerminalId, direction).then((handled) => {
+      return splitTmuxTerminal(targetTerminalId, direction).then((handled) => {
         if (handled) {
-          return;
+          return null;
         }
 
       const allLayouts = useLayoutStore.getState().layouts;
       const layoutKey = findLayoutKeyForTerminal(allLayouts, targetTerminalId);
-      if (!layoutKey) return;
+      if (!layoutKey) return null;
 
       const terminalId = generateId();
 
@@ -537,6 +753,7 @@ export default function App() {
           }
         })
         .catch(() => {});
+      return terminalId;
       });
     },
     [addSession, splitTerminal]
@@ -667,6 +884,75 @@ export default function App() {
     ]
   );
 
+  // Scripts inside a terminal (and the `Dispatcher` CLI) reach the app
+  // through the control socket; the backend forwards what only the renderer
+  // can do and waits for the answer.  The handler lives in a ref so the
+  // listener is registered once and never sees a request twice.
+  const controlRequestRef = useRef<(request: TerminalControlRequest) => Promise<string | null>>(
+    async () => null
+  );
+  controlRequestRef.current = async (request) => {
+    const { updateTitle, updateNotes } = useTerminalStore.getState();
+    switch (request.action) {
+      case "new-tab": {
+        const { projects: allProjects, activeProjectId } = useProjectStore.getState();
+        const wanted = request.project ?? activeProjectId;
+        const project = wanted
+          ? allProjects[wanted] ??
+            Object.values(allProjects).find((p) => p.name === wanted) ??
+            Object.values(allProjects).find((p) => p.name.toLowerCase() === wanted.toLowerCase())
+          : undefined;
+        if (!project) {
+          throw new Error(wanted ? `Project ${wanted} not found` : "No active project");
+        }
+        const terminalId = await createTerminalInProject(
+          project.id,
+          "Shell",
+          undefined,
+          request.cwd ? { cwd: request.cwd } : undefined
+        );
+        return terminalId ?? null;
+      }
+      case "split":
+        return handleSplitPane(request.terminal_id, request.direction);
+      case "rename": {
+        const handled = await renameTmuxTerminal(request.terminal_id, request.title).catch(
+          () => false
+        );
+        if (!handled) {
+          updateTitle(request.terminal_id, request.title);
+        }
+        return null;
+      }
+      case "append-notes": {
+        const notes = useTerminalStore.getState().sessions[request.terminal_id]?.notes ?? "";
+        const separator = notes && !notes.endsWith("\n") ? "\n" : "";
+        updateNotes(request.terminal_id, notes + separator + request.text);
+        return null;
+      }
+      case "close":
+        handleClosePane(request.terminal_id);
+        return null;
+    }
+  };
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+    onTerminalControlRequest((request) => {
+      controlRequestRef
+        .current(request)
+        .then((terminalId) => completeControlRequest(request.request_id, { terminalId }))
+        .catch((err) =>
+          completeControlRequest(request.request_id, {
+            error: err instanceof Error ? err.message : String(err),
+          })
+        )
+        .catch((err) => {
+          debugLog("app.controlRequest", "failed to answer control request", { error: String(err) });
+        });
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
   // Find the layout key (tab root terminal ID) for the currently active terminal.
   const layouts = useLayoutStore((s) => s.layouts);
   const activeTerminalId = useTerminalStore((s) => s.activeTerminalId);
@@ -756,6 +1042,10 @@ export default function App() {
       e.preventDefault();
       window.dispatchEvent(new CustomEvent("toggle-scheme-picker"));
     }
+    if (isMeta && e.shiftKey && e.key.toLowerCase() === "f") {
+      e.preventDefault();
+      window.dispatchEvent(new CustomEvent("toggle-terminal-search"));
+    }
     if (isMeta && e.key === "n") {
       e.preventDefault();
       handleNewProject();
@@ -934,6 +1224,7 @@ export default function App() {
       <Sidebar
         onNewTerminal={handleNewTerminal}
         onNewTerminalInProject={handleNewTerminalInProject}
+        onNewIncognitoTerminalInProject={handleNewIncognitoTerminalInProject}
         onNewProject={handleNewProject}
         onDeleteProject={handleDeleteProject}
         onDeleteTerminal={handleDeleteTerminal}
@@ -984,6 +1275,14 @@ export default function App() {
           onCancel={() => setDialog(null)}
         />
       )}
+      {orphans.length > 0 && (
+        <OrphanedTerminalsDialog
+          orphans={orphans}
+          onReopen={handleReopenOrphan}
+          onClose={handleCloseOrphan}
+          onDismiss={() => setOrphans([])}
+        />
+      )}
       {showKeyDebug && <KeyDebugOverlay />}
     </div>
   );
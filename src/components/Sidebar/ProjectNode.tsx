@@ -2,6 +2,7 @@ import { useState, useRef, useEffect, useCallback } from "react";
 import { useProjectStore } from "../../stores/useProjectStore";
 import { SidebarTreeNode } from "./SidebarTreeNode";
 import { ContextMenu } from "../common/ContextMenu";
+import { ProjectEnvDialog } from "../common/ProjectEnvDialog";
 import { shouldIgnoreDragStartTarget, startDrag } from "../../lib/dragState";
 import type { Project } from "../../types/project";
 
@@ -14,6 +15,7 @@ interface ProjectNodeProps {
   onDeleteProject: () => void;
   onDeleteTerminal: (terminalId: string) => void;
   onNewTerminal: () => void;
+  onNewIncognitoTerminal: () => void;
 }
 
 export function ProjectNode({
@@ -25,6 +27,7 @@ export function ProjectNode({
   onDeleteProject,
   onDeleteTerminal,
   onNewTerminal,
+  onNewIncognitoTerminal,
 }: ProjectNodeProps) {
   const nodes = useProjectStore((s) => s.nodes);
   const toggleExpanded = useProjectStore((s) => s.toggleProjectExpanded);
@@ -34,6 +37,7 @@ export function ProjectNode({
   const [editing, setEditing] = useState(false);
   const [draft, setDraft] = useState("");
   const [menu, setMenu] = useState<{ x: number; y: number } | null>(null);
+  const [showEnvironment, setShowEnvironment] = useState(false);
   const inputRef = useRef<HTMLInputElement>(null);
   const headerRef = useRef<HTMLDivElement>(null);
 
@@ -152,6 +156,25 @@ export function ProjectNode({
               ),
               onClick: onNewTerminal,
             },
+            {
+              label: "New Incognito Terminal",
+              icon: (
+                <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+                  <path d="M1.5 7C3 4.5 4.8 3.5 7 3.5C9.2 3.5 11 4.5 12.5 7C11 9.5 9.2 10.5 7 10.5C4.8 10.5 3 9.5 1.5 7Z" stroke="currentColor" strokeWidth="1.2" strokeLinejoin="round"/>
+                  <path d="M2.5 11.5L11.5 2.5" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+                </svg>
+              ),
+              onClick: onNewIncognitoTerminal,
+            },
+            {
+              label: "Environment...",
+              icon: (
+                <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+                  <path d="M2 3.5H12M2 7H12M2 10.5H8" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+                </svg>
+              ),
+              onClick: () => setShowEnvironment(true),
+            },
             {
               label: "Rename",
               icon: (
@@ -174,6 +197,13 @@ export function ProjectNode({
           ]}
         />
       )}
+      {showEnvironment && (
+        <ProjectEnvDialog
+          projectId={project.id}
+          projectName={project.name}
+          onClose={() => setShowEnvironment(false)}
+        />
+      )}
       {project.expanded && rootNode && rootNode.children && (
         <div
           className="sidebar-project-children"
@@ -7,6 +7,8 @@ import { ProjectNode } from "./ProjectNode";
 import { ContextMenu } from "../common/ContextMenu";
 import { HotkeyHelp } from "../common/HotkeyHelp";
 import { FontSettings } from "../common/FontSettings";
+import { TerminalSearchDialog } from "../common/TerminalSearchDialog";
+import { TerminalSettings } from "../common/TerminalSettings";
 import { SchemePicker } from "../common/SchemePicker";
 import { registerDragCallbacks } from "../../lib/dragState";
 import { handleTmuxTerminalFocus, resolvePreferredTerminalFocus } from "../../lib/tmuxControl";
@@ -14,6 +16,7 @@ import { handleTmuxTerminalFocus, resolvePreferredTerminalFocus } from "../../li
 interface SidebarProps {
   onNewTerminal: () => void;
   onNewTerminalInProject: (projectId: string) => void;
+  onNewIncognitoTerminalInProject: (projectId: string) => void;
   onNewProject: () => void;
   onDeleteProject: (projectId: string) => void;
   onDeleteTerminal: (terminalId: string, projectId: string) => void;
@@ -31,6 +34,7 @@ interface SidebarProps {
 export function Sidebar({
   onNewTerminal,
   onNewTerminalInProject,
+  onNewIncognitoTerminalInProject,
   onNewProject,
   onDeleteProject,
   onDeleteTerminal,
@@ -61,7 +65,9 @@ export function Sidebar({
   const [bgMenu, setBgMenu] = useState<{ x: number; y: number } | null>(null);
   const [showHelp, setShowHelp] = useState(false);
   const [showFontSettings, setShowFontSettings] = useState(false);
+  const [showTerminalSettings, setShowTerminalSettings] = useState(false);
   const [showSchemePicker, setShowSchemePicker] = useState(false);
+  const [showSearch, setShowSearch] = useState(false);
 
   // Listen for the global toggle-scheme-picker event (fired by keyboard shortcut)
   useEffect(() => {
@@ -70,6 +76,12 @@ export function Sidebar({
     return () => window.removeEventListener("toggle-scheme-picker", handler);
   }, []);
 
+  useEffect(() => {
+    const handler = () => setShowSearch((v) => !v);
+    window.addEventListener("toggle-terminal-search", handler);
+    return () => window.removeEventListener("toggle-terminal-search", handler);
+  }, []);
+
   const projectList = projectOrder.map((id) => projects[id]).filter(Boolean);
 
   const bgMenuItems = [
@@ -149,10 +161,17 @@ export function Sidebar({
             onDeleteProject={() => onDeleteProject(project.id)}
             onDeleteTerminal={(terminalId) => onDeleteTerminal(terminalId, project.id)}
             onNewTerminal={() => onNewTerminalInProject(project.id)}
+            onNewIncognitoTerminal={() => onNewIncognitoTerminalInProject(project.id)}
           />
         ))}
       </div>
       <div className="sidebar-footer">
+        <button className="sidebar-help-btn" onClick={() => setShowSearch(true)} title="Search All Terminals (⇧⌘F)">
+          <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+            <circle cx="6" cy="6" r="4" stroke="currentColor" strokeWidth="1.2"/>
+            <path d="M9 9L12.5 12.5" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+          </svg>
+        </button>
         <button className="sidebar-help-btn" onClick={() => setShowSchemePicker(true)} title="Color Scheme (⇧⌘T)">
           <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
             <circle cx="5" cy="5.5" r="2.5" stroke="currentColor" strokeWidth="1.2"/>
@@ -167,6 +186,12 @@ export function Sidebar({
             <path d="M7 1.5V3M7 11V12.5M1.5 7H3M11 7h1.5M2.87 3.17l1.06 1.06M9.77 10.07l1.06 1.06M11.13 3.17l-1.06 1.06M4.23 10.07l-1.06 1.06" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
           </svg>
         </button>
+        <button className="sidebar-help-btn" onClick={() => setShowTerminalSettings(true)} title="Terminal Settings">
+          <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+            <rect x="1.5" y="2.5" width="11" height="9" rx="1.5" stroke="currentColor" strokeWidth="1.2"/>
+            <path d="M4 5.5L6 7L4 8.5M7.5 8.5H10" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round" strokeLinejoin="round"/>
+          </svg>
+        </button>
         <button className="sidebar-help-btn" onClick={() => setShowHelp(true)} title="Keyboard Shortcuts">
           ?
         </button>
@@ -181,7 +206,9 @@ export function Sidebar({
       )}
       {showHelp && <HotkeyHelp onClose={() => setShowHelp(false)} />}
       {showFontSettings && <FontSettings onClose={() => setShowFontSettings(false)} />}
+      {showTerminalSettings && <TerminalSettings onClose={() => setShowTerminalSettings(false)} />}
       {showSchemePicker && <SchemePicker onClose={() => setShowSchemePicker(false)} />}
+      {showSearch && <TerminalSearchDialog onClose={() => setShowSearch(false)} />}
     </div>
   );
 }
@@ -6,6 +6,10 @@ import { useProjectStore } from "../../stores/useProjectStore";
 import { shouldIgnoreDragStartTarget, startDrag } from "../../lib/dragState";
 import { focusTerminalInstance } from "../../hooks/useTerminalBridge";
 import { renameTmuxTerminal } from "../../lib/tmuxControl";
+import { exportTerminalOutput } from "../../lib/tauriCommands";
+import { debugLogError } from "../../lib/debugLog";
+import { useColorSchemeStore } from "../../stores/useColorSchemeStore";
+import { writeText as writeClipboardText } from "@tauri-apps/plugin-clipboard-manager";
 
 interface TerminalNodeProps {
   terminalId: string;
@@ -83,7 +87,7 @@ export function TerminalNode({ terminalId, projectId, nodeId, parentNodeId, isAc
   return (
     <div
       ref={nodeRef}
-      className={`sidebar-terminal-node ${isActive ? "active" : ""}`}
+      className={`sidebar-terminal-node ${isActive ? "active" : ""} ${session.isHibernated ? "hibernated" : ""}`}
       data-node-id={nodeId}
       data-project-id={projectId}
       data-parent-node-id={parentNodeId}
@@ -113,6 +117,7 @@ export function TerminalNode({ terminalId, projectId, nodeId, parentNodeId, isAc
       ) : (
         <span
           className="terminal-node-title"
+          title={session.isHibernated ? "Hibernated: the shell restarts when you open this tab" : undefined}
           onDoubleClick={(e) => {
             e.stopPropagation();
             startRename();
@@ -169,6 +174,24 @@ export function TerminalNode({ terminalId, projectId, nodeId, parentNodeId, isAc
               shortcut: "⌘L",
               onClick: startRename,
             },
+            ...(session.backendKind === "local"
+              ? [{
+                  label: "Export Output",
+                  icon: (
+                    <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+                      <path d="M7 2V9M4 6L7 9L10 6M2.5 11.5H11.5" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round" strokeLinejoin="round"/>
+                    </svg>
+                  ),
+                  // Saves a self-contained HTML file in the app data dir and
+                  // copies its path so it can be shared.
+                  onClick: () => {
+                    const theme = useColorSchemeStore.getState().getActiveScheme().terminal;
+                    exportTerminalOutput(terminalId, "html", "all", { theme })
+                      .then((path) => writeClipboardText(path))
+                      .catch((err) => debugLogError("terminal.export", "export failed", err));
+                  },
+                }]
+              : []),
             {
               label: "Delete",
               icon: (
@@ -0,0 +1,163 @@
+import { useEffect, useState } from "react";
+import { getAutoResponders, setAutoResponders } from "../../lib/tauriCommands";
+import type { AutoResponder, AutoResponderStatus } from "../../lib/tauriCommands";
+
+interface AutoResponderSectionProps {
+  terminalId: string;
+}
+
+interface ResponderDraft {
+  rule: AutoResponder;
+  fireCount: number;
+}
+
+function toDraft(status: AutoResponderStatus): ResponderDraft {
+  const { fire_count, ...rule } = status;
+  return { rule, fireCount: fire_count };
+}
+
+/**
+ * Rules that answer prompts in this terminal. Fire counts come from the
+ * backend and survive edits of a rule that keeps its id.
+ */
+export function AutoResponderSection({ terminalId }: AutoResponderSectionProps) {
+  const [drafts, setDrafts] = useState<ResponderDraft[]>([]);
+  const [isDirty, setIsDirty] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const load = () => {
+    getAutoResponders(terminalId)
+      .then((statuses) => {
+        setDrafts(statuses.map(toDraft));
+        setIsDirty(false);
+        setError(null);
+      })
+      .catch(() => {
+        // Deferred terminals have no shell yet; nothing to show.
+        setDrafts([]);
+      });
+  };
+
+  useEffect(load, [terminalId]);
+
+  const updateRule = (id: string, changes: Partial<AutoResponder>) => {
+    setDrafts((current) =>
+      current.map((draft) =>
+        draft.rule.id === id ? { ...draft, rule: { ...draft.rule, ...changes } } : draft
+      )
+    );
+    setIsDirty(true);
+  };
+
+  const addRule = () => {
+    setDrafts((current) => [
+      ...current,
+      { rule: { id: crypto.randomUUID(), pattern: "", response: "" }, fireCount: 0 },
+    ]);
+    setIsDirty(true);
+  };
+
+  const removeRule = (id: string) => {
+    setDrafts((current) => current.filter((draft) => draft.rule.id !== id));
+    setIsDirty(true);
+  };
+
+  const save = () => {
+    const rules = drafts
+      .map((draft) => draft.rule)
+      .filter((rule) => rule.pattern !== "" && rule.response !== "");
+    setAutoResponders(terminalId, rules)
+      .then(load)
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div
+      className="detail-panel-section"
+      onKeyDown={(e) => {
+        // Same as the notes field: keep typing out of the global hotkeys
+        if ((e.metaKey || e.ctrlKey) && e.shiftKey && (e.code === "BracketLeft" || e.code === "BracketRight")) {
+          return;
+        }
+        e.stopPropagation();
+      }}
+    >
+      <label className="detail-panel-label">Auto-responders</label>
+      {drafts.map(({ rule, fireCount }) => {
+        const pressesEnter = rule.response.endsWith("\r");
+        const text = pressesEnter ? rule.response.slice(0, -1) : rule.response;
+        return (
+          <div key={rule.id} className="detail-panel-responder">
+            <div className="detail-panel-responder-row">
+              <input
+                className="detail-panel-input detail-panel-input-mono"
+                value={rule.pattern}
+                placeholder="Prompt pattern (regex)"
+                onChange={(e) => updateRule(rule.id, { pattern: e.target.value })}
+              />
+              <button
+                className="hotkey-help-close"
+                onClick={() => removeRule(rule.id)}
+                title="Remove auto-responder"
+              >
+                <svg width="12" height="12" viewBox="0 0 12 12" fill="none">
+                  <path d="M3 3L9 9M9 3L3 9" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+                </svg>
+              </button>
+            </div>
+            <div className="detail-panel-responder-row">
+              <input
+                className="detail-panel-input detail-panel-input-mono"
+                value={text}
+                placeholder="Answer"
+                onChange={(e) =>
+                  updateRule(rule.id, { response: e.target.value + (pressesEnter ? "\r" : "") })
+                }
+              />
+              <label className="terminal-settings-check">
+                <input
+                  type="checkbox"
+                  checked={pressesEnter}
+                  onChange={(e) =>
+                    updateRule(rule.id, { response: text + (e.target.checked ? "\r" : "") })
+                  }
+                />
+                <span>Enter</span>
+              </label>
+            </div>
+            <div className="detail-panel-responder-row">
+              <label className="terminal-settings-check">
+                <span>At most</span>
+                <input
+                  type="number"
+                  className="detail-panel-input detail-panel-input-number"
+                  value={rule.maxFires ?? ""}
+                  min={1}
+                  placeholder="∞"
+                  onChange={(e) =>
+                    updateRule(rule.id, {
+                      maxFires: e.target.value === "" ? null : Math.max(1, Number(e.target.value)),
+                    })
+                  }
+                />
+                <span>times</span>
+              </label>
+              <span className="detail-panel-responder-count">
+                answered {fireCount} {fireCount === 1 ? "time" : "times"}
+              </span>
+            </div>
+          </div>
+        );
+      })}
+      {error && <div className="terminal-settings-error">{error}</div>}
+      <div className="detail-panel-responder-actions">
+        <button className="dialog-btn dialog-btn-cancel" onClick={addRule}>
+          Add
+        </button>
+        <button className="dialog-btn dialog-btn-confirm" disabled={!isDirty} onClick={save}>
+          Save
+        </button>
+      </div>
+    </div>
+  );
+}
@@ -2,6 +2,8 @@ import { StatusDot } from "../common/StatusDot";
 import { EditableText } from "../common/EditableText";
 import { useTerminalStore } from "../../stores/useTerminalStore";
 import { useFontStore } from "../../stores/useFontStore";
+import { AutoResponderSection } from "./AutoResponderSection";
+import { EncodingSection } from "./EncodingSection";
 import { isDisconnectedTmuxPlaceholderTerminal, renameTmuxTerminal } from "../../lib/tmuxControl";
 
 interface DetailPanelProps {
@@ -99,6 +101,12 @@ export function DetailPanel({
           }}
         />
       </div>
+      {session.backendKind === "local" && (
+        <>
+          <AutoResponderSection terminalId={terminalId} />
+          <EncodingSection terminalId={terminalId} />
+        </>
+      )}
     </div>
   );
 }
@@ -0,0 +1,80 @@
+import { useEffect, useState } from "react";
+import { getTerminalEncoding, setTerminalEncoding } from "../../lib/tauriCommands";
+
+interface EncodingSectionProps {
+  terminalId: string;
+}
+
+/** Names as the backend reports them, so the current choice matches. */
+const ENCODINGS: Array<{ name: string; label: string }> = [
+  { name: "UTF-8", label: "UTF-8" },
+  { name: "windows-1252", label: "Western (Latin-1)" },
+  { name: "ISO-8859-15", label: "Western (Latin-9)" },
+  { name: "windows-1251", label: "Cyrillic (Windows)" },
+  { name: "KOI8-R", label: "Cyrillic (KOI8-R)" },
+  { name: "Shift_JIS", label: "Japanese (Shift_JIS)" },
+  { name: "EUC-JP", label: "Japanese (EUC-JP)" },
+  { name: "ISO-2022-JP", label: "Japanese (ISO-2022-JP)" },
+  { name: "gb18030", label: "Chinese (GB18030)" },
+  { name: "GBK", label: "Chinese (GBK)" },
+  { name: "Big5", label: "Chinese (Big5)" },
+  { name: "EUC-KR", label: "Korean (EUC-KR)" },
+];
+
+/**
+ * The encoding this terminal's shell reads and writes. Takes effect for
+ * the next output and input; the choice is kept across restores.
+ */
+export function EncodingSection({ terminalId }: EncodingSectionProps) {
+  const [encoding, setEncoding] = useState<string | null>(null);
+  const [isLoaded, setIsLoaded] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    setIsLoaded(false);
+    getTerminalEncoding(terminalId)
+      .then((name) => {
+        setEncoding(name);
+        setIsLoaded(true);
+        setError(null);
+      })
+      .catch(() => {
+        // Deferred terminals have no shell yet; nothing to show.
+        setIsLoaded(false);
+      });
+  }, [terminalId]);
+
+  if (!isLoaded) return null;
+
+  const current = encoding ?? "UTF-8";
+  const options = ENCODINGS.some((option) => option.name === current)
+    ? ENCODINGS
+    : [...ENCODINGS, { name: current, label: current }];
+
+  const change = (name: string) => {
+    setTerminalEncoding(terminalId, name)
+      .then(() => {
+        setEncoding(name);
+        setError(null);
+      })
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div className="detail-panel-section">
+      <label className="detail-panel-label">Encoding</label>
+      <select
+        className="detail-panel-input"
+        value={current}
+        onChange={(e) => change(e.target.value)}
+      >
+        {options.map((option) => (
+          <option key={option.name} value={option.name}>
+            {option.label}
+          </option>
+        ))}
+      </select>
+      {error && <div className="terminal-settings-error">{error}</div>}
+    </div>
+  );
+}
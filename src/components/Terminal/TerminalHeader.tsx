@@ -4,6 +4,8 @@ import { EditableText } from "../common/EditableText";
 import { useTerminalStore } from "../../stores/useTerminalStore";
 import { useUiStore } from "../../stores/useUiStore";
 import { renameTmuxTerminal } from "../../lib/tmuxControl";
+import { restartTerminalSafeMode } from "../../lib/tauriCommands";
+import { debugLogError } from "../../lib/debugLog";
 
 interface TerminalHeaderProps {
   terminalId: string;
@@ -104,6 +106,19 @@ export function TerminalHeader({
         )}
       </div>
       <div className="terminal-header-actions">
+        {session.isStartupStalled && (
+          <button
+            className="terminal-safe-mode"
+            onClick={() => {
+              restartTerminalSafeMode(terminalId).catch((err) => {
+                debugLogError("terminalHeader.safeMode", "restart failed", err);
+              });
+            }}
+            title="The shell did not start or show a prompt: start it without your startup files"
+          >
+            Safe mode
+          </button>
+        )}
         <button onClick={onSplitHorizontal} title="Split Right (⌘D)">
           <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
             <rect x="1" y="1" width="12" height="12" rx="2" stroke="currentColor" strokeWidth="1.2"/>
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback, useRef } from "react";
 import {
+  pushTerminalKeyDebug,
   sendSyntheticTerminalInput,
   suppressTransientFocusSequences,
   type TerminalPasteProgress,
@@ -164,7 +165,7 @@ export function TerminalPane({
     if (!el) return;
 
     const handleKeyDown = (e: KeyboardEvent) => {
-      pushKeyDebug(`terminal.capture:${terminalId}`, describeKeyboardEvent(e));
+      pushTerminalKeyDebug(`terminal.capture:${terminalId}`, terminalId, () => describeKeyboardEvent(e));
 
       // Use Cmd on macOS, Ctrl on other platforms for search
       const isMac = navigator.platform.startsWith("Mac");
@@ -190,10 +191,10 @@ export function TerminalPane({
       if (isMac) {
         const deleteSequence = getMacDeleteSequence(e);
         if (deleteSequence) {
-          pushKeyDebug(`terminal.delete-sequence:${terminalId}`, {
+          pushTerminalKeyDebug(`terminal.delete-sequence:${terminalId}`, terminalId, () => ({
             event: describeKeyboardEvent(e),
             data: describeTerminalData(deleteSequence),
-          });
+          }));
           if (e.repeat) {
             e.preventDefault();
             e.stopPropagation();
@@ -213,10 +214,10 @@ export function TerminalPane({
 
         const controlChar = getCtrlLetterControlCharacter(e);
         if (controlChar) {
-          pushKeyDebug(`terminal.ctrl-sequence:${terminalId}`, {
+          pushTerminalKeyDebug(`terminal.ctrl-sequence:${terminalId}`, terminalId, () => ({
             event: describeKeyboardEvent(e),
             data: describeTerminalData(controlChar),
-          });
+          }));
           if (e.repeat) {
             e.preventDefault();
             e.stopPropagation();
@@ -239,10 +240,10 @@ export function TerminalPane({
 
         const metaSequence = getMacOptionMetaSequence(e);
         if (metaSequence) {
-          pushKeyDebug(`terminal.meta-sequence:${terminalId}`, {
+          pushTerminalKeyDebug(`terminal.meta-sequence:${terminalId}`, terminalId, () => ({
             event: describeKeyboardEvent(e),
             data: describeTerminalData(metaSequence),
-          });
+          }));
           if (e.repeat) {
             e.preventDefault();
             e.stopPropagation();
@@ -263,11 +264,11 @@ export function TerminalPane({
     };
 
     const handleKeyUp = (e: KeyboardEvent) => {
-      pushKeyDebug(`terminal.keyup:${terminalId}`, describeKeyboardEvent(e));
+      pushTerminalKeyDebug(`terminal.keyup:${terminalId}`, terminalId, () => describeKeyboardEvent(e));
     };
 
     const handleInputLikeEvent = (e: Event) => {
-      pushKeyDebug(`terminal.${e.type}:${terminalId}`, describeInputLikeEvent(e));
+      pushTerminalKeyDebug(`terminal.${e.type}:${terminalId}`, terminalId, () => describeInputLikeEvent(e));
     };
 
     el.addEventListener("keydown", handleKeyDown, true);
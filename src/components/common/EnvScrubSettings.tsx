@@ -0,0 +1,185 @@
+import { useEffect, useState } from "react";
+import { getEnvScrubRules, previewSpawnEnvironment, setEnvScrubRules } from "../../lib/tauriCommands";
+import type { EnvScrubRules, SpawnEnvironmentPreview } from "../../lib/tauriCommands";
+import { useProjectStore } from "../../stores/useProjectStore";
+
+function toLines(items: string[]): string {
+  return items.join("\n");
+}
+
+function fromLines(text: string): string[] {
+  return text
+    .split("\n")
+    .map((line) => line.trim())
+    .filter((line) => line !== "");
+}
+
+/**
+ * Edit which inherited variables new shells drop, and preview what a shell
+ * in a given project would actually get.
+ */
+export function EnvScrubSettings() {
+  const projects = useProjectStore((s) => s.projects);
+  const projectOrder = useProjectStore((s) => s.projectOrder);
+  const [rules, setRules] = useState<EnvScrubRules | null>(null);
+  const [isDirty, setIsDirty] = useState(false);
+  const [previewProjectId, setPreviewProjectId] = useState("");
+  const [preview, setPreview] = useState<SpawnEnvironmentPreview | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    getEnvScrubRules()
+      .then(setRules)
+      .catch((err) => setError(String(err)));
+  }, []);
+
+  if (!rules) {
+    return error ? <div className="terminal-settings-error">{error}</div> : null;
+  }
+
+  const updateRules = (patch: Partial<EnvScrubRules>) => {
+    setRules({ ...rules, ...patch });
+    setIsDirty(true);
+  };
+
+  const updateValueRule = (index: number, names: string, pattern: string) => {
+    updateRules({
+      valueRules: rules.valueRules.map((rule, i) =>
+        i === index
+          ? {
+              names: names.split(",").map((name) => name.trim()).filter((name) => name !== ""),
+              pattern,
+            }
+          : rule
+      ),
+    });
+  };
+
+  const save = () => {
+    const next = {
+      ...rules,
+      valueRules: rules.valueRules.filter((rule) => rule.pattern.trim() !== ""),
+    };
+    setEnvScrubRules(next)
+      .then(() => {
+        setRules(next);
+        setIsDirty(false);
+        setError(null);
+      })
+      .catch((err) => setError(String(err)));
+  };
+
+  const runPreview = () => {
+    const project = previewProjectId ? projects[previewProjectId] : undefined;
+    previewSpawnEnvironment(project?.id ?? null, project?.cwd ?? null)
+      .then((result) => {
+        setPreview(result);
+        setError(null);
+      })
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div className="terminal-settings-section">
+      <div className="terminal-settings-section-title">Environment</div>
+      <label className="terminal-settings-hint">Remove variables named (one per line)</label>
+      <textarea
+        className="dialog-input terminal-settings-textarea"
+        value={toLines(rules.exactNames)}
+        onChange={(e) => updateRules({ exactNames: fromLines(e.target.value) })}
+      />
+      <label className="terminal-settings-hint">Remove variables starting with</label>
+      <textarea
+        className="dialog-input terminal-settings-textarea"
+        value={toLines(rules.prefixes)}
+        onChange={(e) => updateRules({ prefixes: fromLines(e.target.value) })}
+      />
+      <label className="terminal-settings-hint">Always keep</label>
+      <textarea
+        className="dialog-input terminal-settings-textarea"
+        value={toLines(rules.allow)}
+        onChange={(e) => updateRules({ allow: fromLines(e.target.value) })}
+      />
+      <label className="terminal-settings-hint">Remove variables whose value matches</label>
+      {rules.valueRules.map((rule, index) => (
+        <div key={index} className="terminal-settings-rule-row">
+          <input
+            className="dialog-input terminal-settings-input"
+            value={(rule.names ?? []).join(", ")}
+            placeholder="Any variable"
+            onChange={(e) => updateValueRule(index, e.target.value, rule.pattern)}
+          />
+          <input
+            className="dialog-input terminal-settings-input terminal-settings-input-wide"
+            value={rule.pattern}
+            placeholder="Pattern (regex)"
+            onChange={(e) => updateValueRule(index, (rule.names ?? []).join(", "), e.target.value)}
+          />
+          <button
+            className="hotkey-help-close"
+            onClick={() =>
+              updateRules({ valueRules: rules.valueRules.filter((_, i) => i !== index) })
+            }
+            title="Remove rule"
+          >
+            <svg width="12" height="12" viewBox="0 0 12 12" fill="none">
+              <path d="M3 3L9 9M9 3L3 9" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+            </svg>
+          </button>
+        </div>
+      ))}
+      <div className="dialog-actions">
+        <button
+          className="dialog-btn dialog-btn-cancel"
+          onClick={() => updateRules({ valueRules: [...rules.valueRules, { names: [], pattern: "" }] })}
+        >
+          Add value rule
+        </button>
+        <button className="dialog-btn dialog-btn-confirm" disabled={!isDirty} onClick={save}>
+          Save
+        </button>
+      </div>
+
+      <div className="terminal-settings-rule-row">
+        <select
+          className="terminal-settings-select"
+          value={previewProjectId}
+          onChange={(e) => setPreviewProjectId(e.target.value)}
+        >
+          <option value="">No project</option>
+          {projectOrder
+            .filter((id) => projects[id])
+            .map((id) => (
+              <option key={id} value={id}>
+                {projects[id].name}
+              </option>
+            ))}
+        </select>
+        <button className="dialog-btn dialog-btn-cancel" onClick={runPreview}>
+          Preview new shell
+        </button>
+      </div>
+      {error && <div className="terminal-settings-error">{error}</div>}
+      {preview && (
+        <div className="terminal-settings-env-preview">
+          <div className="terminal-settings-hint">
+            {preview.shell} · {preview.inherited.length} kept · {preview.removed.length} removed
+            {preview.project_rules_path && ` · project rules from ${preview.project_rules_path}`}
+          </div>
+          {preview.removed.map((variable) => (
+            <div key={`removed:${variable.name}`} className="terminal-settings-env-var removed">
+              <span className="terminal-settings-env-name">{variable.name}</span>
+              <span className="terminal-settings-env-source">{variable.reason}</span>
+            </div>
+          ))}
+          {preview.inherited.map((variable) => (
+            <div key={variable.name} className="terminal-settings-env-var" title={variable.value}>
+              <span className="terminal-settings-env-name">{variable.name}</span>
+              <span className="terminal-settings-env-source">{variable.source}</span>
+            </div>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -18,6 +18,7 @@ const shortcuts = [
   { keys: "⇧⌘]", description: "Next terminal" },
   { keys: "⇧⌘[", description: "Previous terminal" },
   { keys: "⌘F", description: "Search in terminal" },
+  { keys: "⇧⌘F", description: "Search all terminals" },
   { keys: "⌘K", description: "Clear terminal" },
   { keys: "⌘+", description: "Increase font size" },
   { keys: "⌘\u2212", description: "Decrease font size" },
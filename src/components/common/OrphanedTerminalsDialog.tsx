@@ -0,0 +1,74 @@
+import { useEffect } from "react";
+import type { OrphanedTerminal } from "../../lib/tauriCommands";
+
+interface OrphanedTerminalsDialogProps {
+  orphans: OrphanedTerminal[];
+  onReopen: (terminalId: string) => void;
+  onClose: (terminalId: string) => void;
+  onDismiss: () => void;
+}
+
+function formatMinutes(ms: number): string {
+  const minutes = Math.max(1, Math.round(ms / 60000));
+  return minutes === 1 ? "1 minute" : `${minutes} minutes`;
+}
+
+/**
+ * Shells the backend still runs although no tab owns them, e.g. after a
+ * renderer crash. Each can be reopened as a tab or closed; dismissed ones
+ * close on their own once their timeout runs out.
+ */
+export function OrphanedTerminalsDialog({
+  orphans,
+  onReopen,
+  onClose,
+  onDismiss,
+}: OrphanedTerminalsDialogProps) {
+  useEffect(() => {
+    const handler = (e: KeyboardEvent) => {
+      if (e.key === "Escape") onDismiss();
+    };
+    window.addEventListener("keydown", handler);
+    return () => window.removeEventListener("keydown", handler);
+  }, [onDismiss]);
+
+  return (
+    <div className="dialog-overlay" onClick={onDismiss}>
+      <div className="dialog orphaned-terminals-dialog" onClick={(e) => e.stopPropagation()}>
+        <div className="dialog-title">Terminals without a tab</div>
+        <div className="orphaned-terminals-hint">
+          These shells are still running but no tab shows them.
+        </div>
+        <div className="orphaned-terminals-list">
+          {orphans.map((orphan) => (
+            <div key={orphan.terminal_id} className="orphaned-terminals-row">
+              <div className="orphaned-terminals-info">
+                <span className="orphaned-terminals-id">{orphan.terminal_id.slice(0, 8)}</span>
+                <span className="orphaned-terminals-expiry">
+                  closes in {formatMinutes(orphan.closes_in_ms)}
+                </span>
+              </div>
+              <button
+                className="dialog-btn dialog-btn-cancel"
+                onClick={() => onClose(orphan.terminal_id)}
+              >
+                Close
+              </button>
+              <button
+                className="dialog-btn dialog-btn-confirm"
+                onClick={() => onReopen(orphan.terminal_id)}
+              >
+                Reopen
+              </button>
+            </div>
+          ))}
+        </div>
+        <div className="dialog-actions">
+          <button className="dialog-btn dialog-btn-cancel" onClick={onDismiss}>
+            Later
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,125 @@
+import { useState } from "react";
+import { setPatternWatchers } from "../../lib/tauriCommands";
+import type { PatternWatcher } from "../../lib/tauriCommands";
+import { usePatternWatcherStore } from "../../stores/usePatternWatcherStore";
+import { useProjectStore } from "../../stores/useProjectStore";
+
+/**
+ * Edit the output watchers. Changes are checked by the backend (patterns
+ * are Rust regexes) before they replace the saved list.
+ */
+export function PatternWatcherSettings() {
+  const watchers = usePatternWatcherStore((s) => s.watchers);
+  const setWatchers = usePatternWatcherStore((s) => s.setWatchers);
+  const projects = useProjectStore((s) => s.projects);
+  const projectOrder = useProjectStore((s) => s.projectOrder);
+  const [draft, setDraft] = useState<PatternWatcher[]>(watchers);
+  const [error, setError] = useState<string | null>(null);
+  const isDirty = draft !== watchers;
+
+  const updateDraft = (id: string, changes: Partial<PatternWatcher>) => {
+    setDraft((current) =>
+      current.map((watcher) => (watcher.id === id ? { ...watcher, ...changes } : watcher))
+    );
+  };
+
+  const addDraft = () => {
+    setDraft((current) => [
+      ...current,
+      { id: crypto.randomUUID(), name: "", pattern: "", notify: true, needsAttention: true },
+    ]);
+  };
+
+  const save = () => {
+    const next = draft.filter((watcher) => watcher.pattern.trim() !== "");
+    setPatternWatchers(next)
+      .then(() => {
+        setWatchers(next);
+        setDraft(next);
+        setError(null);
+      })
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div className="terminal-settings-section">
+      <div className="terminal-settings-section-title">Output watchers</div>
+      {draft.map((watcher) => (
+        <div key={watcher.id} className="terminal-settings-rule">
+          <div className="terminal-settings-rule-row">
+            <input
+              className="dialog-input terminal-settings-input"
+              value={watcher.name}
+              placeholder="Name"
+              onChange={(e) => updateDraft(watcher.id, { name: e.target.value })}
+            />
+            <input
+              className="dialog-input terminal-settings-input terminal-settings-input-wide"
+              value={watcher.pattern}
+              placeholder="Pattern (regex)"
+              onChange={(e) => updateDraft(watcher.id, { pattern: e.target.value })}
+            />
+            <button
+              className="hotkey-help-close"
+              onClick={() => setDraft((current) => current.filter((w) => w.id !== watcher.id))}
+              title="Remove watcher"
+            >
+              <svg width="12" height="12" viewBox="0 0 12 12" fill="none">
+                <path d="M3 3L9 9M9 3L3 9" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+              </svg>
+            </button>
+          </div>
+          <div className="terminal-settings-rule-row">
+            <select
+              className="terminal-settings-select"
+              value={watcher.projectId ?? ""}
+              onChange={(e) => updateDraft(watcher.id, { projectId: e.target.value || null })}
+            >
+              <option value="">All projects</option>
+              {projectOrder
+                .filter((id) => projects[id])
+                .map((id) => (
+                  <option key={id} value={id}>
+                    {projects[id].name}
+                  </option>
+                ))}
+            </select>
+            <label className="terminal-settings-check">
+              <input
+                type="checkbox"
+                checked={watcher.caseSensitive ?? false}
+                onChange={(e) => updateDraft(watcher.id, { caseSensitive: e.target.checked })}
+              />
+              <span>Match case</span>
+            </label>
+            <label className="terminal-settings-check">
+              <input
+                type="checkbox"
+                checked={watcher.notify ?? false}
+                onChange={(e) => updateDraft(watcher.id, { notify: e.target.checked })}
+              />
+              <span>Notify</span>
+            </label>
+            <label className="terminal-settings-check">
+              <input
+                type="checkbox"
+                checked={watcher.needsAttention ?? false}
+                onChange={(e) => updateDraft(watcher.id, { needsAttention: e.target.checked })}
+              />
+              <span>Flag tab</span>
+            </label>
+          </div>
+        </div>
+      ))}
+      {error && <div className="terminal-settings-error">{error}</div>}
+      <div className="dialog-actions">
+        <button className="dialog-btn dialog-btn-cancel" onClick={addDraft}>
+          Add watcher
+        </button>
+        <button className="dialog-btn dialog-btn-confirm" disabled={!isDirty} onClick={save}>
+          Save
+        </button>
+      </div>
+    </div>
+  );
+}
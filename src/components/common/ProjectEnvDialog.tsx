@@ -0,0 +1,164 @@
+import { useEffect, useState } from "react";
+import { getProjectEnvConfig, setProjectEnvConfig } from "../../lib/tauriCommands";
+import type { ProjectEnvConfig } from "../../lib/tauriCommands";
+
+interface ProjectEnvDialogProps {
+  projectId: string;
+  projectName: string;
+  onClose: () => void;
+}
+
+function formatEnv(env: Record<string, string>): string {
+  return Object.entries(env)
+    .map(([name, value]) => `${name}=${value}`)
+    .join("\n");
+}
+
+function parseEnv(text: string): Record<string, string> {
+  const env: Record<string, string> = {};
+  for (const line of text.split("\n")) {
+    const separator = line.indexOf("=");
+    if (separator <= 0) continue;
+    env[line.slice(0, separator).trim()] = line.slice(separator + 1);
+  }
+  return env;
+}
+
+/**
+ * The environment new shells in a project start with. Env files are read
+ * only once listed here, so opening a cloned repository runs nothing.
+ */
+export function ProjectEnvDialog({ projectId, projectName, onClose }: ProjectEnvDialogProps) {
+  const [config, setConfig] = useState<ProjectEnvConfig | null>(null);
+  const [envFiles, setEnvFiles] = useState("");
+  const [env, setEnv] = useState("");
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    getProjectEnvConfig(projectId)
+      .then((loaded) => {
+        setConfig(loaded);
+        setEnvFiles(loaded.envFiles.join("\n"));
+        setEnv(formatEnv(loaded.env));
+      })
+      .catch((err) => setError(String(err)));
+  }, [projectId]);
+
+  // Escape to close
+  useEffect(() => {
+    const handler = (e: KeyboardEvent) => {
+      if (e.key === "Escape") onClose();
+    };
+    window.addEventListener("keydown", handler);
+    return () => window.removeEventListener("keydown", handler);
+  }, [onClose]);
+
+  const activationKind = config?.activation?.kind ?? "";
+
+  const save = () => {
+    if (!config) return;
+    const next: ProjectEnvConfig = {
+      ...config,
+      envFiles: envFiles
+        .split("\n")
+        .map((file) => file.trim())
+        .filter((file) => file !== ""),
+      env: parseEnv(env),
+    };
+    setProjectEnvConfig(projectId, next)
+      .then(onClose)
+      .catch((err) => setError(String(err)));
+  };
+
+  return (
+    <div className="dialog-overlay" onClick={onClose}>
+      <div className="dialog terminal-settings-dialog" onClick={(e) => e.stopPropagation()}>
+        <div className="dialog-title">{projectName} environment</div>
+        {config && (
+          <>
+            <div className="terminal-settings-section">
+              <div className="terminal-settings-section-title">Env files</div>
+              <textarea
+                className="dialog-input terminal-settings-textarea"
+                value={envFiles}
+                placeholder=".env"
+                onChange={(e) => setEnvFiles(e.target.value)}
+              />
+              <div className="terminal-settings-hint">
+                Relative to the project folder, read in order. Only list files you trust: loader
+                and shell startup variables (LD_*, BASH_ENV, PROMPT_COMMAND, ...) in them are
+                ignored, everything else is applied.
+              </div>
+            </div>
+
+            <div className="terminal-settings-section">
+              <div className="terminal-settings-section-title">Variables</div>
+              <textarea
+                className="dialog-input terminal-settings-textarea"
+                value={env}
+                placeholder="NAME=value"
+                onChange={(e) => setEnv(e.target.value)}
+              />
+              <div className="terminal-settings-hint">
+                Applied after the files; values may use $NAME and ${"{NAME:-default}"}.
+              </div>
+            </div>
+
+            <div className="terminal-settings-section">
+              <div className="terminal-settings-section-title">Activation</div>
+              <div className="terminal-settings-rule-row">
+                <select
+                  className="terminal-settings-select"
+                  value={activationKind}
+                  onChange={(e) => {
+                    const kind = e.target.value;
+                    setConfig({
+                      ...config,
+                      activation:
+                        kind === "direnv"
+                          ? { kind: "direnv" }
+                          : kind === "command"
+                            ? { kind: "command", command: "" }
+                            : null,
+                    });
+                  }}
+                >
+                  <option value="">None</option>
+                  <option value="direnv">direnv</option>
+                  <option value="command">Command</option>
+                </select>
+                {config.activation?.kind === "command" && (
+                  <input
+                    className="dialog-input terminal-settings-input terminal-settings-input-wide"
+                    value={config.activation.command}
+                    placeholder="nix print-dev-env --json"
+                    onChange={(e) =>
+                      setConfig({ ...config, activation: { kind: "command", command: e.target.value } })
+                    }
+                  />
+                )}
+              </div>
+              <label className="terminal-settings-check">
+                <input
+                  type="checkbox"
+                  checked={config.projectHistory ?? false}
+                  onChange={(e) => setConfig({ ...config, projectHistory: e.target.checked })}
+                />
+                <span>Keep a separate shell history for this project</span>
+              </label>
+            </div>
+          </>
+        )}
+        {error && <div className="terminal-settings-error">{error}</div>}
+        <div className="dialog-actions">
+          <button className="dialog-btn dialog-btn-cancel" onClick={onClose}>
+            Cancel
+          </button>
+          <button className="dialog-btn dialog-btn-confirm" disabled={!config} onClick={save}>
+            Save
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
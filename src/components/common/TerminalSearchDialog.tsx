@@ -0,0 +1,135 @@
+import { useEffect, useRef, useState } from "react";
+import { searchTerminals } from "../../lib/tauriCommands";
+import type { TerminalSearchMatch } from "../../lib/tauriCommands";
+import { handleTmuxTerminalFocus, resolvePreferredTerminalFocus } from "../../lib/tmuxControl";
+import { useTerminalStore } from "../../stores/useTerminalStore";
+
+interface TerminalSearchDialogProps {
+  onClose: () => void;
+}
+
+const SEARCH_DEBOUNCE_MS = 250;
+
+/**
+ * Search the output of every local terminal, including tabs that are not
+ * mounted or are hibernated. Clicking a match switches to its tab.
+ */
+export function TerminalSearchDialog({ onClose }: TerminalSearchDialogProps) {
+  const [query, setQuery] = useState("");
+  const [regex, setRegex] = useState(false);
+  const [caseSensitive, setCaseSensitive] = useState(false);
+  const [matches, setMatches] = useState<TerminalSearchMatch[]>([]);
+  const [searching, setSearching] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const sessions = useTerminalStore((s) => s.sessions);
+  const searchIdRef = useRef(0);
+
+  // Escape to close
+  useEffect(() => {
+    const handler = (e: KeyboardEvent) => {
+      if (e.key === "Escape") onClose();
+    };
+    window.addEventListener("keydown", handler);
+    return () => window.removeEventListener("keydown", handler);
+  }, [onClose]);
+
+  useEffect(() => {
+    const searchId = ++searchIdRef.current;
+    const isCurrent = () => searchIdRef.current === searchId;
+    setMatches([]);
+    setError(null);
+    if (!query) {
+      setSearching(false);
+      return;
+    }
+
+    const timer = setTimeout(() => {
+      setSearching(true);
+      searchTerminals(query, { regex, caseSensitive }, (batch) => {
+        if (isCurrent()) setMatches((previous) => [...previous, ...batch]);
+      })
+        .then((all) => {
+          if (!isCurrent()) return;
+          setMatches(all);
+          setSearching(false);
+        })
+        .catch((err) => {
+          if (!isCurrent()) return;
+          setError(String(err));
+          setSearching(false);
+        });
+    }, SEARCH_DEBOUNCE_MS);
+    return () => clearTimeout(timer);
+  }, [query, regex, caseSensitive]);
+
+  const openMatch = (terminalId: string) => {
+    const target = resolvePreferredTerminalFocus(terminalId);
+    useTerminalStore.getState().setActiveTerminal(target);
+    handleTmuxTerminalFocus(target);
+    onClose();
+  };
+
+  return (
+    <div className="dialog-overlay" onClick={onClose}>
+      <div className="dialog terminal-search-dialog" onClick={(e) => e.stopPropagation()}>
+        <div className="dialog-title">Search all terminals</div>
+        <input
+          className="dialog-input"
+          autoFocus
+          value={query}
+          placeholder="Text to find..."
+          onChange={(e) => setQuery(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter" && matches.length > 0) openMatch(matches[0].terminal_id);
+          }}
+        />
+        <div className="terminal-search-options">
+          <label className="terminal-settings-check">
+            <input type="checkbox" checked={regex} onChange={(e) => setRegex(e.target.checked)} />
+            <span>Regex</span>
+          </label>
+          <label className="terminal-settings-check">
+            <input
+              type="checkbox"
+              checked={caseSensitive}
+              onChange={(e) => setCaseSensitive(e.target.checked)}
+            />
+            <span>Match case</span>
+          </label>
+          <span className="terminal-search-count">
+            {searching ? "Searching..." : query ? `${matches.length} matches` : ""}
+          </span>
+        </div>
+        {error && <div className="terminal-settings-error">{error}</div>}
+        <div className="terminal-search-results">
+          {matches.map((match) => (
+            <div
+              key={`${match.terminal_id}:${match.line_number}`}
+              className="terminal-search-result"
+              onClick={() => openMatch(match.terminal_id)}
+            >
+              <div className="terminal-search-result-header">
+                <span className="terminal-search-result-title">
+                  {sessions[match.terminal_id]?.title ?? match.terminal_id.slice(0, 8)}
+                </span>
+                {match.origin !== "live" && (
+                  <span className="terminal-search-result-meta">{match.origin}</span>
+                )}
+                {match.timestamp_ms !== null && (
+                  <span className="terminal-search-result-meta">
+                    {new Date(match.timestamp_ms).toLocaleTimeString()}
+                  </span>
+                )}
+              </div>
+              <div className="terminal-search-result-line">
+                {match.line.slice(0, match.match_start)}
+                <mark>{match.line.slice(match.match_start, match.match_end)}</mark>
+                {match.line.slice(match.match_end)}
+              </div>
+            </div>
+          ))}
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,112 @@
+import { useEffect, useState } from "react";
+import { useTerminalSettingsStore } from "../../stores/useTerminalSettingsStore";
+import { getHibernationConfig, setHibernationConfig } from "../../lib/tauriCommands";
+import type { HibernationConfig } from "../../lib/tauriCommands";
+import { EnvScrubSettings } from "./EnvScrubSettings";
+import { PatternWatcherSettings } from "./PatternWatcherSettings";
+
+interface TerminalSettingsProps {
+  onClose: () => void;
+}
+
+export function TerminalSettings({ onClose }: TerminalSettingsProps) {
+  const offerLastCommand = useTerminalSettingsStore((s) => s.offerLastCommand);
+  const setOfferLastCommand = useTerminalSettingsStore((s) => s.setOfferLastCommand);
+  const [hibernation, setHibernation] = useState<HibernationConfig | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    getHibernationConfig()
+      .then(setHibernation)
+      .catch((err) => setError(String(err)));
+  }, []);
+
+  const updateHibernation = (patch: Partial<HibernationConfig>) => {
+    if (!hibernation) return;
+    const next = { ...hibernation, ...patch };
+    setHibernation(next);
+    setHibernationConfig(next)
+      .then(() => setError(null))
+      .catch((err) => setError(String(err)));
+  };
+
+  // Escape to close
+  useEffect(() => {
+    const handler = (e: KeyboardEvent) => {
+      if (e.key === "Escape") onClose();
+    };
+    window.addEventListener("keydown", handler);
+    return () => window.removeEventListener("keydown", handler);
+  }, [onClose]);
+
+  return (
+    <div className="dialog-overlay" onClick={onClose}>
+      <div className="dialog terminal-settings-dialog" onClick={(e) => e.stopPropagation()}>
+        <div className="font-settings-header">
+          <span className="dialog-title">Terminals</span>
+          <button className="hotkey-help-close" onClick={onClose}>
+            <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
+              <path d="M3 3L11 11M11 3L3 11" stroke="currentColor" strokeWidth="1.2" strokeLinecap="round"/>
+            </svg>
+          </button>
+        </div>
+
+        <div className="terminal-settings-section">
+          <div className="terminal-settings-section-title">Restore</div>
+          <label className="terminal-settings-check">
+            <input
+              type="checkbox"
+              checked={offerLastCommand}
+              onChange={(e) => setOfferLastCommand(e.target.checked)}
+            />
+            <span>Type the last command into restored tabs</span>
+          </label>
+          <div className="terminal-settings-hint">
+            The command waits at the first prompt; press Enter to run it.
+          </div>
+        </div>
+
+        {hibernation && (
+          <div className="terminal-settings-section">
+            <div className="terminal-settings-section-title">Hibernation</div>
+            <label className="terminal-settings-check">
+              <input
+                type="checkbox"
+                checked={hibernation.enabled}
+                onChange={(e) => updateHibernation({ enabled: e.target.checked })}
+              />
+              <span>Shut down idle shells</span>
+            </label>
+            <div className="font-settings-row">
+              <label className="font-settings-label">Idle for (hours)</label>
+              <input
+                type="number"
+                className="font-settings-number"
+                value={Math.round((hibernation.idleAfterSecs / 3600) * 10) / 10}
+                min={0.1}
+                step={0.5}
+                disabled={!hibernation.enabled}
+                onChange={(e) => {
+                  const hours = Number(e.target.value);
+                  if (hours > 0) {
+                    updateHibernation({ idleAfterSecs: Math.round(hours * 3600) });
+                  }
+                }}
+              />
+            </div>
+            <div className="terminal-settings-hint">
+              Only shells sitting at their prompt are shut down. Opening the tab starts the shell
+              again in the same directory.
+            </div>
+          </div>
+        )}
+
+        {error && <div className="terminal-settings-error">{error}</div>}
+
+        <PatternWatcherSettings />
+
+        <EnvScrubSettings />
+      </div>
+    </div>
+  );
+}
@@ -2,6 +2,7 @@ import { afterEach, beforeEach, describe, expect, it, vi } from "vitest";
 
 const {
   createTerminalMock,
+  createTerminalsMock,
   writeTerminalMock,
   resizeTerminalMock,
   warmPoolMock,
@@ -11,6 +12,11 @@ const {
   createdChannels,
 } = vi.hoisted(() => ({
   createTerminalMock: vi.fn(async () => {}),
+  createTerminalsMock: vi.fn(
+    async (
+      _requests: Array<{ terminalId: string; deferred: boolean }>
+    ): Promise<Array<{ terminal_id: string; status: string; error: string | null }>> => []
+  ),
   writeTerminalMock: vi.fn(async () => {}),
   resizeTerminalMock: vi.fn(async () => {}),
   warmPoolMock: vi.fn(async () => {}),
@@ -137,6 +143,7 @@ vi.mock("@tauri-apps/api/core", () => {
 
 vi.mock("../../lib/tauriCommands", () => ({
   createTerminal: createTerminalMock,
+  createTerminals: createTerminalsMock,
   writeTerminal: writeTerminalMock,
   resizeTerminal: resizeTerminalMock,
   warmPool: warmPoolMock,
@@ -153,12 +160,15 @@ import {
   ensureTerminalScreenshotTarget,
   handleTerminalInputData,
   hasTerminalFrontend,
+  pushTerminalKeyDebug,
   queueTerminalOutput,
   reflectImmediateTabActivity,
   sendSyntheticTerminalInput,
+  startDeferredTerminals,
   stripGeneratedTerminalResponseSequences,
   syncTerminalFrontendSize,
 } from "../useTerminalBridge";
+import { describeKeyboardEvent, getKeyDebugEntries } from "../../lib/keyDebug";
 import { useLayoutStore } from "../../stores/useLayoutStore";
 import { useTerminalStore } from "../../stores/useTerminalStore";
 
@@ -168,6 +178,7 @@ describe("useTerminalBridge synthetic input", () => {
     createdFitAddons.length = 0;
     createdChannels.length = 0;
     createTerminalMock.mockClear();
+    createTerminalsMock.mockClear();
     writeTerminalMock.mockClear();
     resizeTerminalMock.mockClear();
     warmPoolMock.mockClear();
@@ -190,6 +201,8 @@ describe("useTerminalBridge synthetic input", () => {
     globalThis.__dispatcherTmuxTransportOutputRouter = undefined;
     disposeTerminalInstance("tab-root");
     disposeTerminalInstance("pane");
+    disposeTerminalInstance("restored-a");
+    disposeTerminalInstance("restored-b");
   });
 
   it("scrolls synthetic terminal input to the bottom before writing to the PTY", () => {
@@ -206,6 +219,37 @@ describe("useTerminalBridge synthetic input", () => {
     expect(writeTerminalMock).toHaveBeenCalledWith("term-scroll-test", "\u0003");
   });
 
+  it("keeps typed keys and synthetic input out of the key debug log at a password prompt", () => {
+    useTerminalStore.getState().addSession("term-scroll-test", "A");
+    useTerminalStore.getState().patchSession("term-scroll-test", { isSecureInput: true });
+    ensureTerminalScreenshotTarget("term-scroll-test");
+    const describe = vi.fn(() =>
+      describeKeyboardEvent(new KeyboardEvent("keydown", { key: "h", code: "KeyH" }))
+    );
+
+    pushTerminalKeyDebug("terminal.capture:term-scroll-test", "term-scroll-test", describe);
+    sendSyntheticTerminalInput("term-scroll-test", "hunter2");
+
+    expect(describe).not.toHaveBeenCalled();
+    const logged = getKeyDebugEntries().slice(-2);
+    expect(logged.map((entry) => entry.source)).toEqual([
+      "terminal.capture:term-scroll-test",
+      "terminal.synthetic-input:term-scroll-test",
+    ]);
+    for (const entry of logged) {
+      expect(entry.detail).toContain("secure input");
+      expect(entry.detail).not.toContain("KeyH");
+      expect(entry.detail).not.toContain("hunter2");
+    }
+    expect(writeTerminalMock).toHaveBeenCalledWith("term-scroll-test", "hunter2");
+
+    useTerminalStore.getState().patchSession("term-scroll-test", { isSecureInput: false });
+    pushTerminalKeyDebug("terminal.capture:term-scroll-test", "term-scroll-test", describe);
+
+    expect(describe).toHaveBeenCalledTimes(1);
+    expect(getKeyDebugEntries().slice(-1)[0].detail).toContain("KeyH");
+  });
+
   it("routes existing PTY channel output through the current tmux router", async () => {
     ensureTerminalScreenshotTarget("term-query-test");
     expect(createdChannels).toHaveLength(1);
@@ -225,6 +269,38 @@ describe("useTerminalBridge synthetic input", () => {
     );
   });
 
+  it("defers restored local terminals that have no PTY yet and reports failed spawns", async () => {
+    useTerminalStore.getState().addSession("term-scroll-test", "Mounted");
+    useTerminalStore.getState().addSession("restored-a", "A");
+    useTerminalStore.getState().addSession("restored-b", "B");
+    useTerminalStore.getState().addSession("tmux-pane-test", "Pane");
+    useTerminalStore.getState().patchSession("tmux-pane-test", { backendKind: "tmux-pane" });
+    ensureTerminalScreenshotTarget("term-scroll-test");
+    createTerminalsMock.mockResolvedValueOnce([
+      { terminal_id: "restored-a", status: "deferred", error: null },
+      { terminal_id: "restored-b", status: "failed", error: "no such directory" },
+    ]);
+
+    startDeferredTerminals(Object.keys(useTerminalStore.getState().sessions));
+    await Promise.resolve();
+    await Promise.resolve();
+
+    expect(createTerminalsMock).toHaveBeenCalledTimes(1);
+    const requests = createTerminalsMock.mock.calls[0][0];
+    expect(requests.map((request) => request.terminalId)).toEqual(["restored-a", "restored-b"]);
+    expect(requests.every((request) => request.deferred)).toBe(true);
+    const failed = createdTerminals[createdTerminals.length - 1];
+    expect(failed.write).toHaveBeenCalledWith(
+      "\r\nError creating terminal: no such directory\r\n"
+    );
+
+    // Registered terminals are not sent again; the failed one is retried.
+    startDeferredTerminals(["restored-a", "restored-b"]);
+    expect(createTerminalsMock.mock.calls[1][0].map((request) => request.terminalId)).toEqual([
+      "restored-b",
+    ]);
+  });
+
   it("resizes an existing xterm frontend to match a tmux pane grid", () => {
     ensureTerminalScreenshotTarget("term-scroll-test");
 
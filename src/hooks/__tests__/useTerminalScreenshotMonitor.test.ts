@@ -98,6 +98,16 @@ describe("shouldUseTimestampOnlyStatus", () => {
       session({ backendKind: "local" }),
     ])).toBe(false);
   });
+
+  it("uses timestamp-only status for local tabs tracked by the backend", () => {
+    expect(shouldUseTimestampOnlyStatus([
+      session({ backendKind: "local", hasBackendActivity: true }),
+    ])).toBe(true);
+    expect(shouldUseTimestampOnlyStatus([
+      session({ backendKind: "local", hasBackendActivity: true }),
+      session({ backendKind: "local" }),
+    ])).toBe(false);
+  });
 });
 
 describe("resolveTimestampStatusChangedAt", () => {
@@ -8,12 +8,18 @@ import { Channel } from "@tauri-apps/api/core";
 import { readText as readClipboardText, writeText as writeClipboardText } from "@tauri-apps/plugin-clipboard-manager";
 import { open } from "@tauri-apps/plugin-shell";
 import {
+  adoptTerminal as adoptPty,
   createTerminal as createPty,
+  createTerminals as createPtys,
   writeTerminal,
   resizeTerminal,
   warmPool,
 } from "../lib/tauriCommands";
-import type { TerminalOutputPayload } from "../lib/tauriCommands";
+import type {
+  TerminalOutputPayload,
+  TerminalSpawnOptions,
+  TerminalSpawnRequest,
+} from "../lib/tauriCommands";
 import type { TerminalBackendKind } from "../types/terminal";
 import { useFontStore } from "../stores/useFontStore";
 import { useColorSchemeStore } from "../stores/useColorSchemeStore";
@@ -22,6 +28,9 @@ import { findLayoutKeyForTerminal } from "../lib/layoutUtils";
 import { getTabStatusTerminalIds, type TerminalVisualTextSnapshot } from "../lib/terminalScreenshotHash";
 import { useLayoutStore } from "../stores/useLayoutStore";
 import { useTerminalStore } from "../stores/useTerminalStore";
+import { useProjectStore } from "../stores/useProjectStore";
+import { useTerminalSettingsStore } from "../stores/useTerminalSettingsStore";
+import { findProjectIdForTerminal } from "../lib/treeUtils";
 import { describeKeyboardEvent, describeTerminalData, pushKeyDebug } from "../lib/keyDebug";
 import { debugLog } from "../lib/debugLog";
 import { isLinkOpenModifierPressed } from "../lib/terminalMouse";
@@ -320,7 +329,7 @@ export function useTerminalPasteProgress(terminalId: string): TerminalPasteProgr
 }
 
 async function pasteTextIntoTerminal(terminalId: string, xterm: Terminal, text: string) {
-  pushKeyDebug(`terminal.paste-data:${terminalId}`, describeTerminalData(text));
+  pushKeyDebug(`terminal.paste-data:${terminalId}`, describeInputData(terminalId, text));
   xterm.focus();
 
   const backendKind = useTerminalStore.getState().sessions[terminalId]?.backendKind ?? "local";
@@ -789,12 +798,37 @@ function shouldSuppressTransientFocusSequence(terminalId: string, data: string):
   return true;
 }
 
+function isSecureInput(terminalId: string): boolean {
+  return useTerminalStore.getState().sessions[terminalId]?.isSecureInput === true;
+}
+
+// Keystrokes typed at a password prompt are recorded by length only.
+function describeInputData(terminalId: string, data: string): Record<string, unknown> {
+  if (isSecureInput(terminalId)) {
+    return { redacted: "secure input", length: data.length };
+  }
+  return describeTerminalData(data);
+}
+
+/**
+ * Record a key-debug entry whose detail may contain typed keys or data.
+ * While the terminal is at a password prompt only a redaction marker is
+ * kept; `describe` is not called at all.
+ */
+export function pushTerminalKeyDebug(
+  source: string,
+  terminalId: string,
+  describe: () => unknown
+): void {
+  pushKeyDebug(source, isSecureInput(terminalId) ? { redacted: "secure input" } : describe());
+}
+
 function isTransientFocusSequence(data: string): boolean {
   return data === "\u001b[I" || data === "\u001b[O";
 }
 
 export function handleTerminalInputData(terminalId: string, data: string) {
-  pushKeyDebug(`xterm.onData:${terminalId}`, describeTerminalData(data));
+  pushKeyDebug(`xterm.onData:${terminalId}`, describeInputData(terminalId, data));
   const backendKind = useTerminalStore.getState().sessions[terminalId]?.backendKind ?? "local";
   let inputData = data;
   if (backendKind === "tmux-pane") {
@@ -805,26 +839,26 @@ export function handleTerminalInputData(terminalId: string, data: string) {
         strippedBytes: sanitized.strippedBytes,
         strippedCount: sanitized.strippedCount,
         forwardedBytes: sanitized.data.length,
-        rawPreview: describeTerminalData(data),
-        forwardedPreview: sanitized.data.length > 0 ? describeTerminalData(sanitized.data) : "",
+        rawPreview: describeInputData(terminalId, data),
+        forwardedPreview: sanitized.data.length > 0 ? describeInputData(terminalId, sanitized.data) : "",
       });
       pushKeyDebug(
         `xterm.terminal-response-suppressed:${terminalId}`,
-        describeTerminalData(data)
+        describeInputData(terminalId, data)
       );
       inputData = sanitized.data;
       if (inputData.length === 0) {
         return;
       }
-      pushKeyDebug(`xterm.onData-sanitized:${terminalId}`, describeTerminalData(inputData));
+      pushKeyDebug(`xterm.onData-sanitized:${terminalId}`, describeInputData(terminalId, inputData));
     }
   }
   if (shouldSuppressSyntheticEcho(terminalId, inputData)) {
-    pushKeyDebug(`xterm.synthetic-echo-suppressed:${terminalId}`, describeTerminalData(inputData));
+    pushKeyDebug(`xterm.synthetic-echo-suppressed:${terminalId}`, describeInputData(terminalId, inputData));
     return;
   }
   if (shouldSuppressTransientFocusSequence(terminalId, inputData)) {
-    pushKeyDebug(`xterm.focus-sequence-suppressed:${terminalId}`, describeTerminalData(inputData));
+    pushKeyDebug(`xterm.focus-sequence-suppressed:${terminalId}`, describeInputData(terminalId, inputData));
     return;
   }
   // Any submitted command may change cwd; force a fresh lookup on next spawn.
@@ -835,7 +869,7 @@ export function handleTerminalInputData(terminalId: string, data: string) {
     useTerminalStore.getState().markTerminalActivity(terminalId);
     reflectImmediateTabActivity(terminalId);
   }
-  pushKeyDebug(`pty.write-request:${terminalId}`, describeTerminalData(inputData));
+  pushKeyDebug(`pty.write-request:${terminalId}`, describeInputData(terminalId, inputData));
   if (backendKind === "tmux-pane") {
     sendInputToTmuxTerminal(terminalId, inputData).catch(() => {});
   } else {
@@ -1090,7 +1124,7 @@ function createTerminalInstance(terminalId: string): TerminalInstance {
   loadWebGLAddon(xterm);
 
   xterm.attachCustomKeyEventHandler((e) => {
-    pushKeyDebug(`xterm.custom-key:${terminalId}`, describeKeyboardEvent(e));
+    pushTerminalKeyDebug(`xterm.custom-key:${terminalId}`, terminalId, () => describeKeyboardEvent(e));
     if (e.type !== "keydown") return true;
     if (e.defaultPrevented) return false;
 
@@ -1128,6 +1162,38 @@ export function hasTerminalFrontend(terminalId: string): boolean {
   return instances.has(terminalId);
 }
 
+/** Output channel, size and spawn options for a local terminal's PTY. */
+function prepareTerminalBackend(terminalId: string, instance: TerminalInstance) {
+  const channel = new Channel<TerminalOutputPayload>();
+  channel.onmessage = (msg) => {
+    // Tauri channels live for the lifetime of the PTY. In dev, Vite can hot
+    // swap tmuxControl.ts without recreating this callback, so resolve the
+    // current router lazily instead of capturing a stale module function.
+    const nextData = getCurrentTmuxTransportOutputRouter()(msg.terminal_id, msg.data);
+    if (nextData) {
+      batchedWrite(msg.terminal_id, nextData);
+    }
+  };
+
+  const cols = instance.xterm.cols || 80;
+  const rows = instance.xterm.rows || 24;
+
+  const projectState = useProjectStore.getState();
+  const projectId = findProjectIdForTerminal(
+    projectState.projects,
+    projectState.projectOrder,
+    projectState.nodes,
+    useTerminalStore.getState().sessions,
+    findLayoutKeyForTerminal(useLayoutStore.getState().layouts, terminalId) ?? terminalId
+  );
+
+  const projectRoot = projectId ? projectState.projects[projectId]?.cwd || null : null;
+  const incognito = useTerminalStore.getState().sessions[terminalId]?.isIncognito ?? false;
+  const { offerLastCommand } = useTerminalSettingsStore.getState();
+  const options: TerminalSpawnOptions = { projectId, projectRoot, incognito, offerLastCommand };
+  return { channel, cols, rows, options };
+}
+
 function ensureTerminalBackend(terminalId: string, cwd?: string) {
   const instance = createTerminalInstance(terminalId);
   const backendKind = useTerminalStore.getState().sessions[terminalId]?.backendKind ?? "local";
@@ -1139,23 +1205,10 @@ function ensureTerminalBackend(terminalId: string, cwd?: string) {
   if (!createdPtys.has(terminalId)) {
     createdPtys.add(terminalId);
 
-    const channel = new Channel<TerminalOutputPayload>();
-    channel.onmessage = (msg) => {
-      // Tauri channels live for the lifetime of the PTY. In dev, Vite can hot
-      // swap tmuxControl.ts without recreating this callback, so resolve the
-      // current router lazily instead of capturing a stale module function.
-      const nextData = getCurrentTmuxTransportOutputRouter()(msg.terminal_id, msg.data);
-      if (nextData) {
-        batchedWrite(msg.terminal_id, nextData);
-      }
-    };
-
-    const cols = instance.xterm.cols || 80;
-    const rows = instance.xterm.rows || 24;
-
-    createPty(terminalId, channel, cwd, cols, rows)
+    const { channel, cols, rows, options } = prepareTerminalBackend(terminalId, instance);
+    createPty(terminalId, channel, cwd, cols, rows, options)
       .then(() => {
-        warmPool(1).catch(() => {});
+        warmPool(1, options.projectId, options.projectRoot).catch(() => {});
       })
       .catch((err) => {
         instance.xterm.write(`\r\nError creating terminal: ${err}\r\n`);
@@ -1165,6 +1218,76 @@ function ensureTerminalBackend(terminalId: string, cwd?: string) {
   return instance;
 }
 
+/**
+ * Register shells for local tabs that are not on screen, e.g. after a
+ * restore. The backend forks them a few at a time in the background, or as
+ * soon as a tab is focused, instead of all at once.
+ */
+export function startDeferredTerminals(terminalIds: string[]) {
+  const sessions = useTerminalStore.getState().sessions;
+  const requests: TerminalSpawnRequest[] = [];
+  for (const terminalId of terminalIds) {
+    const session = sessions[terminalId];
+    if (!session || (session.backendKind ?? "local") !== "local" || createdPtys.has(terminalId)) {
+      continue;
+    }
+    createdPtys.add(terminalId);
+    const instance = createTerminalInstance(terminalId);
+    const { channel, cols, rows, options } = prepareTerminalBackend(terminalId, instance);
+    requests.push({
+      ...options,
+      terminalId,
+      cwd: session.cwd ?? null,
+      cols,
+      rows,
+      deferred: true,
+      onOutput: channel,
+    });
+  }
+  if (requests.length === 0) {
+    return;
+  }
+
+  createPtys(requests)
+    .then((results) => {
+      for (const result of results) {
+        if (result.status === "failed") {
+          reportTerminalSpawnFailure(result.terminal_id, result.error ?? "unknown error");
+        }
+      }
+    })
+    .catch((err) => {
+      for (const request of requests) {
+        reportTerminalSpawnFailure(request.terminalId, String(err));
+      }
+    });
+}
+
+/**
+ * Attach a frontend to a backend terminal the renderer lost track of, before
+ * its session is added back so the pane does not spawn a new shell.
+ */
+export async function adoptTerminalBackend(terminalId: string): Promise<void> {
+  const instance = createTerminalInstance(terminalId);
+  const { channel } = prepareTerminalBackend(terminalId, instance);
+  createdPtys.add(terminalId);
+  try {
+    await adoptPty(terminalId, channel);
+  } catch (err) {
+    disposeTerminalInstance(terminalId);
+    throw err;
+  }
+}
+
+/**
+ * Show why a terminal has no shell. The next mount of its pane tries
+ * again.
+ */
+export function reportTerminalSpawnFailure(terminalId: string, error: string) {
+  createdPtys.delete(terminalId);
+  instances.get(terminalId)?.xterm.write(`\r\nError creating terminal: ${error}\r\n`);
+}
+
 function parseCssPixelValue(value: string | null | undefined): number | null {
   if (!value) {
     return null;
@@ -1660,7 +1783,7 @@ export function captureTerminalVisualSnapshot(
 }
 
 export function sendSyntheticTerminalInput(terminalId: string, data: string) {
-  pushKeyDebug(`terminal.synthetic-input:${terminalId}`, describeTerminalData(data));
+  pushKeyDebug(`terminal.synthetic-input:${terminalId}`, describeInputData(terminalId, data));
   syntheticInputSuppressions.set(terminalId, {
     data,
     expiresAt: Date.now() + SYNTHETIC_INPUT_SUPPRESSION_MS,
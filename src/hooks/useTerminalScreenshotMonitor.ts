@@ -6,7 +6,7 @@ import {
 } from "./useTerminalBridge";
 import { findLayoutKeyForTerminal } from "../lib/layoutUtils";
 import { pushScreenshotDebug } from "../lib/screenshotDebug";
-import { writeDebugArtifact } from "../lib/tauriCommands";
+import { getTerminalScreen, writeDebugArtifact } from "../lib/tauriCommands";
 import {
   buildCompoundScreenshotHashInput,
   buildTerminalVisualHashInput,
@@ -63,7 +63,12 @@ export function shouldUseTimestampOnlyStatus(sessions: readonly TerminalSession[
   // Tmux control mode already gives us a precise signal when pane data arrives.
   // Visual sampling is weaker for tmux because focus redraws, cursor updates,
   // and capture replays can change the xterm buffer without agent progress.
-  return sessions.some(isTmuxStatusSession);
+  // Local PTYs report output timestamps from the backend reader threads,
+  // which keep working while the renderer is throttled.
+  return (
+    sessions.some(isTmuxStatusSession)
+    || (sessions.length > 0 && sessions.every((session) => session.hasBackendActivity === true))
+  );
 }
 
 export function resolveTimestampStatusChangedAt(args: {
@@ -89,6 +94,26 @@ async function hashScreenshot(screenshot: string): Promise<string> {
   return Array.from(new Uint8Array(digest), (value) => value.toString(16).padStart(2, "0")).join("");
 }
 
+/**
+ * Local tabs that were never mounted have no xterm instance; the backend's
+ * screen model holds the same text. Lines are padded like xterm's.
+ */
+async function readBackendVisualSnapshot(
+  terminalId: string
+): Promise<TerminalVisualTextSnapshot | null> {
+  try {
+    const screen = await getTerminalScreen(terminalId, 0);
+    return {
+      terminalId,
+      cols: screen.cols,
+      rows: screen.rows,
+      lines: screen.lines.map((line) => line.text.padEnd(screen.cols)),
+    };
+  } catch {
+    return null;
+  }
+}
+
 function getActiveTabRootTerminalId(): string | null {
   const activeTerminalId = useTerminalStore.getState().activeTerminalId;
   if (!activeTerminalId) {
@@ -396,7 +421,11 @@ async function writeScreenshotDebugArtifacts(args: {
   };
 
   const paths = [
-    await writeDebugArtifact(`${prefix}.json`, JSON.stringify(metadata, null, 2)),
+    await writeDebugArtifact(
+      `${prefix}.json`,
+      JSON.stringify(metadata, null, 2),
+      args.terminalIds
+    ),
   ];
 
   for (const [index, sample] of args.screenshots
@@ -426,7 +455,8 @@ async function writeScreenshotDebugArtifacts(args: {
           changedRows: args.visualChange.changedRows,
           changedChars: args.visualChange.changedChars,
         },
-      })
+      }),
+      [args.tabRootTerminalId, sample.terminalId]
     ));
   }
 
@@ -756,12 +786,12 @@ export function useTerminalScreenshotMonitor() {
               break;
             }
 
-            if (!hasTerminalFrontend(terminalId)) {
-              isReady = false;
-              break;
+            let visualSnapshot: TerminalVisualTextSnapshot | null = null;
+            if (hasTerminalFrontend(terminalId)) {
+              visualSnapshot = captureTerminalVisualSnapshot(terminalId);
+            } else if ((session.backendKind ?? "local") === "local") {
+              visualSnapshot = await readBackendVisualSnapshot(terminalId);
             }
-
-            const visualSnapshot = captureTerminalVisualSnapshot(terminalId);
             if (visualSnapshot === null) {
               isReady = false;
               break;
@@ -973,7 +1003,11 @@ export function useTerminalScreenshotMonitor() {
           const screenshotsWithOptionalImages = shouldAttachScreenshotImages
             ? attachScreenshotImages(screenshots, { maxImages: MAX_SCREENSHOT_IMAGE_CAPTURES_PER_SAMPLE })
             : screenshots;
-          if (shouldWriteScreenshotArtifact) {
+          // The backend refuses artifacts that show incognito tabs.
+          const hasIncognitoTerminal = terminalIds.some(
+            (terminalId) => latestStore.sessions[terminalId]?.isIncognito
+          );
+          if (shouldWriteScreenshotArtifact && !hasIncognitoTerminal) {
             lastArtifactAt.set(tabRootTerminalId, now);
             lastGlobalArtifactAt = now;
             void writeScreenshotDebugArtifacts({
@@ -1,4 +1,5 @@
 import { invoke, Channel } from "@tauri-apps/api/core";
+import type { TerminalColors } from "../types/colorScheme";
 
 export interface TerminalOutputPayload {
   terminal_id: string;
@@ -13,6 +14,187 @@ export interface TerminalDebugInfo {
   vreprint: number | null;
 }
 
+export interface TerminalSpawnOptions {
+  profile?: string | null;
+  env?: Record<string, string>;
+  offerLastCommand?: boolean;
+  projectId?: string | null;
+  /** Project root; project config files are read from here. */
+  projectRoot?: string | null;
+  autoResponders?: AutoResponder[];
+  incognito?: boolean;
+  /** Encoding label such as "shift_jis" or "gb18030"; UTF-8 when unset. */
+  encoding?: string | null;
+}
+
+export interface TerminalSpawnRequest extends TerminalSpawnOptions {
+  terminalId: string;
+  cwd: string | null;
+  cols: number;
+  rows: number;
+  deferred: boolean;
+  onOutput: Channel<TerminalOutputPayload>;
+}
+
+export interface LocalSessionRecord {
+  terminal_id: string;
+  cwd: string | null;
+  profile: string | null;
+  env: Record<string, string>;
+  last_command: string | null;
+  history: string[];
+  updated_at_ms: number;
+}
+
+export interface TerminalSpawnResult {
+  terminal_id: string;
+  status: "spawned" | "deferred" | "failed";
+  error: string | null;
+}
+
+export interface OrphanedTerminal {
+  terminal_id: string;
+  orphaned_ms: number;
+  closes_in_ms: number;
+}
+
+export interface TerminalActivity {
+  terminal_id: string;
+  state: "idle" | "active" | "stale" | "long_idle";
+  last_output_at_ms: number | null;
+  total_bytes: number;
+  bytes_per_sec: number;
+}
+
+/** Palette index (0-255) or `#rrggbb`; omitted for the default color. */
+export type ScreenColor = number | string;
+
+export interface ScreenSpan {
+  text: string;
+  fg?: ScreenColor;
+  bg?: ScreenColor;
+  bold?: boolean;
+  dim?: boolean;
+  italic?: boolean;
+  underline?: boolean;
+  inverse?: boolean;
+  strikethrough?: boolean;
+  hidden?: boolean;
+}
+
+export interface ScreenLine {
+  text: string;
+  wrapped: boolean;
+  spans: ScreenSpan[];
+}
+
+export interface TerminalScreen {
+  cols: number;
+  rows: number;
+  history_lines: number;
+  cursor: { row: number; col: number; visible: boolean };
+  alternate_screen: boolean;
+  lines: ScreenLine[];
+}
+
+export interface HibernationConfig {
+  enabled: boolean;
+  idleAfterSecs: number;
+}
+
+export interface PatternWatcher {
+  id: string;
+  name: string;
+  pattern: string;
+  caseSensitive?: boolean;
+  terminalId?: string | null;
+  projectId?: string | null;
+  notify?: boolean;
+  needsAttention?: boolean;
+}
+
+export interface PatternMatch {
+  watcher_id: string;
+  watcher_name: string;
+  terminal_id: string;
+  line: string;
+  matched_text: string;
+  notify: boolean;
+  needs_attention: boolean;
+}
+
+export interface AutoResponder {
+  id: string;
+  pattern: string;
+  /** Typed as-is; end with "\r" to press Enter. */
+  response: string;
+  caseSensitive?: boolean;
+  maxFires?: number | null;
+  minIntervalMs?: number;
+}
+
+export interface AutoResponderStatus extends AutoResponder {
+  fire_count: number;
+}
+
+export interface EnvValueRule {
+  /** Variables the rule looks at; every variable when empty. */
+  names?: string[];
+  pattern: string;
+}
+
+export interface EnvScrubRules {
+  exactNames: string[];
+  prefixes: string[];
+  valueRules: EnvValueRule[];
+  /** Names kept even when another rule matches. */
+  allow: string[];
+}
+
+export interface EnvVarPreview {
+  name: string;
+  value: string;
+  overridden: boolean;
+  source: "inherited" | "activation" | "project" | "terminal";
+}
+
+export interface RemovedEnvVar {
+  name: string;
+  value: string;
+  reason: string;
+}
+
+export interface SpawnEnvironmentPreview {
+  shell: string;
+  inherited: EnvVarPreview[];
+  removed: RemovedEnvVar[];
+  project_rules_path: string | null;
+}
+
+/**
+ * Evaluated in the project root before a shell starts; cached until
+ * `.envrc` or `flake.lock` changes. A command prints a JSON object (like
+ * `direnv export json`) or `env -0` output.
+ */
+export type EnvActivation = { kind: "direnv" } | { kind: "command"; command: string };
+
+export interface ProjectEnvConfig {
+  /**
+   * Relative to the project root, applied in order; missing files are
+   * skipped. Empty until the user lists files; loader and shell-startup
+   * variables in them are ignored.
+   */
+  envFiles: string[];
+  /** Applied after the files; values may use $NAME / ${NAME:-default}. */
+  env: Record<string, string>;
+  activation?: EnvActivation | null;
+  /**
+   * Keep the project's shell history in its own file (kept across renames
+   * and moves); Alt+R searches the global history.
+   */
+  projectHistory?: boolean;
+}
+
 export interface RendererHeartbeatDetails {
   sequence: number;
   reason: string;
@@ -33,17 +215,182 @@ export async function createTerminal(
   onOutput: Channel<TerminalOutputPayload>,
   cwd?: string,
   cols: number = 80,
-  rows: number = 24
+  rows: number = 24,
+  options?: TerminalSpawnOptions
 ): Promise<void> {
   await invoke("create_terminal", {
     terminalId,
     cwd: cwd ?? null,
     cols,
     rows,
+    options: options ?? null,
     onOutput,
   });
 }
 
+export async function createTerminals(
+  terminals: TerminalSpawnRequest[]
+): Promise<TerminalSpawnResult[]> {
+  return await invoke("create_terminals", { terminals });
+}
+
+export async function getRestorableTerminals(): Promise<LocalSessionRecord[]> {
+  return await invoke("get_restorable_terminals");
+}
+
+export async function getTerminalActivity(): Promise<TerminalActivity[]> {
+  return await invoke("get_terminal_activity");
+}
+
+export async function getTerminalScreen(
+  terminalId: string,
+  historyLines?: number
+): Promise<TerminalScreen> {
+  return await invoke("get_terminal_screen", { terminalId, historyLines });
+}
+
+export type TerminalExportFormat = "html" | "ansi" | "text";
+export type TerminalExportRange = "all" | "last_command";
+
+/**
+ * Write a terminal's output to `path`, or to the app data dir when omitted,
+ * and return the written path. HTML uses `theme` for its colors.
+ */
+export async function exportTerminalOutput(
+  terminalId: string,
+  format: TerminalExportFormat,
+  range: TerminalExportRange,
+  options?: { path?: string; theme?: TerminalColors }
+): Promise<string> {
+  return await invoke("export_terminal_output", {
+    terminalId,
+    format,
+    range,
+    path: options?.path,
+    theme: options?.theme,
+  });
+}
+
+export interface TerminalSearchMatch {
+  terminal_id: string;
+  line_number: number;
+  line: string;
+  match_start: number;
+  match_end: number;
+  context_before: string[];
+  context_after: string[];
+  timestamp_ms: number | null;
+  origin: "live" | "hibernated" | "saved";
+}
+
+/**
+ * Search the retained output of every terminal. Matches are streamed to
+ * `onMatches` per terminal as they are found; the promise resolves with all
+ * of them once the search finishes.
+ */
+export async function searchTerminals(
+  query: string,
+  options: { regex?: boolean; caseSensitive?: boolean },
+  onMatches: (matches: TerminalSearchMatch[]) => void
+): Promise<TerminalSearchMatch[]> {
+  const channel = new Channel<TerminalSearchMatch[]>();
+  channel.onmessage = onMatches;
+  return await invoke("search_terminals", {
+    query,
+    regex: options.regex,
+    caseSensitive: options.caseSensitive,
+    onMatches: channel,
+  });
+}
+
+export async function getHibernationConfig(): Promise<HibernationConfig> {
+  return await invoke("get_hibernation_config");
+}
+
+export async function setHibernationConfig(config: HibernationConfig): Promise<void> {
+  await invoke("set_hibernation_config", { config });
+}
+
+export async function getPatternWatchers(): Promise<PatternWatcher[]> {
+  return await invoke("get_pattern_watchers");
+}
+
+export async function setPatternWatchers(watchers: PatternWatcher[]): Promise<void> {
+  await invoke("set_pattern_watchers", { watchers });
+}
+
+export async function getAutoResponders(terminalId: string): Promise<AutoResponderStatus[]> {
+  return await invoke("get_auto_responders", { terminalId });
+}
+
+export async function setAutoResponders(
+  terminalId: string,
+  rules: AutoResponder[]
+): Promise<void> {
+  await invoke("set_auto_responders", { terminalId, rules });
+}
+
+/** The terminal's encoding name, or null when it never chose one (UTF-8). */
+export async function getTerminalEncoding(terminalId: string): Promise<string | null> {
+  return await invoke("get_terminal_encoding", { terminalId });
+}
+
+export async function setTerminalEncoding(
+  terminalId: string,
+  encoding: string | null
+): Promise<void> {
+  await invoke("set_terminal_encoding", { terminalId, encoding });
+}
+
+/** Replace the terminal's shell with one that skips the user's startup files. */
+export async function restartTerminalSafeMode(terminalId: string): Promise<void> {
+  await invoke("restart_terminal_safe_mode", { terminalId });
+}
+
+/** Answer a `terminal-control-request` forwarded from the control socket. */
+export async function completeControlRequest(
+  requestId: number,
+  result: { error?: string; terminalId?: string | null }
+): Promise<void> {
+  await invoke("complete_control_request", {
+    requestId,
+    error: result.error ?? null,
+    terminalId: result.terminalId ?? null,
+  });
+}
+
+export async function getEnvScrubRules(): Promise<EnvScrubRules> {
+  return await invoke("get_env_scrub_rules");
+}
+
+export async function setEnvScrubRules(rules: EnvScrubRules): Promise<void> {
+  await invoke("set_env_scrub_rules", { rules });
+}
+
+export async function getProjectEnvConfig(projectId: string): Promise<ProjectEnvConfig> {
+  return await invoke("get_project_env_config", { projectId });
+}
+
+export async function setProjectEnvConfig(
+  projectId: string,
+  config: ProjectEnvConfig
+): Promise<void> {
+  await invoke("set_project_env_config", { projectId, config });
+}
+
+/** Rejects with "file:line: message" when a project env file does not parse. */
+export async function previewSpawnEnvironment(
+  projectId: string | null,
+  projectRoot: string | null,
+  env?: Record<string, string>
+): Promise<SpawnEnvironmentPreview> {
+  return await invoke("preview_spawn_environment", { projectId, projectRoot, env });
+}
+
+export async function activateTerminal(terminalId: string): Promise<boolean> {
+  return await invoke("activate_terminal", { terminalId });
+}
+
 export async function writeTerminal(
   terminalId: string,
   data: string
@@ -63,8 +410,29 @@ export async function closeTerminal(terminalId: string): Promise<void> {
   await invoke("close_terminal", { terminalId });
 }
 
-export async function warmPool(count: number = 3): Promise<void> {
-  await invoke("warm_pool", { count });
+export async function reconcileTerminals(
+  liveIds: string[]
+): Promise<OrphanedTerminal[]> {
+  return await invoke("reconcile_terminals", { liveIds });
+}
+
+export async function adoptTerminal(
+  terminalId: string,
+  onOutput: Channel<TerminalOutputPayload>
+): Promise<void> {
+  await invoke("adopt_terminal", { terminalId, onOutput });
+}
+
+/**
+ * With a project, warms that project's own shell when it has an environment
+ * (env files, stored variables or scrub rules); otherwise the shared pool.
+ */
+export async function warmPool(
+  count: number = 3,
+  projectId: string | null = null,
+  projectRoot: string | null = null
+): Promise<void> {
+  await invoke("warm_pool", { count, projectId, projectRoot });
 }
 
 export async function refreshPool(): Promise<void> {
@@ -105,11 +473,13 @@ export async function getDebugLogPath(): Promise<string> {
   return await invoke("get_debug_log_path");
 }
 
+/** `terminalIds` are the terminals the artifact shows; incognito ones are refused. */
 export async function writeDebugArtifact(
   fileName: string,
-  content: string
+  content: string,
+  terminalIds: string[]
 ): Promise<string> {
-  return await invoke("write_debug_artifact", { fileName, content });
+  return await invoke("write_debug_artifact", { fileName, content, terminalIds });
 }
 
 export async function readAppStateBackup(): Promise<string | null> {
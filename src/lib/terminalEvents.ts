@@ -1,4 +1,5 @@
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import type { OrphanedTerminal, PatternMatch, TerminalActivity } from "./tauriCommands";
 
 export interface TerminalExitPayload {
   terminal_id: string;
@@ -12,3 +13,96 @@ export function onTerminalExit(
     callback(event.payload);
   });
 }
+
+export interface TerminalSpawnFailedPayload {
+  terminal_id: string;
+  error: string;
+}
+
+/** A deferred terminal's background spawn failed; no exit event follows. */
+export function onTerminalSpawnFailed(
+  callback: (payload: TerminalSpawnFailedPayload) => void
+): Promise<UnlistenFn> {
+  return listen<TerminalSpawnFailedPayload>("terminal-spawn-failed", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onTerminalOrphansDetected(
+  callback: (orphans: OrphanedTerminal[]) => void
+): Promise<UnlistenFn> {
+  return listen<OrphanedTerminal[]>("terminal-orphans-detected", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onTerminalActivityChanged(
+  callback: (activity: TerminalActivity) => void
+): Promise<UnlistenFn> {
+  return listen<TerminalActivity>("terminal-activity-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
+export interface TerminalHibernationPayload {
+  terminal_id: string;
+  hibernated: boolean;
+}
+
+export function onTerminalHibernationChanged(
+  callback: (payload: TerminalHibernationPayload) => void
+): Promise<UnlistenFn> {
+  return listen<TerminalHibernationPayload>("terminal-hibernation-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
+export interface SecureInputPayload {
+  terminal_id: string;
+  active: boolean;
+}
+
+export function onSecureInputChanged(
+  callback: (payload: SecureInputPayload) => void
+): Promise<UnlistenFn> {
+  return listen<SecureInputPayload>("secure-input-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
+export interface StartupStalledPayload {
+  terminal_id: string;
+  stalled: boolean;
+}
+
+export function onTerminalStartupStalled(
+  callback: (payload: StartupStalledPayload) => void
+): Promise<UnlistenFn> {
+  return listen<StartupStalledPayload>("terminal-startup-stalled", (event) => {
+    callback(event.payload);
+  });
+}
+
+export type TerminalControlRequest = { request_id: number } & (
+  | { action: "new-tab"; project: string | null; cwd: string | null }
+  | { action: "split"; terminal_id: string; direction: "horizontal" | "vertical" }
+  | { action: "rename"; terminal_id: string; title: string }
+  | { action: "append-notes"; terminal_id: string; text: string }
+  | { action: "close"; terminal_id: string }
+);
+
+export function onTerminalControlRequest(
+  callback: (request: TerminalControlRequest) => void
+): Promise<UnlistenFn> {
+  return listen<TerminalControlRequest>("terminal-control-request", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onTerminalPatternMatched(
+  callback: (match: PatternMatch) => void
+): Promise<UnlistenFn> {
+  return listen<PatternMatch>("terminal-pattern-matched", (event) => {
+    callback(event.payload);
+  });
+}
@@ -0,0 +1,34 @@
+import { create } from "zustand";
+import { persist } from "zustand/middleware";
+import type { PatternWatcher } from "../lib/tauriCommands";
+
+interface PatternWatcherStore {
+  watchers: PatternWatcher[];
+  setWatchers: (watchers: PatternWatcher[]) => void;
+  addWatcher: (watcher: PatternWatcher) => void;
+  updateWatcher: (id: string, changes: Partial<PatternWatcher>) => void;
+  removeWatcher: (id: string) => void;
+}
+
+export const usePatternWatcherStore = create<PatternWatcherStore>()(
+  persist(
+    (set) => ({
+      watchers: [],
+      setWatchers: (watchers) => set({ watchers }),
+      addWatcher: (watcher) =>
+        set((state) => ({ watchers: [...state.watchers, watcher] })),
+      updateWatcher: (id, changes) =>
+        set((state) => ({
+          watchers: state.watchers.map((watcher) =>
+            watcher.id === id ? { ...watcher, ...changes } : watcher
+          ),
+        })),
+      removeWatcher: (id) =>
+        set((state) => ({ watchers: state.watchers.filter((watcher) => watcher.id !== id) })),
+    }),
+    {
+      name: "dispatcher-pattern-watchers",
+      partialize: (state) => ({ watchers: state.watchers }),
+    }
+  )
+);
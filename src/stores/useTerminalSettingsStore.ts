@@ -0,0 +1,20 @@
+import { create } from "zustand";
+import { persist } from "zustand/middleware";
+
+interface TerminalSettingsStore {
+  /** Type a restored tab's last command at its first prompt, without Enter. */
+  offerLastCommand: boolean;
+  setOfferLastCommand: (offer: boolean) => void;
+}
+
+export const useTerminalSettingsStore = create<TerminalSettingsStore>()(
+  persist(
+    (set) => ({
+      offerLastCommand: true,
+      setOfferLastCommand: (offer) => set({ offerLastCommand: offer }),
+    }),
+    {
+      name: "dispatcher-terminal-settings",
+    }
+  )
+);
@@ -10,6 +10,7 @@ interface TerminalStore {
   removeSession: (id: string) => void;
   markTerminalActivity: (id: string) => void;
   markTerminalOutput: (id: string) => void;
+  applyBackendActivity: (id: string, lastOutputAt: number | null) => void;
   setActiveTerminal: (id: string | null) => void;
   setDetectedActivity: (id: string, hasDetectedActivity: boolean) => void;
   setNeedsAttention: (id: string, isNeedsAttention: boolean) => void;
@@ -126,6 +127,29 @@ export const useTerminalStore = create<TerminalStore>()(
         });
       },
 
+      applyBackendActivity: (id, lastOutputAt) => {
+        set((state) => {
+          const session = state.sessions[id];
+          if (!session) {
+            return state;
+          }
+
+          const hasOutput = lastOutputAt !== null && lastOutputAt > session.lastOutputAt;
+          return {
+            sessions: {
+              ...state.sessions,
+              [id]: {
+                ...session,
+                hasBackendActivity: true,
+                ...(hasOutput
+                  ? { hasDetectedActivity: true, lastOutputAt, isLongInactive: false }
+                  : {}),
+              },
+            },
+          };
+        });
+      },
+
       setActiveTerminal: (id) => {
         if (id) {
           set((state) => {
@@ -285,6 +309,9 @@ export const useTerminalStore = create<TerminalStore>()(
             isPossiblyDone: false,
             isLongInactive: false,
             isRecentlyFocused: false,
+            isSecureInput: false,
+            isStartupStalled: false,
+            isHibernated: false,
             backendKind:
               isRestoredTmuxWindow || isRestoredTmuxPane
                 ? session.backendKind
@@ -12,6 +12,16 @@ export interface TerminalSession {
   hasDetectedActivity: boolean;
   lastUserInputAt: number;
   lastOutputAt: number;
+  /** The PTY backend reports output activity for this session. */
+  hasBackendActivity?: boolean;
+  /** The shell is reading a password; input stays out of debug logs. */
+  isSecureInput?: boolean;
+  /** Created incognito: kept out of logs, backups and debug artifacts. */
+  isIncognito?: boolean;
+  /** The shell showed no prompt in time; a safe-mode restart is offered. */
+  isStartupStalled?: boolean;
+  /** The idle shell was shut down; it respawns when the tab is used. */
+  isHibernated?: boolean;
   isNeedsAttention: boolean;
   isPossiblyDone: boolean;
   isLongInactive: boolean;